@@ -0,0 +1,6 @@
+-- Link follow-up feedback (e.g. a comment after an NPS score) to the feedback it follows
+ALTER TABLE feedbacks
+    ADD COLUMN parent_id UUID REFERENCES feedbacks(id) ON DELETE SET NULL;
+
+-- Index for listing the children of a feedback
+CREATE INDEX idx_feedbacks_parent_id ON feedbacks(parent_id) WHERE parent_id IS NOT NULL;
@@ -0,0 +1,8 @@
+-- One feedback per user, service and context.message_id for services in upsert mode
+-- (MESSAGE_ID_UPSERT_SERVICES); other rows are left unconstrained
+ALTER TABLE feedbacks
+    ADD COLUMN unique_per_message BOOLEAN NOT NULL DEFAULT false;
+
+CREATE UNIQUE INDEX idx_feedbacks_user_message_id
+    ON feedbacks (user_id, service, (context->>'message_id'))
+    WHERE unique_per_message;
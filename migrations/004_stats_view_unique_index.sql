@@ -0,0 +1,4 @@
+-- REFRESH MATERIALIZED VIEW CONCURRENTLY (used by refresh_feedback_stats) needs a
+-- unique index covering every row of the view
+CREATE UNIQUE INDEX idx_feedback_stats_unique
+    ON feedback_stats (service, feedback_type, date);
@@ -0,0 +1,7 @@
+-- Optional BCP-47 language tag of the feedback (e.g. "fr", "en-GB"), for routing and
+-- per-language stats
+ALTER TABLE feedbacks
+    ADD COLUMN language VARCHAR(35);
+
+-- Index for filtering and breaking stats down by language
+CREATE INDEX idx_feedbacks_language ON feedbacks(language) WHERE language IS NOT NULL;
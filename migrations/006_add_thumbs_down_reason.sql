@@ -0,0 +1,7 @@
+-- Optional reason for thumbs-down feedback (e.g. "inaccurate", "slow"), taken from the
+-- service's configured taxonomy
+ALTER TABLE feedbacks
+    ADD COLUMN reason TEXT;
+
+-- Index for filtering and counting thumbs-down feedback by reason
+CREATE INDEX idx_feedbacks_service_reason ON feedbacks(service, reason) WHERE reason IS NOT NULL;
@@ -0,0 +1,3 @@
+-- Serves the "latest comments" query: newest non-empty comments of a service
+CREATE INDEX idx_feedbacks_recent_comments ON feedbacks(service, created_at DESC)
+    WHERE comment IS NOT NULL AND comment <> '';
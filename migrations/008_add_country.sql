@@ -0,0 +1,7 @@
+-- ISO 3166-1 alpha-2 country of the submitter, resolved from the source IP when a
+-- GeoIP database is configured
+ALTER TABLE feedbacks
+    ADD COLUMN country CHAR(2);
+
+-- Index for filtering and breaking stats down by country
+CREATE INDEX idx_feedbacks_country ON feedbacks(country) WHERE country IS NOT NULL;
@@ -0,0 +1,31 @@
+-- Deployment-defined feedback types (CUSTOM_FEEDBACK_TYPES): stored with feedback_type
+-- 'custom' and the configured type name in custom_type
+ALTER TYPE feedback_type ADD VALUE IF NOT EXISTS 'custom';
+
+ALTER TABLE feedbacks
+    ADD COLUMN custom_type VARCHAR(64);
+
+-- Index for filtering and breaking stats down by custom type
+CREATE INDEX idx_feedbacks_custom_type ON feedbacks(service, custom_type)
+    WHERE custom_type IS NOT NULL;
+
+-- Custom feedback carries its type name and is checked against that type's rules by the
+-- service; its ratings use the type's own scale, so they are exempt from the 0-10 bound.
+-- The enum value can't be used in the transaction that adds it, hence the text casts.
+ALTER TABLE feedbacks
+    DROP CONSTRAINT chk_rating,
+    DROP CONSTRAINT chk_feedback_data;
+
+ALTER TABLE feedbacks
+    ADD CONSTRAINT chk_rating CHECK (
+        rating IS NULL OR
+        feedback_type::text = 'custom' OR
+        (rating >= 0 AND rating <= 10)
+    ),
+    ADD CONSTRAINT chk_feedback_data CHECK (
+        (feedback_type = 'rating' AND rating IS NOT NULL) OR
+        (feedback_type = 'thumbs' AND thumbs_up IS NOT NULL) OR
+        (feedback_type = 'comment' AND comment IS NOT NULL) OR
+        (feedback_type = 'nps' AND rating IS NOT NULL) OR
+        (feedback_type::text = 'custom' AND custom_type IS NOT NULL)
+    );
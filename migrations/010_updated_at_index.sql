@@ -0,0 +1,2 @@
+-- Serves change polling: feedback updated within a range, oldest change first
+CREATE INDEX idx_feedbacks_updated_at ON feedbacks(updated_at, id);
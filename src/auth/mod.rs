@@ -1,31 +1,210 @@
 use axum::{
     body::Body,
     extract::State,
-    http::{Request, StatusCode},
+    http::{header::WWW_AUTHENTICATE, HeaderValue, Request, StatusCode},
     middleware::Next,
-    response::Response,
+    response::{IntoResponse, Response},
 };
-use jsonwebtoken::{decode, decode_header, Algorithm, DecodingKey, Validation};
+use jsonwebtoken::{decode, decode_header, errors::ErrorKind, Algorithm, DecodingKey, Validation};
 use serde::{Deserialize, Serialize};
 use std::sync::Arc;
 use tokio::sync::RwLock;
 use std::collections::HashMap;
 
+/// Error code for tokens whose authentication is too old for a sensitive endpoint
+const REAUTHENTICATION_REQUIRED: &str = "reauthentication_required";
+
+/// Scope for reading feedback, stats and the other non-mutating endpoints
+pub const SCOPE_READ: &str = "feedback:read";
+/// Scope for submitting, editing and importing feedback, and admin actions
+pub const SCOPE_WRITE: &str = "feedback:write";
+/// Scope for bulk and per-user exports
+pub const SCOPE_EXPORT: &str = "feedback:export";
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Claims {
-    pub sub: String,          // User ID
+    pub sub: String,          // Subject
     pub email: Option<String>,
     pub preferred_username: Option<String>,
     pub exp: usize,
     pub iat: usize,
+    /// When the user last actively authenticated (OIDC `auth_time`), if the issuer sends it
+    #[serde(default)]
+    pub auth_time: Option<usize>,
     pub iss: String,
+    pub realm_access: Option<RealmAccess>,
+    /// Granted OAuth scopes, space-delimited (RFC 8693 `scope` claim)
+    #[serde(default)]
+    pub scope: Option<String>,
+    /// Client the token was issued to (OIDC authorized party)
+    #[serde(default)]
+    pub azp: Option<String>,
+    /// Stable user identifier, resolved by `auth_middleware` from the configured claim
+    #[serde(skip)]
+    pub user_id: String,
+    /// Remaining claims, so any of them can be used as the user identifier
+    #[serde(flatten)]
+    pub extra: HashMap<String, serde_json::Value>,
+}
+
+/// Keycloak realm-level role assignments
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RealmAccess {
+    #[serde(default)]
+    pub roles: Vec<String>,
+}
+
+impl Claims {
+    /// Whether the token carries the given realm role
+    pub fn has_role(&self, role: &str) -> bool {
+        self.realm_access
+            .as_ref()
+            .is_some_and(|access| access.roles.iter().any(|r| r == role))
+    }
+
+    /// Whether the token was granted the given OAuth scope
+    pub fn has_scope(&self, scope: &str) -> bool {
+        self.scope
+            .as_deref()
+            .is_some_and(|granted| granted.split_whitespace().any(|s| s == scope))
+    }
+
+    /// When the user last authenticated: `auth_time`, or `iat` when the issuer omits it
+    pub fn authenticated_at(&self) -> usize {
+        self.auth_time.unwrap_or(self.iat)
+    }
+
+    /// The calling client: `azp`, or the `client_id` claim some issuers use instead
+    pub fn client_id(&self) -> Option<&str> {
+        self.claim_as_str("azp").or_else(|| self.claim_as_str("client_id"))
+    }
+
+    /// Read a string claim by name from the decoded token
+    pub fn claim_as_str(&self, name: &str) -> Option<&str> {
+        match name {
+            "sub" => Some(self.sub.as_str()),
+            "email" => self.email.as_deref(),
+            "preferred_username" => self.preferred_username.as_deref(),
+            "iss" => Some(self.iss.as_str()),
+            "scope" => self.scope.as_deref(),
+            "azp" => self.azp.as_deref(),
+            _ => self.extra.get(name).and_then(|v| v.as_str()),
+        }
+        .filter(|v| !v.is_empty())
+    }
+}
+
+/// Why a bearer token failed validation
+#[derive(Debug)]
+pub enum TokenError {
+    /// The token was well-formed and signed but its `exp` has passed
+    Expired,
+    /// Any other failure (malformed token, unknown key, bad signature or issuer, JWKS errors)
+    Invalid(String),
+}
+
+impl std::fmt::Display for TokenError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            TokenError::Expired => write!(f, "Token has expired"),
+            TokenError::Invalid(msg) => write!(f, "{}", msg),
+        }
+    }
+}
+
+/// Reason `auth_middleware` rejected a request, used as the metric label
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum AuthRejection {
+    MissingHeader,
+    BadScheme,
+    ValidationFailed,
+    Expired,
+}
+
+impl AuthRejection {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            AuthRejection::MissingHeader => "missing_header",
+            AuthRejection::BadScheme => "bad_scheme",
+            AuthRejection::ValidationFailed => "validation_failed",
+            AuthRejection::Expired => "expired",
+        }
+    }
+
+    /// RFC 6750 `error` code; a request without credentials gets none
+    fn bearer_error(&self) -> Option<(&'static str, &'static str)> {
+        match self {
+            AuthRejection::MissingHeader => None,
+            AuthRejection::BadScheme => Some((
+                "invalid_request",
+                "Authorization header must use the Bearer scheme",
+            )),
+            AuthRejection::ValidationFailed => Some(("invalid_token", "The token is invalid")),
+            AuthRejection::Expired => Some(("invalid_token", "The token has expired")),
+        }
+    }
+
+    /// Record the rejection and produce a 401 with a `WWW-Authenticate` challenge
+    fn reject(self, realm: &str) -> Response {
+        crate::metrics::AUTH_REJECTIONS
+            .with_label_values(&[self.as_str()])
+            .inc();
+
+        let mut challenge = format!("Bearer realm=\"{}\"", realm.replace(['"', '\\'], ""));
+        if let Some((error, description)) = self.bearer_error() {
+            challenge.push_str(&format!(
+                ", error=\"{}\", error_description=\"{}\"",
+                error, description
+            ));
+        }
+
+        let mut response = StatusCode::UNAUTHORIZED.into_response();
+        if let Ok(value) = HeaderValue::from_str(&challenge) {
+            response.headers_mut().insert(WWW_AUTHENTICATE, value);
+        }
+        response
+    }
 }
 
 #[derive(Clone)]
 pub struct AuthState {
-    pub keycloak_url: String,
+    pub issuers: Arc<Vec<Issuer>>,
     pub realm: String,
-    pub jwks_cache: Arc<RwLock<JwksCache>>,
+    pub user_id_claim: String,
+}
+
+/// A trusted token issuer (Keycloak realm) with its own JWKS cache
+pub struct Issuer {
+    pub url: String,
+    pub jwks_cache: RwLock<JwksCache>,
+}
+
+impl Issuer {
+    fn new(url: String, cache_ttl: u64) -> Self {
+        Self {
+            url,
+            jwks_cache: RwLock::new(JwksCache::new(cache_ttl)),
+        }
+    }
+
+    /// `iss` values accepted for this realm; allows both localhost and container
+    /// name for dev environments
+    fn accepted_issuers(&self) -> [String; 2] {
+        [
+            self.url.clone(),
+            self.url.replace("keycloak:8180", "localhost:8180"),
+        ]
+    }
+
+    fn matches(&self, iss: &str) -> bool {
+        self.accepted_issuers().iter().any(|accepted| accepted == iss)
+    }
+}
+
+/// Just the issuer claim, read before the signature is checked to pick the JWKS
+#[derive(Debug, Deserialize)]
+struct UnverifiedIssuer {
+    iss: String,
 }
 
 pub struct JwksCache {
@@ -63,18 +242,28 @@ struct JwkKey {
 }
 
 impl AuthState {
-    pub fn new(keycloak_url: String, realm: String, cache_ttl: u64) -> Self {
+    pub fn new(
+        keycloak_urls: Vec<String>,
+        realm: String,
+        cache_ttl: u64,
+        user_id_claim: String,
+    ) -> Self {
+        let issuers = keycloak_urls
+            .into_iter()
+            .map(|url| Issuer::new(url, cache_ttl))
+            .collect();
+
         Self {
-            keycloak_url,
+            issuers: Arc::new(issuers),
             realm,
-            jwks_cache: Arc::new(RwLock::new(JwksCache::new(cache_ttl))),
+            user_id_claim,
         }
     }
 
-    async fn fetch_jwks(&self) -> Result<HashMap<String, DecodingKey>, String> {
+    async fn fetch_jwks(issuer: &Issuer) -> Result<HashMap<String, DecodingKey>, String> {
         let url = format!(
             "{}/protocol/openid-connect/certs",
-            self.keycloak_url
+            issuer.url
         );
 
         let response = reqwest::get(&url)
@@ -103,10 +292,10 @@ impl AuthState {
         Ok(keys)
     }
 
-    pub async fn get_decoding_key(&self, kid: &str) -> Result<DecodingKey, String> {
+    pub async fn get_decoding_key(issuer: &Issuer, kid: &str) -> Result<DecodingKey, String> {
         // Check if cache is expired
         {
-            let cache = self.jwks_cache.read().await;
+            let cache = issuer.jwks_cache.read().await;
             if !cache.is_expired() {
                 if let Some(key) = cache.keys.get(kid) {
                     return Ok(key.clone());
@@ -115,7 +304,7 @@ impl AuthState {
         }
 
         // Refresh cache
-        let keys = self.fetch_jwks().await?;
+        let keys = Self::fetch_jwks(issuer).await?;
         let key = keys
             .get(kid)
             .ok_or_else(|| format!("Key with kid '{}' not found", kid))?
@@ -123,7 +312,22 @@ impl AuthState {
 
         // Update cache
         {
-            let mut cache = self.jwks_cache.write().await;
+            let mut cache = issuer.jwks_cache.write().await;
+
+            // The first load of an empty cache is not a rotation
+            if !cache.keys.is_empty() {
+                for new_kid in keys.keys().filter(|k| !cache.keys.contains_key(*k)) {
+                    tracing::info!(
+                        issuer = %issuer.url,
+                        kid = %new_kid,
+                        "New JWKS signing key discovered"
+                    );
+                    crate::metrics::JWKS_NEW_KEYS
+                        .with_label_values(&[&issuer.url])
+                        .inc();
+                }
+            }
+
             cache.keys = keys;
             cache.last_update = std::time::Instant::now();
         }
@@ -131,53 +335,164 @@ impl AuthState {
         Ok(key)
     }
 
-    pub async fn validate_token(&self, token: &str) -> Result<Claims, String> {
+    pub async fn validate_token(&self, token: &str) -> Result<Claims, TokenError> {
         let header = decode_header(token)
-            .map_err(|e| format!("Invalid token header: {}", e))?;
+            .map_err(|e| TokenError::Invalid(format!("Invalid token header: {}", e)))?;
 
         let kid = header
             .kid
-            .ok_or_else(|| "Token header missing 'kid'".to_string())?;
+            .ok_or_else(|| TokenError::Invalid("Token header missing 'kid'".to_string()))?;
 
-        let key = self.get_decoding_key(&kid).await?;
+        // Pick the realm from the (not yet trusted) issuer claim so each token only
+        // ever triggers a JWKS refresh on its own realm
+        let iss = Self::peek_issuer(token)?;
+        let issuer = self
+            .issuers
+            .iter()
+            .find(|issuer| issuer.matches(&iss))
+            .ok_or_else(|| TokenError::Invalid(format!("Untrusted token issuer '{}'", iss)))?;
+
+        let key = Self::get_decoding_key(issuer, &kid)
+            .await
+            .map_err(TokenError::Invalid)?;
 
         let mut validation = Validation::new(Algorithm::RS256);
-        // Allow both localhost and container name for dev environments
-        let localhost_url = self.keycloak_url.replace("keycloak:8180", "localhost:8180");
-        validation.set_issuer(&[&self.keycloak_url, &localhost_url]);
+        let accepted: Vec<String> = self
+            .issuers
+            .iter()
+            .flat_map(|issuer| issuer.accepted_issuers())
+            .collect();
+        validation.set_issuer(&accepted);
 
-        let token_data = decode::<Claims>(token, &key, &validation)
-            .map_err(|e| format!("Token validation failed: {}", e))?;
+        let token_data = decode::<Claims>(token, &key, &validation).map_err(|e| match e.kind() {
+            ErrorKind::ExpiredSignature => TokenError::Expired,
+            _ => TokenError::Invalid(format!("Token validation failed: {}", e)),
+        })?;
 
         Ok(token_data.claims)
     }
+
+    /// Read the `iss` claim without verifying the signature
+    fn peek_issuer(token: &str) -> Result<String, TokenError> {
+        let mut validation = Validation::new(Algorithm::RS256);
+        validation.insecure_disable_signature_validation();
+        validation.validate_exp = false;
+        validation.required_spec_claims.clear();
+
+        decode::<UnverifiedIssuer>(token, &DecodingKey::from_secret(&[]), &validation)
+            .map(|data| data.claims.iss)
+            .map_err(|e| TokenError::Invalid(format!("Token missing issuer: {}", e)))
+    }
 }
 
 pub async fn auth_middleware(
     State(auth_state): State<AuthState>,
     mut req: Request<Body>,
     next: Next,
-) -> Result<Response, StatusCode> {
+) -> Result<Response, Response> {
     let auth_header = req
         .headers()
         .get("authorization")
         .and_then(|h| h.to_str().ok())
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+        .ok_or_else(|| AuthRejection::MissingHeader.reject(&auth_state.realm))?;
 
     let token = auth_header
         .strip_prefix("Bearer ")
-        .ok_or(StatusCode::UNAUTHORIZED)?;
+        .ok_or_else(|| AuthRejection::BadScheme.reject(&auth_state.realm))?;
 
-    let claims = auth_state
+    let mut claims = auth_state
         .validate_token(token)
         .await
         .map_err(|e| {
             tracing::error!("Token validation failed: {}", e);
-            StatusCode::UNAUTHORIZED
+            let rejection = match e {
+                TokenError::Expired => AuthRejection::Expired,
+                TokenError::Invalid(_) => AuthRejection::ValidationFailed,
+            };
+            rejection.reject(&auth_state.realm)
         })?;
 
+    // Resolve the user identifier once so every handler attributes feedback the same way
+    claims.user_id = claims
+        .claim_as_str(&auth_state.user_id_claim)
+        .ok_or_else(|| {
+            tracing::error!(
+                claim = %auth_state.user_id_claim,
+                "Token is missing the configured user id claim"
+            );
+            AuthRejection::ValidationFailed.reject(&auth_state.realm)
+        })?
+        .to_string();
+
     // Insert claims into request extensions for handlers to access
     req.extensions_mut().insert(claims);
 
     Ok(next.run(req).await)
 }
+
+/// Reject requests whose token lacks `scope` with a 403 naming it. Layered inside
+/// `auth_middleware`, which provides the claims.
+pub async fn require_scope(
+    State(scope): State<&'static str>,
+    req: Request<Body>,
+    next: Next,
+) -> crate::error::Result<Response> {
+    let granted = req
+        .extensions()
+        .get::<Claims>()
+        .is_some_and(|claims| claims.has_scope(scope));
+    if !granted {
+        return Err(crate::error::AppError::Forbidden(format!(
+            "Missing required scope '{}'",
+            scope
+        )));
+    }
+
+    Ok(next.run(req).await)
+}
+
+/// Reject tokens whose user authenticated more than `max_age_secs` ago with a 403
+/// `reauthentication_required`, so sensitive endpoints need a recent login (step-up).
+/// Layered inside `auth_middleware`, which provides the claims.
+pub async fn require_fresh_auth(
+    State(max_age_secs): State<u64>,
+    req: Request<Body>,
+    next: Next,
+) -> Response {
+    let now = std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map(|d| d.as_secs())
+        .unwrap_or_default();
+    let age_secs = req
+        .extensions()
+        .get::<Claims>()
+        .map(|claims| now.saturating_sub(claims.authenticated_at() as u64));
+
+    if age_secs.is_some_and(|age| age <= max_age_secs) {
+        return next.run(req).await;
+    }
+
+    tracing::warn!(
+        age_secs = ?age_secs,
+        max_age_secs = max_age_secs,
+        path = %req.uri().path(),
+        "Token authentication too old for this endpoint"
+    );
+    let description = format!("Sign in again: authentication must be under {}s old", max_age_secs);
+    let mut response = (
+        StatusCode::FORBIDDEN,
+        axum::Json(serde_json::json!({
+            "error": REAUTHENTICATION_REQUIRED,
+            "details": description,
+        })),
+    )
+        .into_response();
+    let challenge = format!(
+        "Bearer error=\"{}\", error_description=\"{}\", max_age={}",
+        REAUTHENTICATION_REQUIRED, description, max_age_secs
+    );
+    if let Ok(value) = HeaderValue::from_str(&challenge) {
+        response.headers_mut().insert(WWW_AUTHENTICATE, value);
+    }
+    response
+}
@@ -1,16 +1,92 @@
 use anyhow::{Context, Result};
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
+use std::collections::{HashMap, HashSet};
+use std::ops::RangeInclusive;
+use std::str::FromStr;
 
 #[derive(Debug, Clone, Deserialize)]
 pub struct Config {
     pub host: String,
     pub port: u16,
     pub database_url: String,
-    pub keycloak_url: String,
+    pub db_warmup_connections: u32,
+    pub db_idle_timeout_secs: u64,
+    pub db_max_lifetime_secs: u64,
+    pub metrics_init_retries: u32,
+    pub metrics_init_required: bool,
+    pub metrics_namespace: Option<String>,
+    pub keycloak_urls: Vec<String>,
     pub keycloak_realm: String,
     pub keycloak_jwks_cache_ttl: u64,
+    pub user_id_claim: String,
+    pub admin_role: String,
+    pub impersonation_scope: String,
+    pub db_timeout_override_max_ms: u64,
+    pub require_scopes: bool,
+    pub default_service: Option<String>,
+    pub service_from_client: bool,
+    pub client_services: HashMap<String, String>,
     pub webhook_urls: Vec<String>,
+    pub service_webhook_urls: HashMap<String, Vec<String>>,
+    pub webhook_allow_insecure: bool,
+    pub webhook_format: WebhookFormat,
+    pub webhook_headers: HashMap<String, String>,
+    pub webhook_max_concurrency: usize,
     pub export_max_records: usize,
+    pub export_excluded_context_keys: Vec<String>,
+    pub indexed_context_keys: Vec<String>,
+    pub context_filter_indexed_only: bool,
+    pub export_signing_key: Option<String>,
+    pub export_max_auth_age_secs: u64,
+    pub feedback_edit_window_secs: u64,
+    pub comment_overflow: CommentOverflow,
+    pub rating_out_of_range: RatingOutOfRange,
+    pub custom_feedback_types: HashMap<String, CustomFeedbackType>,
+    pub disposable_email_domains: HashSet<String>,
+    pub disabled_services: HashSet<String>,
+    pub context_sample_rates: HashMap<String, f64>,
+    pub context_max_keys: HashMap<String, usize>,
+    pub context_max_top_level_keys: usize,
+    pub context_max_bytes: usize,
+    pub context_schemas: HashMap<String, serde_json::Value>,
+    pub thumbs_down_reasons: HashMap<String, Vec<String>>,
+    pub require_thumbs_down_reason: bool,
+    pub geoip_db_path: Option<String>,
+    pub trusted_proxy_hops: usize,
+    pub message_id_upsert_services: Vec<String>,
+    pub retention_days: u32,
+    pub retention_purge_interval_secs: u64,
+    pub retention_purge_batch_size: i64,
+    pub retention_purge_batch_pause_ms: u64,
+    pub import_max_future_skew_secs: i64,
+    pub import_earliest_date: Option<DateTime<Utc>>,
+    pub import_batch_size: usize,
+    pub import_max_concurrency: usize,
+    pub stats_cache_ttl_secs: u64,
+    pub health_cache_ttl_ms: u64,
+    pub service_groups: HashMap<String, String>,
+    pub health_signal_window_hours: i64,
+    pub health_thumbs_up_ratio_warning: f64,
+    pub health_thumbs_up_ratio_critical: f64,
+    pub health_rating_warning: f64,
+    pub health_rating_critical: f64,
+    pub thumbs_up_score: f64,
+    pub thumbs_down_score: f64,
+    pub metrics_timezone: String,
+    pub stats_warmup_services: Vec<String>,
+    pub comment_text_search_config: String,
+    pub request_id_header: String,
+    pub log_error_request_bodies: bool,
+    pub response_cache_max_age_secs: u64,
+    pub empty_query_no_content: bool,
+    pub sample_size: i64,
+    pub json_pretty: bool,
+    pub slow_request_ms: u64,
+    pub enable_export: bool,
+    pub enable_stats: bool,
+    pub enable_import: bool,
+    pub enable_bulk: bool,
     pub allowed_origins: Vec<String>,
 }
 
@@ -27,8 +103,59 @@ impl Config {
         let database_url = std::env::var("DATABASE_URL")
             .context("DATABASE_URL must be set")?;
 
-        let keycloak_url = std::env::var("KEYCLOAK_URL")
-            .context("KEYCLOAK_URL must be set")?;
+        // Connections opened at startup and kept open as the pool minimum (0 = lazy)
+        let db_warmup_connections = std::env::var("DB_WARMUP")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+
+        // Pooled connections are closed after sitting idle this long, and replaced after
+        // being open this long, before a pooler or managed Postgres drops them (0 = never)
+        let db_idle_timeout_secs = std::env::var("DB_IDLE_TIMEOUT_SECS")
+            .unwrap_or_else(|_| crate::db::DEFAULT_IDLE_TIMEOUT_SECS.to_string())
+            .parse()
+            .unwrap_or(crate::db::DEFAULT_IDLE_TIMEOUT_SECS);
+        let db_max_lifetime_secs = std::env::var("DB_MAX_LIFETIME_SECS")
+            .unwrap_or_else(|_| crate::db::DEFAULT_MAX_LIFETIME_SECS.to_string())
+            .parse()
+            .unwrap_or(crate::db::DEFAULT_MAX_LIFETIME_SECS);
+
+        // Retries (with exponential backoff) of seeding the counters from the database at
+        // startup; when not required, a final failure starts with empty counters instead
+        let metrics_init_retries = std::env::var("METRICS_INIT_RETRIES")
+            .unwrap_or_else(|_| "3".to_string())
+            .parse()
+            .unwrap_or(3);
+        let metrics_init_required = env_flag("METRICS_INIT_REQUIRED", true);
+
+        // Prefix for every exposed metric name, e.g. `tenantx` for `tenantx_feedback_total`
+        let metrics_namespace = std::env::var("METRICS_NAMESPACE")
+            .ok()
+            .map(|namespace| namespace.trim().to_string())
+            .filter(|namespace| !namespace.is_empty());
+        if let Some(namespace) = &metrics_namespace {
+            let mut chars = namespace.chars();
+            let valid = chars.next().is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+                && chars.all(|c| c.is_ascii_alphanumeric() || c == '_');
+            if !valid {
+                anyhow::bail!(
+                    "Invalid METRICS_NAMESPACE '{}': use letters, digits and underscores, \
+                     not starting with a digit",
+                    namespace
+                );
+            }
+        }
+
+        // One or more realm URLs (comma-separated); tokens from any of them are accepted
+        let keycloak_urls: Vec<String> = std::env::var("KEYCLOAK_URL")
+            .context("KEYCLOAK_URL must be set")?
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.trim().trim_end_matches('/').to_string())
+            .collect();
+        if keycloak_urls.is_empty() {
+            anyhow::bail!("KEYCLOAK_URL must contain at least one realm URL");
+        }
 
         let keycloak_realm = std::env::var("KEYCLOAK_REALM")
             .unwrap_or_else(|_| "master".to_string());
@@ -38,18 +165,439 @@ impl Config {
             .parse()
             .unwrap_or(3600);
 
+        // JWT claim used as the stable user identifier (e.g. "sub" or "preferred_username")
+        let user_id_claim = std::env::var("USER_ID_CLAIM")
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "sub".to_string());
+
+        // Realm role granting access to other users' data
+        let admin_role = std::env::var("ADMIN_ROLE")
+            .unwrap_or_else(|_| "admin".to_string());
+
+        // Scope (or realm role) letting a service account submit feedback on behalf of a user
+        let impersonation_scope = std::env::var("IMPERSONATION_SCOPE")
+            .unwrap_or_else(|_| "feedback:impersonate".to_string());
+
+        // Upper bound for the statement timeout admins may request per query with the
+        // X-DB-Timeout-Ms header (0 = header not accepted)
+        let db_timeout_override_max_ms = std::env::var("DB_TIMEOUT_OVERRIDE_MAX_MS")
+            .unwrap_or_else(|_| "60000".to_string())
+            .parse()
+            .unwrap_or(60000);
+
+        // Gate each endpoint on its OAuth scope (feedback:read, feedback:write,
+        // feedback:export) on top of authentication
+        let require_scopes = env_flag("REQUIRE_SCOPES", false);
+
+        // Service assigned to submissions that arrive without one (legacy clients)
+        let default_service = std::env::var("DEFAULT_SERVICE")
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+
+        // Attribute submissions to the calling client's service (from the token's `azp` or
+        // `client_id`), overriding any `service` they send. CLIENT_SERVICES maps client IDs
+        // to services, e.g. `visio-web=visio`; unmapped clients use their ID as the service.
+        let service_from_client = env_flag("SERVICE_FROM_CLIENT", false);
+        let client_services = parse_service_map("CLIENT_SERVICES")?;
+
+        // Plain http:// webhooks are only accepted when explicitly allowed (local dev)
+        let webhook_allow_insecure = std::env::var("WEBHOOK_ALLOW_INSECURE")
+            .unwrap_or_else(|_| "false".to_string())
+            .parse()
+            .unwrap_or(false);
+
         let webhook_urls = std::env::var("WEBHOOK_URLS")
             .unwrap_or_default()
             .split(',')
-            .filter(|s| !s.is_empty())
-            .map(|s| s.trim().to_string())
-            .collect();
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| validate_webhook_url(s.trim(), webhook_allow_insecure))
+            .collect::<Result<Vec<_>>>()?;
+        let webhook_urls = dedup_webhook_urls(webhook_urls);
+
+        // Per-service webhook routing, e.g. `visio=https://a,https://b;docs=https://c`;
+        // services not listed use WEBHOOK_URLS
+        let service_webhook_urls = parse_service_webhook_urls(
+            &std::env::var("SERVICE_WEBHOOK_URLS").unwrap_or_default(),
+            webhook_allow_insecure,
+        )?;
+
+        // Body of webhook deliveries: the raw payload, or a CloudEvents 1.0 envelope
+        let webhook_format = std::env::var("WEBHOOK_FORMAT")
+            .unwrap_or_else(|_| "raw".to_string())
+            .parse()?;
+
+        // Static headers added to every webhook delivery, e.g. `X-Tenant-Id=acme`, so
+        // receivers can route or authorize deliveries without reading the body
+        let webhook_headers =
+            parse_webhook_headers(&std::env::var("WEBHOOK_HEADERS").unwrap_or_default())?;
+
+        // Webhook deliveries in flight across all feedback, so spikes don't flood receivers
+        let webhook_max_concurrency = std::env::var("WEBHOOK_MAX_CONCURRENCY")
+            .unwrap_or_else(|_| "16".to_string())
+            .parse::<usize>()
+            .unwrap_or(16)
+            .max(1);
 
         let export_max_records = std::env::var("EXPORT_MAX_RECORDS")
             .unwrap_or_else(|_| "10000".to_string())
             .parse()
             .unwrap_or(10000);
 
+        // Context keys that must never leave the system through exports
+        let export_excluded_context_keys = std::env::var("EXPORT_EXCLUDED_CONTEXT_KEYS")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.trim().to_string())
+            .collect();
+
+        // Sunset services: new feedback for them is rejected, existing feedback stays
+        // queryable and exportable
+        let disabled_services = std::env::var("DISABLED_SERVICES")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.trim().to_string())
+            .collect();
+
+        // Context keys given an expression index at startup, so filtering on them stays
+        // fast; filters on other keys are logged, or rejected with CONTEXT_FILTER_INDEXED_ONLY
+        let indexed_context_keys =
+            parse_indexed_context_keys(&std::env::var("INDEXED_CONTEXT_KEYS").unwrap_or_default())?;
+        let context_filter_indexed_only = env_flag("CONTEXT_FILTER_INDEXED_ONLY", false);
+
+        // Shared secret for the HMAC-SHA256 signature sent with exports (unset = unsigned)
+        let export_signing_key = std::env::var("EXPORT_SIGNING_KEY")
+            .ok()
+            .filter(|s| !s.is_empty());
+
+        // Step-up for exports: the user must have authenticated (auth_time, else iat)
+        // within this many seconds (0 = any valid token)
+        let export_max_auth_age_secs = std::env::var("EXPORT_MAX_AUTH_AGE_SECS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+
+        // How long after submission feedback may still be edited (0 = unlimited)
+        let feedback_edit_window_secs = std::env::var("FEEDBACK_EDIT_WINDOW_SECS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+
+        // What to do with comments over the maximum length
+        let comment_overflow = std::env::var("COMMENT_OVERFLOW")
+            .unwrap_or_else(|_| "reject".to_string())
+            .parse()?;
+
+        // What to do with ratings and NPS scores outside their valid range
+        let rating_out_of_range = match std::env::var("RATING_OUT_OF_RANGE") {
+            Ok(value) => value.parse()?,
+            Err(_) => RatingOutOfRange::default(),
+        };
+
+        // Deployment-defined feedback types, e.g. `effort=1..7,bug_report=comment`; submitted
+        // as `"feedback_type": "custom"` with the name in `custom_type`
+        let custom_feedback_types: HashMap<String, CustomFeedbackType> =
+            parse_service_map("CUSTOM_FEEDBACK_TYPES")?;
+        if let Some(name) = custom_feedback_types.keys().find(|name| {
+            name.len() > 64
+                || !name
+                    .chars()
+                    .all(|c| c.is_ascii_lowercase() || c.is_ascii_digit() || c == '_' || c == '-')
+        }) {
+            anyhow::bail!(
+                "Invalid CUSTOM_FEEDBACK_TYPES name '{}': expected up to 64 lowercase letters, \
+                 digits, '_' or '-'",
+                name
+            );
+        }
+
+        // Reject feedback from users whose email is at one of these domains (or a subdomain);
+        // one domain per line in the file, `#` starts a comment
+        let disposable_email_domains = match std::env::var("DISPOSABLE_EMAIL_DOMAINS_FILE") {
+            Ok(path) if !path.trim().is_empty() => load_domain_list(path.trim())?,
+            _ => HashSet::new(),
+        };
+
+        // Per-service storage policy for `context`: keep it for only a fraction of
+        // submissions (0.0-1.0) and/or cap it to a number of top-level keys
+        let context_sample_rates: HashMap<String, f64> = parse_service_map("CONTEXT_SAMPLE_RATES")?;
+        if let Some((service, rate)) = context_sample_rates
+            .iter()
+            .find(|(_, rate)| !(0.0..=1.0).contains(*rate))
+        {
+            anyhow::bail!(
+                "Invalid CONTEXT_SAMPLE_RATES entry for '{}': {} is not between 0.0 and 1.0",
+                service,
+                rate
+            );
+        }
+        let context_max_keys = parse_service_map("CONTEXT_MAX_KEYS")?;
+
+        // Submissions whose `context` has more top-level keys than this are rejected,
+        // which catches clients dumping whole request objects into it
+        let context_max_top_level_keys = std::env::var("CONTEXT_MAX_TOP_LEVEL_KEYS")
+            .unwrap_or_else(|_| "50".to_string())
+            .parse()
+            .unwrap_or(50)
+            .max(1);
+
+        // Largest serialized `context` accepted, checked when the body (or an edit) is read
+        // so an oversized blob is rejected with 400 well below the 1MB body limit. Never
+        // below 2 bytes, the size of `{}`, so a stray 0 cannot reject every context.
+        let context_max_bytes = std::env::var("CONTEXT_MAX_BYTES")
+            .unwrap_or_else(|_| "16384".to_string())
+            .parse()
+            .unwrap_or(16384)
+            .max(2);
+
+        // JSON Schemas that a service's `context` must match, one `<service>.json` file
+        // per service; services without a file accept any context
+        let context_schemas = match std::env::var("CONTEXT_SCHEMA_DIR") {
+            Ok(dir) if !dir.trim().is_empty() => load_context_schemas(dir.trim())?,
+            _ => HashMap::new(),
+        };
+
+        // Per-service taxonomy of thumbs-down reasons, e.g. `chatbot=inaccurate|slow|rude`;
+        // services without one accept no reason
+        let thumbs_down_reasons = parse_service_map::<String>("THUMBS_DOWN_REASONS")?
+            .into_iter()
+            .map(|(service, reasons)| {
+                let reasons = reasons
+                    .split('|')
+                    .filter(|r| !r.trim().is_empty())
+                    .map(|r| r.trim().to_string())
+                    .collect();
+                (service, reasons)
+            })
+            .collect();
+        // Whether thumbs-down feedback must carry a reason on services with a taxonomy
+        let require_thumbs_down_reason = env_flag("REQUIRE_THUMBS_DOWN_REASON", false);
+
+        // MaxMind GeoLite2/GeoIP2 country database used to tag new feedback with the
+        // submitter's country (unset = no enrichment)
+        let geoip_db_path = std::env::var("GEOIP_DB_PATH")
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty());
+        if let Some(path) = &geoip_db_path {
+            maxminddb::Reader::open_readfile(path)
+                .with_context(|| format!("Invalid GEOIP_DB_PATH '{}'", path))?;
+        }
+
+        // Reverse proxies in front of the service, whose Forwarded / X-Forwarded-For
+        // entries are trusted to locate the submitter (0 = use the peer address)
+        let trusted_proxy_hops = std::env::var("TRUSTED_PROXY_HOPS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+
+        // Services where a user's repeat submission for the same context.message_id
+        // updates their earlier feedback instead of adding another one
+        let message_id_upsert_services = std::env::var("MESSAGE_ID_UPSERT_SERVICES")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.trim().to_string())
+            .collect();
+
+        // Feedback older than this many days is purged by a background job (0 = keep forever).
+        // Rows are deleted in small batches with a pause in between to avoid long locks.
+        let retention_days = std::env::var("RETENTION_DAYS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+
+        let retention_purge_interval_secs = std::env::var("RETENTION_PURGE_INTERVAL_SECS")
+            .unwrap_or_else(|_| "3600".to_string())
+            .parse()
+            .unwrap_or(3600);
+
+        let retention_purge_batch_size = std::env::var("RETENTION_PURGE_BATCH_SIZE")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse::<i64>()
+            .unwrap_or(1000)
+            .max(1);
+
+        let retention_purge_batch_pause_ms = std::env::var("RETENTION_PURGE_BATCH_PAUSE_MS")
+            .unwrap_or_else(|_| "100".to_string())
+            .parse()
+            .unwrap_or(100);
+
+        // Guards on imported timestamps: tolerate a little clock skew into the future,
+        // and optionally reject anything older than an earliest plausible date
+        let import_max_future_skew_secs = std::env::var("IMPORT_MAX_FUTURE_SKEW_SECS")
+            .unwrap_or_else(|_| "300".to_string())
+            .parse()
+            .unwrap_or(300);
+
+        let import_earliest_date = std::env::var("IMPORT_EARLIEST_DATE")
+            .ok()
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| {
+                DateTime::parse_from_rfc3339(s.trim())
+                    .map(|d| d.with_timezone(&Utc))
+                    .context("Invalid IMPORT_EARLIEST_DATE (expected RFC 3339)")
+            })
+            .transpose()?;
+
+        // Imports are written in transactions of this many rows, with at most this many
+        // batches in flight at once, so large files do not swamp the database
+        let import_batch_size = std::env::var("IMPORT_BATCH_SIZE")
+            .unwrap_or_else(|_| "500".to_string())
+            .parse::<usize>()
+            .unwrap_or(500)
+            .max(1);
+
+        let import_max_concurrency = std::env::var("IMPORT_MAX_CONCURRENCY")
+            .unwrap_or_else(|_| "4".to_string())
+            .parse::<usize>()
+            .unwrap_or(4)
+            .max(1);
+
+        // How long computed stats are served from memory (0 = always query the database)
+        let stats_cache_ttl_secs = std::env::var("STATS_CACHE_TTL_SECS")
+            .unwrap_or_else(|_| "0".to_string())
+            .parse()
+            .unwrap_or(0);
+
+        // How long a /health database check is reused by later probes (0 = check every time)
+        let health_cache_ttl_ms = std::env::var("HEALTH_CACHE_TTL_MS")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse()
+            .unwrap_or(1000);
+
+        // Logical products for grouped stats, e.g. `visio=video,visio-mobile=video`
+        let service_groups = parse_service_map("SERVICE_GROUPS")?;
+
+        // Rolling window and floors for the per-service health signal: a thumbs-up ratio or
+        // average rating below the warning (critical) threshold turns it warning (critical)
+        let health_signal_window_hours = std::env::var("HEALTH_SIGNAL_WINDOW_HOURS")
+            .unwrap_or_else(|_| "24".to_string())
+            .parse::<i64>()
+            .unwrap_or(24)
+            .max(1);
+        let threshold = |var: &str, default: f64| {
+            std::env::var(var)
+                .ok()
+                .and_then(|s| s.trim().parse().ok())
+                .unwrap_or(default)
+        };
+        let health_thumbs_up_ratio_warning = threshold("HEALTH_THUMBS_UP_RATIO_WARNING", 0.7);
+        let health_thumbs_up_ratio_critical = threshold("HEALTH_THUMBS_UP_RATIO_CRITICAL", 0.5);
+        let health_rating_warning = threshold("HEALTH_RATING_WARNING", 3.5);
+        let health_rating_critical = threshold("HEALTH_RATING_CRITICAL", 2.5);
+        if health_thumbs_up_ratio_critical > health_thumbs_up_ratio_warning {
+            anyhow::bail!(
+                "HEALTH_THUMBS_UP_RATIO_CRITICAL ({}) must not exceed \
+                 HEALTH_THUMBS_UP_RATIO_WARNING ({})",
+                health_thumbs_up_ratio_critical,
+                health_thumbs_up_ratio_warning
+            );
+        }
+        if health_rating_critical > health_rating_warning {
+            anyhow::bail!(
+                "HEALTH_RATING_CRITICAL ({}) must not exceed HEALTH_RATING_WARNING ({})",
+                health_rating_critical,
+                health_rating_warning
+            );
+        }
+
+        // Rating-scale scores thumbs count as in the stats satisfaction score
+        let thumbs_up_score = std::env::var("THUMBS_UP_SCORE")
+            .unwrap_or_else(|_| "5".to_string())
+            .parse::<f64>()
+            .unwrap_or(5.0);
+        let thumbs_down_score = std::env::var("THUMBS_DOWN_SCORE")
+            .unwrap_or_else(|_| "1".to_string())
+            .parse::<f64>()
+            .unwrap_or(1.0);
+
+        // IANA timezone for the hour-of-day feedback metric
+        let metrics_timezone = std::env::var("METRICS_TIMEZONE")
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "UTC".to_string());
+        if metrics_timezone.parse::<chrono_tz::Tz>().is_err() {
+            anyhow::bail!(
+                "Invalid METRICS_TIMEZONE '{}': expected an IANA name such as Europe/Paris",
+                metrics_timezone
+            );
+        }
+
+        // Services whose stats are computed and cached during startup
+        let stats_warmup_services = std::env::var("STATS_WARMUP_SERVICES")
+            .unwrap_or_default()
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| s.trim().to_string())
+            .collect();
+
+        // Postgres text search configuration used to tokenize comments for word clouds;
+        // it decides the stopword list and stemming
+        let comment_text_search_config = std::env::var("COMMENT_TEXT_SEARCH_CONFIG")
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "english".to_string());
+        if !comment_text_search_config
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '_')
+        {
+            anyhow::bail!(
+                "Invalid COMMENT_TEXT_SEARCH_CONFIG '{}': expected a name such as english",
+                comment_text_search_config
+            );
+        }
+
+        // Header the request ID is read from (to propagate it) and echoed in
+        let request_id_header = std::env::var("REQUEST_ID_HEADER")
+            .ok()
+            .map(|s| s.trim().to_string())
+            .filter(|s| !s.is_empty())
+            .unwrap_or_else(|| "X-Request-ID".to_string());
+        axum::http::HeaderName::from_bytes(request_id_header.as_bytes())
+            .with_context(|| format!("Invalid REQUEST_ID_HEADER '{}'", request_id_header))?;
+
+        // Debug aid: log a redacted, truncated copy of the body of failed requests
+        let log_error_request_bodies = env_flag("LOG_ERROR_REQUEST_BODIES", false);
+
+        // How long clients may cache stats and query responses (0 = no Cache-Control)
+        let response_cache_max_age_secs = std::env::var("RESPONSE_CACHE_MAX_AGE_SECS")
+            .unwrap_or_else(|_| "10".to_string())
+            .parse()
+            .unwrap_or(10);
+
+        // Requests taking longer than this are also logged at warn with slow_request=true
+        // (0 = disabled)
+        let slow_request_ms = std::env::var("SLOW_REQUEST_MS")
+            .unwrap_or_else(|_| "1000".to_string())
+            .parse()
+            .unwrap_or(1000);
+
+        // Answer `GET /feedbacks` with 204 No Content instead of `200 []` when nothing matches
+        let empty_query_no_content = env_flag("EMPTY_QUERY_NO_CONTENT", false);
+
+        // Rows returned by `GET /feedbacks?sample=true` when the request sets no limit
+        let sample_size = std::env::var("SAMPLE_SIZE")
+            .unwrap_or_else(|_| "50".to_string())
+            .parse::<i64>()
+            .unwrap_or(50)
+            .clamp(1, 1000);
+
+        // Pretty-print JSON responses by default (clients can still ask with ?pretty=true)
+        let json_pretty = env_flag("JSON_PRETTY", false);
+
+        // Optional endpoint families; disabled ones answer 404
+        let enable_export = env_flag("ENABLE_EXPORT", true);
+        let enable_stats = env_flag("ENABLE_STATS", true);
+        let enable_import = env_flag("ENABLE_IMPORT", true);
+        let enable_bulk = env_flag("ENABLE_BULK", true);
+
         let allowed_origins = std::env::var("ALLOWED_ORIGINS")
             .unwrap_or_default()
             .split(',')
@@ -61,11 +609,83 @@ impl Config {
             host,
             port,
             database_url,
-            keycloak_url,
+            db_warmup_connections,
+            db_idle_timeout_secs,
+            db_max_lifetime_secs,
+            metrics_init_retries,
+            metrics_init_required,
+            metrics_namespace,
+            keycloak_urls,
             keycloak_realm,
             keycloak_jwks_cache_ttl,
+            user_id_claim,
+            admin_role,
+            impersonation_scope,
+            db_timeout_override_max_ms,
+            require_scopes,
+            default_service,
+            service_from_client,
+            client_services,
             webhook_urls,
+            service_webhook_urls,
+            webhook_allow_insecure,
+            webhook_format,
+            webhook_headers,
+            webhook_max_concurrency,
             export_max_records,
+            export_excluded_context_keys,
+            indexed_context_keys,
+            context_filter_indexed_only,
+            export_signing_key,
+            export_max_auth_age_secs,
+            feedback_edit_window_secs,
+            comment_overflow,
+            rating_out_of_range,
+            custom_feedback_types,
+            disposable_email_domains,
+            disabled_services,
+            context_sample_rates,
+            context_max_keys,
+            context_max_top_level_keys,
+            context_max_bytes,
+            context_schemas,
+            thumbs_down_reasons,
+            require_thumbs_down_reason,
+            geoip_db_path,
+            trusted_proxy_hops,
+            message_id_upsert_services,
+            retention_days,
+            retention_purge_interval_secs,
+            retention_purge_batch_size,
+            retention_purge_batch_pause_ms,
+            import_max_future_skew_secs,
+            import_earliest_date,
+            import_batch_size,
+            import_max_concurrency,
+            stats_cache_ttl_secs,
+            health_cache_ttl_ms,
+            service_groups,
+            health_signal_window_hours,
+            health_thumbs_up_ratio_warning,
+            health_thumbs_up_ratio_critical,
+            health_rating_warning,
+            health_rating_critical,
+            thumbs_up_score,
+            thumbs_down_score,
+            metrics_timezone,
+            stats_warmup_services,
+            comment_text_search_config,
+            request_id_header,
+            log_error_request_bodies,
+            response_cache_max_age_secs,
+            empty_query_no_content,
+            sample_size,
+            json_pretty,
+            slow_request_ms,
+            enable_export,
+            enable_stats,
+            enable_import,
+            enable_bulk,
             allowed_origins,
         })
     }
@@ -73,4 +693,439 @@ impl Config {
     pub fn bind_address(&self) -> String {
         format!("{}:{}", self.host, self.port)
     }
+
+    /// Realm URL used for flows that need a single realm, such as the login proxy
+    pub fn primary_keycloak_url(&self) -> &str {
+        &self.keycloak_urls[0]
+    }
+}
+
+/// Drop repeated webhook URLs, keeping the first occurrence, so a copy-pasted entry does
+/// not deliver every event twice. URLs are compared in their normalized form, where a
+/// bare host gains its trailing slash (`https://x.io` and `https://x.io/` are the same).
+fn dedup_webhook_urls(urls: Vec<String>) -> Vec<String> {
+    let mut seen = std::collections::HashSet::new();
+    urls.into_iter()
+        .filter(|url| {
+            let normalized = reqwest::Url::parse(url)
+                .map(String::from)
+                .unwrap_or_else(|_| url.clone());
+            let first = seen.insert(normalized);
+            if !first {
+                tracing::warn!(url = %url, "Ignoring duplicate WEBHOOK_URLS entry");
+            }
+            first
+        })
+        .collect()
+}
+
+/// Check a webhook URL is well-formed and uses HTTPS (or HTTP when `allow_insecure`)
+fn validate_webhook_url(url: &str, allow_insecure: bool) -> Result<String> {
+    let parsed = reqwest::Url::parse(url)
+        .with_context(|| format!("Invalid WEBHOOK_URLS entry '{}'", url))?;
+
+    if parsed.host_str().is_none() {
+        anyhow::bail!("Invalid WEBHOOK_URLS entry '{}': missing host", url);
+    }
+
+    match parsed.scheme() {
+        "https" => {}
+        "http" if allow_insecure => {
+            tracing::warn!(
+                url = %url,
+                "Insecure http:// webhook allowed by WEBHOOK_ALLOW_INSECURE"
+            );
+        }
+        "http" => anyhow::bail!(
+            "Webhook URL '{}' must use https:// \
+             (set WEBHOOK_ALLOW_INSECURE=true for local development)",
+            url
+        ),
+        scheme => anyhow::bail!(
+            "Invalid WEBHOOK_URLS entry '{}': unsupported scheme '{}'",
+            url,
+            scheme
+        ),
+    }
+
+    Ok(url.to_string())
+}
+
+/// Handling of comments longer than the maximum length
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum CommentOverflow {
+    /// Fail validation (default)
+    Reject,
+    /// Keep the start of the comment, append a marker and log a warning
+    Truncate,
+}
+
+impl FromStr for CommentOverflow {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "reject" => Ok(CommentOverflow::Reject),
+            "truncate" => Ok(CommentOverflow::Truncate),
+            other => anyhow::bail!(
+                "Invalid COMMENT_OVERFLOW '{}': expected 'reject' or 'truncate'",
+                other
+            ),
+        }
+    }
+}
+
+/// Body format of webhook deliveries
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum WebhookFormat {
+    /// `{"event": ..., "feedback": ...}` as `application/json` (default)
+    Raw,
+    /// The feedback as `data` of a CloudEvents 1.0 structured-mode event
+    CloudEvents,
+}
+
+impl FromStr for WebhookFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "raw" => Ok(WebhookFormat::Raw),
+            "cloudevents" => Ok(WebhookFormat::CloudEvents),
+            other => anyhow::bail!(
+                "Invalid WEBHOOK_FORMAT '{}': expected 'raw' or 'cloudevents'",
+                other
+            ),
+        }
+    }
+}
+
+/// Handling of ratings and NPS scores outside their valid range
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum RatingOutOfRange {
+    /// Fail validation (default)
+    #[default]
+    Reject,
+    /// Move the value to the nearest bound of the range and log a warning
+    Clamp,
+}
+
+impl FromStr for RatingOutOfRange {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s.trim().to_ascii_lowercase().as_str() {
+            "reject" => Ok(RatingOutOfRange::Reject),
+            "clamp" => Ok(RatingOutOfRange::Clamp),
+            other => anyhow::bail!(
+                "Invalid RATING_OUT_OF_RANGE '{}': expected 'reject' or 'clamp'",
+                other
+            ),
+        }
+    }
+}
+
+/// A deployment-defined feedback type, from `CUSTOM_FEEDBACK_TYPES`
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize)]
+pub struct CustomFeedbackType {
+    /// Accepted ratings; a rating is then required, and rejected when unset
+    pub rating_range: Option<RangeInclusive<i32>>,
+    /// Whether a non-empty comment is required
+    pub requires_comment: bool,
+}
+
+impl FromStr for CustomFeedbackType {
+    type Err = anyhow::Error;
+
+    /// `|`-separated rules: `min..max` for a rating range and `comment` for a required
+    /// comment, e.g. `1..7|comment`
+    fn from_str(s: &str) -> Result<Self> {
+        let mut custom_type = CustomFeedbackType {
+            rating_range: None,
+            requires_comment: false,
+        };
+
+        for rule in s.split('|').map(str::trim).filter(|r| !r.is_empty()) {
+            if rule == "comment" {
+                custom_type.requires_comment = true;
+            } else if let Some((min, max)) = rule.split_once("..") {
+                let (min, max): (i32, i32) = match (min.trim().parse(), max.trim().parse()) {
+                    (Ok(min), Ok(max)) if min <= max => (min, max),
+                    _ => anyhow::bail!("invalid rating range '{}'", rule),
+                };
+                custom_type.rating_range = Some(min..=max);
+            } else {
+                anyhow::bail!("unknown rule '{}': expected min..max or comment", rule);
+            }
+        }
+
+        Ok(custom_type)
+    }
+}
+
+/// Parse a `service=value,other=value` environment variable into a map
+fn parse_service_map<T>(var: &str) -> Result<HashMap<String, T>>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    let mut map = HashMap::new();
+
+    for entry in std::env::var(var).unwrap_or_default().split(',') {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+
+        let (service, value) = entry
+            .split_once('=')
+            .with_context(|| format!("Invalid {} entry '{}': expected service=value", var, entry))?;
+        let value = value
+            .trim()
+            .parse()
+            .map_err(|e| anyhow::anyhow!("Invalid {} value for '{}': {}", var, service.trim(), e))?;
+
+        map.insert(service.trim().to_string(), value);
+    }
+
+    Ok(map)
+}
+
+/// Parse `INDEXED_CONTEXT_KEYS` (`key1,key2`). Index names are the lowercased key, so
+/// keys differing only in case would share one index and are rejected.
+fn parse_indexed_context_keys(raw: &str) -> Result<Vec<String>> {
+    let keys: Vec<String> = raw
+        .split(',')
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| s.trim().to_string())
+        .collect();
+
+    let mut index_names = HashMap::new();
+    for key in &keys {
+        if !crate::validation::is_valid_context_key(key) {
+            anyhow::bail!(
+                "Invalid INDEXED_CONTEXT_KEYS entry '{}': use 1-{} letters, digits or underscores",
+                key,
+                crate::validation::MAX_CONTEXT_KEY_LENGTH
+            );
+        }
+        if let Some(other) = index_names.insert(key.to_lowercase(), key) {
+            if other != key {
+                anyhow::bail!(
+                    "INDEXED_CONTEXT_KEYS entries '{}' and '{}' differ only in case",
+                    other,
+                    key
+                );
+            }
+        }
+    }
+
+    Ok(keys)
+}
+
+/// Parse `WEBHOOK_HEADERS` (`Name=value;Other=value`). Entries are split on `;` so values
+/// may contain commas; each name and value must be a valid header, and a name may appear
+/// only once (names are case-insensitive).
+fn parse_webhook_headers(raw: &str) -> Result<HashMap<String, String>> {
+    let mut headers = HashMap::new();
+    let mut seen = HashSet::new();
+
+    for entry in raw.split(';') {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+
+        let (name, value) = entry.split_once('=').with_context(|| {
+            format!("Invalid WEBHOOK_HEADERS entry '{}': expected Header-Name=value", entry)
+        })?;
+        let (name, value) = (name.trim(), value.trim());
+        reqwest::header::HeaderName::from_bytes(name.as_bytes())
+            .with_context(|| format!("Invalid WEBHOOK_HEADERS header name '{}'", name))?;
+        reqwest::header::HeaderValue::from_str(value)
+            .with_context(|| format!("Invalid WEBHOOK_HEADERS value for header '{}'", name))?;
+        if !seen.insert(name.to_ascii_lowercase()) {
+            anyhow::bail!("WEBHOOK_HEADERS sets header '{}' more than once", name);
+        }
+
+        headers.insert(name.to_string(), value.to_string());
+    }
+
+    Ok(headers)
+}
+
+/// Parse `SERVICE_WEBHOOK_URLS` (`service=url1,url2;other=url3`) into each service's
+/// validated, deduplicated webhook URLs. Each service must be named, and only once.
+fn parse_service_webhook_urls(
+    raw: &str,
+    allow_insecure: bool,
+) -> Result<HashMap<String, Vec<String>>> {
+    let mut map = HashMap::new();
+
+    for entry in raw.split(';') {
+        let entry = entry.trim();
+        if entry.is_empty() {
+            continue;
+        }
+
+        let (service, urls) = entry.split_once('=').with_context(|| {
+            format!(
+                "Invalid SERVICE_WEBHOOK_URLS entry '{}': expected service=url1,url2",
+                entry
+            )
+        })?;
+        let service = service.trim();
+        if service.is_empty() {
+            anyhow::bail!("Invalid SERVICE_WEBHOOK_URLS entry '{}': missing service name", entry);
+        }
+        if map.contains_key(service) {
+            anyhow::bail!("SERVICE_WEBHOOK_URLS lists service '{}' more than once", service);
+        }
+        let urls = urls
+            .split(',')
+            .filter(|s| !s.trim().is_empty())
+            .map(|s| validate_webhook_url(s.trim(), allow_insecure))
+            .collect::<Result<Vec<_>>>()?;
+
+        map.insert(service.to_string(), dedup_webhook_urls(urls));
+    }
+
+    Ok(map)
+}
+
+/// Load every `<service>.json` file in `dir` as the context schema for that service,
+/// failing if any of them is not a valid JSON Schema
+fn load_context_schemas(dir: &str) -> Result<HashMap<String, serde_json::Value>> {
+    let mut schemas = HashMap::new();
+
+    let entries = std::fs::read_dir(dir)
+        .with_context(|| format!("Failed to read CONTEXT_SCHEMA_DIR '{}'", dir))?;
+    for entry in entries {
+        let path = entry?.path();
+        if path.extension().and_then(|ext| ext.to_str()) != Some("json") {
+            continue;
+        }
+        let Some(service) = path.file_stem().and_then(|stem| stem.to_str()) else {
+            continue;
+        };
+
+        let raw = std::fs::read_to_string(&path)
+            .with_context(|| format!("Failed to read context schema {}", path.display()))?;
+        let schema: serde_json::Value = serde_json::from_str(&raw)
+            .with_context(|| format!("Invalid JSON in context schema {}", path.display()))?;
+        jsonschema::validator_for(&schema).map_err(|e| {
+            anyhow::anyhow!("Invalid context schema {}: {}", path.display(), e)
+        })?;
+
+        schemas.insert(service.to_string(), schema);
+    }
+
+    Ok(schemas)
+}
+
+/// Load a list of domains, one per line, ignoring blank lines and `#` comments
+fn load_domain_list(path: &str) -> Result<HashSet<String>> {
+    let raw = std::fs::read_to_string(path)
+        .with_context(|| format!("Failed to read DISPOSABLE_EMAIL_DOMAINS_FILE '{}'", path))?;
+
+    Ok(raw
+        .lines()
+        .map(|line| line.split('#').next().unwrap_or_default().trim())
+        .filter(|domain| !domain.is_empty())
+        .map(|domain| domain.trim_start_matches('@').to_ascii_lowercase())
+        .collect())
+}
+
+/// Read a boolean environment variable, falling back to `default` when unset or invalid
+fn env_flag(var: &str, default: bool) -> bool {
+    std::env::var(var)
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .unwrap_or(default)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn urls(urls: &[&str]) -> Vec<String> {
+        urls.iter().map(|url| url.to_string()).collect()
+    }
+
+    #[test]
+    fn test_indexed_context_keys_differing_in_case_rejected() {
+        assert_eq!(
+            parse_indexed_context_keys(" page, call_id ,,page").unwrap(),
+            vec!["page", "call_id", "page"]
+        );
+
+        let error = parse_indexed_context_keys("Page,call_id,page").unwrap_err();
+        assert!(error.to_string().contains("'Page' and 'page' differ only in case"));
+        assert!(parse_indexed_context_keys("page-id").is_err());
+    }
+
+    #[test]
+    fn test_parse_webhook_headers() {
+        let headers =
+            parse_webhook_headers(" X-Tenant-Id=acme ; Accept=text/plain, application/json;")
+                .unwrap();
+        assert_eq!(headers.len(), 2);
+        assert_eq!(headers["X-Tenant-Id"], "acme");
+        assert_eq!(headers["Accept"], "text/plain, application/json");
+        assert!(parse_webhook_headers("").unwrap().is_empty());
+
+        let error = parse_webhook_headers("X-Tenant-Id").unwrap_err();
+        assert!(error.to_string().contains("expected Header-Name=value"));
+        let error = parse_webhook_headers("Bad Name=x").unwrap_err();
+        assert!(error.to_string().contains("header name 'Bad Name'"));
+        let error = parse_webhook_headers("X-Trace=a\u{7f}b").unwrap_err();
+        assert!(error.to_string().contains("value for header 'X-Trace'"));
+        let error = parse_webhook_headers("X-Tenant-Id=a;x-tenant-id=b").unwrap_err();
+        assert!(error.to_string().contains("'x-tenant-id' more than once"));
+    }
+
+    #[test]
+    fn test_parse_service_webhook_urls() {
+        let map = parse_service_webhook_urls(
+            "visio=https://a.example/hook,https://b.example/hook; docs=https://c.example/hook",
+            false,
+        )
+        .unwrap();
+        assert_eq!(map["visio"], urls(&["https://a.example/hook", "https://b.example/hook"]));
+        assert_eq!(map["docs"], urls(&["https://c.example/hook"]));
+
+        let error = parse_service_webhook_urls("=https://a.example/hook", false).unwrap_err();
+        assert!(error.to_string().contains("missing service name"));
+        let error = parse_service_webhook_urls(
+            "docs=https://a.example/hook;docs=https://b.example/hook",
+            false,
+        )
+        .unwrap_err();
+        assert!(error.to_string().contains("'docs' more than once"));
+    }
+
+    #[test]
+    fn test_dedup_webhook_urls_keeps_first_in_order() {
+        let deduped = dedup_webhook_urls(urls(&[
+            "https://b.example.com/hook",
+            "https://a.example.com",
+            "https://b.example.com/hook",
+            "https://a.example.com/",
+            "https://a.example.com/hook/",
+            "https://a.example.com/hook",
+        ]));
+
+        // A bare host matches its trailing-slash form; a path keeps its trailing slash
+        assert_eq!(
+            deduped,
+            urls(&[
+                "https://b.example.com/hook",
+                "https://a.example.com",
+                "https://a.example.com/hook/",
+                "https://a.example.com/hook",
+            ])
+        );
+    }
 }
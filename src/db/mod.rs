@@ -1,6 +1,212 @@
-use crate::models::{Feedback, FeedbackQuery, FeedbackStats, FeedbackSubmission, MetricsAggregate};
+use crate::models::{
+    CountryStats, CustomTypeStats, Feedback, FeedbackQuery, FeedbackStats, FeedbackSubmission,
+    FeedbackUpdate, FirstFeedbackStats, ImportRecord, LanguageStats, MetricsAggregate, QueryOrder,
+    ReasonStats, RecentComment, TimeseriesPoint, TimeseriesQuery, UserFeedbackSummary,
+    WordCloudQuery, WordFrequency,
+};
 use anyhow::{Context, Result};
-use sqlx::{PgPool, postgres::PgPoolOptions};
+use std::collections::HashMap;
+use std::time::Duration;
+use sqlx::postgres::{PgArguments, PgPoolOptions};
+use sqlx::query::QueryAs;
+use sqlx::{FromRow, PgPool, Postgres, Transaction};
+
+const INSERT_FEEDBACK_SQL: &str = r#"
+    INSERT INTO feedbacks (user_id, user_email, service, feedback_type, rating, thumbs_up, comment, context, parent_id, language, reason, country, custom_type)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13)
+    RETURNING *
+"#;
+
+/// Like `UPSERT_FEEDBACK_SQL`, keeping the original `created_at` ($14) of a new row;
+/// `$15` is `unique_per_message`, so rows imported with it false are plain inserts
+const IMPORT_FEEDBACK_SQL: &str = r#"
+    INSERT INTO feedbacks (user_id, user_email, service, feedback_type, rating, thumbs_up, comment, context, parent_id, language, reason, country, custom_type, created_at, updated_at, unique_per_message)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, $14, $14, $15)
+    ON CONFLICT (user_id, service, (context->>'message_id')) WHERE unique_per_message
+    DO UPDATE SET
+        user_email = EXCLUDED.user_email,
+        feedback_type = EXCLUDED.feedback_type,
+        rating = EXCLUDED.rating,
+        thumbs_up = EXCLUDED.thumbs_up,
+        comment = EXCLUDED.comment,
+        context = EXCLUDED.context,
+        parent_id = EXCLUDED.parent_id,
+        language = EXCLUDED.language,
+        reason = EXCLUDED.reason,
+        country = EXCLUDED.country,
+        custom_type = EXCLUDED.custom_type
+    RETURNING *, (xmax = 0) AS inserted
+"#;
+
+/// Insert, or update the user's existing feedback for the same `context.message_id`.
+/// `inserted` tells a new row (no previous version, `xmax = 0`) from an update.
+const UPSERT_FEEDBACK_SQL: &str = r#"
+    INSERT INTO feedbacks (user_id, user_email, service, feedback_type, rating, thumbs_up, comment, context, parent_id, language, reason, country, custom_type, unique_per_message)
+    VALUES ($1, $2, $3, $4, $5, $6, $7, $8, $9, $10, $11, $12, $13, true)
+    ON CONFLICT (user_id, service, (context->>'message_id')) WHERE unique_per_message
+    DO UPDATE SET
+        user_email = EXCLUDED.user_email,
+        feedback_type = EXCLUDED.feedback_type,
+        rating = EXCLUDED.rating,
+        thumbs_up = EXCLUDED.thumbs_up,
+        comment = EXCLUDED.comment,
+        context = EXCLUDED.context,
+        parent_id = EXCLUDED.parent_id,
+        language = EXCLUDED.language,
+        reason = EXCLUDED.reason,
+        country = EXCLUDED.country,
+        custom_type = EXCLUDED.custom_type
+    RETURNING *, (xmax = 0) AS inserted
+"#;
+
+/// Bind the submitter and the submission's columns as `$1`..`$13`, in the column order
+/// shared by the insert, upsert and import statements
+fn bind_submission<'q, O>(
+    query: QueryAs<'q, Postgres, O, PgArguments>,
+    user_id: &'q str,
+    user_email: Option<&'q str>,
+    submission: FeedbackSubmission,
+) -> QueryAs<'q, Postgres, O, PgArguments> {
+    query
+        .bind(user_id)
+        .bind(user_email)
+        .bind(submission.service)
+        .bind(submission.feedback_type)
+        .bind(submission.rating)
+        .bind(submission.thumbs_up)
+        .bind(submission.comment)
+        .bind(submission.context)
+        .bind(submission.parent_id)
+        .bind(submission.language)
+        .bind(submission.reason)
+        .bind(submission.country)
+        .bind(submission.custom_type)
+}
+
+/// Aggregates of a `FeedbackStats` row. The rating average and count only cover `rating`
+/// feedback, so NPS scores and custom ratings on their own scales do not skew them.
+const STATS_AGGREGATES: &str = r#"
+    COUNT(*) as total_count,
+    CAST(AVG(CASE WHEN feedback_type = 'rating' THEN rating END) AS float8) as rating_avg,
+    COUNT(CASE WHEN feedback_type = 'rating' THEN rating END)::bigint as rating_count,
+    COUNT(CASE WHEN thumbs_up = true THEN 1 END)::bigint as thumbs_up_count,
+    COUNT(CASE WHEN thumbs_up = false THEN 1 END)::bigint as thumbs_down_count,
+    CASE
+        WHEN COUNT(CASE WHEN thumbs_up IS NOT NULL THEN 1 END) > 0
+        THEN COUNT(CASE WHEN thumbs_up = true THEN 1 END)::float / COUNT(CASE WHEN thumbs_up IS NOT NULL THEN 1 END)::float
+        ELSE NULL
+    END as thumbs_up_ratio,
+    COUNT(CASE WHEN comment IS NOT NULL THEN 1 END)::bigint as comment_count
+"#;
+
+/// A `FeedbackStats` query: `service` (an expression) as the service, the stats
+/// aggregates, then `rest` (FROM, WHERE and GROUP BY)
+fn stats_sql(service: &str, rest: &str) -> String {
+    format!("SELECT {} as service, {} {}", service, STATS_AGGREGATES, rest)
+}
+
+/// A row returned by `UPSERT_FEEDBACK_SQL` or `IMPORT_FEEDBACK_SQL`
+#[derive(FromRow)]
+struct UpsertedFeedback {
+    #[sqlx(flatten)]
+    feedback: Feedback,
+    inserted: bool,
+}
+
+/// `ts_headline` options for search highlights: at most three fragments of up to 15
+/// words, matches wrapped in `**`
+const HIGHLIGHT_OPTIONS: &str =
+    "MaxFragments=3, MaxWords=15, MinWords=5, StartSel=**, StopSel=**, FragmentDelimiter=\" ... \"";
+
+/// Size of the connection pool
+pub const MAX_CONNECTIONS: u32 = 50;
+
+/// Default for how long a pooled connection may sit idle before it is closed
+pub const DEFAULT_IDLE_TIMEOUT_SECS: u64 = 600;
+
+/// Default for how long a pooled connection is used before it is replaced
+pub const DEFAULT_MAX_LIFETIME_SECS: u64 = 1800;
+
+/// Advisory lock key serializing stats refreshes across instances
+const STATS_REFRESH_LOCK_KEY: i64 = 0x6766_7374_6174_7300;
+
+/// SQLSTATE `read_only_sql_transaction`, returned for writes while Postgres is read-only
+const READ_ONLY_SQLSTATE: &str = "25006";
+
+/// Whether a write failed because the database is currently read-only
+pub fn is_read_only_error(err: &sqlx::Error) -> bool {
+    err.as_database_error()
+        .and_then(|db_err| db_err.code())
+        .is_some_and(|code| code == READ_ONLY_SQLSTATE)
+}
+
+/// Error raised when one row of a batch insert fails; the whole batch is rolled back
+#[derive(Debug, thiserror::Error)]
+#[error("Failed to create feedback at batch position {position}: {message}")]
+pub struct BatchInsertError {
+    /// Position of the failing row within the batch that was being inserted
+    pub position: usize,
+    pub message: String,
+}
+
+/// An open transaction, rolled back when dropped without `commit`
+pub struct DbTransaction {
+    tx: Transaction<'static, Postgres>,
+}
+
+impl DbTransaction {
+    pub async fn create_feedback(
+        &mut self,
+        user_id: &str,
+        user_email: Option<&str>,
+        submission: FeedbackSubmission,
+    ) -> Result<Feedback> {
+        let query = sqlx::query_as::<_, Feedback>(INSERT_FEEDBACK_SQL);
+        let feedback = bind_submission(query, user_id, user_email, submission)
+            .fetch_one(&mut *self.tx)
+            .await
+            .context("Failed to create feedback")?;
+
+        Ok(feedback)
+    }
+
+    /// Create a feedback, or overwrite the user's feedback for the same `context.message_id`.
+    /// Returns the row and whether it was newly created.
+    pub async fn upsert_feedback(
+        &mut self,
+        user_id: &str,
+        user_email: Option<&str>,
+        submission: FeedbackSubmission,
+    ) -> Result<(Feedback, bool)> {
+        let query = sqlx::query_as::<_, UpsertedFeedback>(UPSERT_FEEDBACK_SQL);
+        let row = bind_submission(query, user_id, user_email, submission)
+            .fetch_one(&mut *self.tx)
+            .await
+            .context("Failed to upsert feedback")?;
+
+        Ok((row.feedback, row.inserted))
+    }
+
+    /// Delete every feedback of a user, returning how many were deleted. Follow-ups
+    /// of other users pointing at them lose their `parent_id`.
+    pub async fn delete_feedbacks_by_user(&mut self, user_id: &str) -> Result<u64> {
+        let result = sqlx::query("DELETE FROM feedbacks WHERE user_id = $1")
+            .bind(user_id)
+            .execute(&mut *self.tx)
+            .await
+            .context("Failed to delete user feedbacks")?;
+
+        Ok(result.rows_affected())
+    }
+
+    pub async fn commit(self) -> Result<()> {
+        self.tx.commit().await.context("Failed to commit transaction")
+    }
+
+    pub async fn rollback(self) -> Result<()> {
+        self.tx.rollback().await.context("Failed to roll back transaction")
+    }
+}
 
 pub struct Database {
     pool: PgPool,
@@ -8,8 +214,29 @@ pub struct Database {
 
 impl Database {
     pub async fn new(database_url: &str) -> Result<Self> {
+        Self::connect(
+            database_url,
+            0,
+            Some(Duration::from_secs(DEFAULT_IDLE_TIMEOUT_SECS)),
+            Some(Duration::from_secs(DEFAULT_MAX_LIFETIME_SECS)),
+        )
+        .await
+    }
+
+    /// Connect, keeping at least `min_connections` open once they are established. Idle
+    /// connections are closed after `idle_timeout` and any connection is replaced after
+    /// `max_lifetime` (`None` = never), before a pooler or the server drops them.
+    pub async fn connect(
+        database_url: &str,
+        min_connections: u32,
+        idle_timeout: Option<Duration>,
+        max_lifetime: Option<Duration>,
+    ) -> Result<Self> {
         let pool = PgPoolOptions::new()
-            .max_connections(50)
+            .max_connections(MAX_CONNECTIONS)
+            .min_connections(min_connections.min(MAX_CONNECTIONS))
+            .idle_timeout(idle_timeout)
+            .max_lifetime(max_lifetime)
             .connect(database_url)
             .await
             .context("Failed to connect to database")?;
@@ -17,6 +244,27 @@ impl Database {
         Ok(Self { pool })
     }
 
+    /// Open `connections` pooled connections up front and check each with a query, so the
+    /// first requests after a start do not pay for connecting
+    pub async fn warm_up(&self, connections: u32) -> Result<()> {
+        // Held together until all are open, so each acquire opens a new connection
+        let mut held = Vec::new();
+        for _ in 0..connections.min(MAX_CONNECTIONS) {
+            let mut conn = self
+                .pool
+                .acquire()
+                .await
+                .context("Failed to open connection during pool warm-up")?;
+            sqlx::query("SELECT 1")
+                .execute(&mut *conn)
+                .await
+                .context("Warm-up query failed")?;
+            held.push(conn);
+        }
+
+        Ok(())
+    }
+
     pub async fn run_migrations(&self) -> Result<()> {
         sqlx::migrate!("./migrations")
             .run(&self.pool)
@@ -25,51 +273,244 @@ impl Database {
         Ok(())
     }
 
+    /// Create an expression index on `context->>'key'` for each key that lacks one. Built
+    /// concurrently, so writes are not blocked; keys must pass `is_valid_context_key`.
+    /// An index left INVALID by an interrupted build is dropped and built again.
+    pub async fn ensure_context_indexes(&self, keys: &[String]) -> Result<()> {
+        for key in keys {
+            let index = format!("idx_feedbacks_context_{}", key.to_lowercase());
+
+            let invalid: Option<bool> = sqlx::query_scalar(
+                "SELECT NOT indisvalid FROM pg_index WHERE indexrelid = to_regclass($1)",
+            )
+            .bind(&index)
+            .fetch_optional(&self.pool)
+            .await
+            .with_context(|| format!("Failed to check index {}", index))?;
+            if invalid == Some(true) {
+                tracing::warn!(index = %index, "Rebuilding invalid context key index");
+                sqlx::query(&format!("DROP INDEX CONCURRENTLY IF EXISTS {}", index))
+                    .execute(&self.pool)
+                    .await
+                    .with_context(|| format!("Failed to drop invalid index {}", index))?;
+            }
+
+            let sql = format!(
+                "CREATE INDEX CONCURRENTLY IF NOT EXISTS {} ON feedbacks ((context->>'{}'))",
+                index, key
+            );
+            sqlx::query(&sql)
+                .execute(&self.pool)
+                .await
+                .with_context(|| format!("Failed to create index on context key '{}'", key))?;
+        }
+        Ok(())
+    }
+
     pub async fn create_feedback(
         &self,
         user_id: &str,
         user_email: Option<&str>,
         submission: FeedbackSubmission,
     ) -> Result<Feedback> {
+        let query = sqlx::query_as::<_, Feedback>(INSERT_FEEDBACK_SQL);
+        let feedback = bind_submission(query, user_id, user_email, submission)
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to create feedback")?;
+
+        Ok(feedback)
+    }
+
+    /// Create a feedback, or overwrite the user's feedback for the same `context.message_id`.
+    /// Returns the row and whether it was newly created.
+    pub async fn upsert_feedback(
+        &self,
+        user_id: &str,
+        user_email: Option<&str>,
+        submission: FeedbackSubmission,
+    ) -> Result<(Feedback, bool)> {
+        let query = sqlx::query_as::<_, UpsertedFeedback>(UPSERT_FEEDBACK_SQL);
+        let row = bind_submission(query, user_id, user_email, submission)
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to upsert feedback")?;
+
+        Ok((row.feedback, row.inserted))
+    }
+
+    /// Start a transaction; writes made through it are discarded unless it is committed
+    pub async fn begin(&self) -> Result<DbTransaction> {
+        let tx = self.pool.begin().await.context("Failed to begin transaction")?;
+        Ok(DbTransaction { tx })
+    }
+
+    /// Insert historical feedbacks with their original timestamps, atomically. Each record
+    /// is paired with whether it upserts by `context.message_id`; each row is returned with
+    /// whether it was newly created.
+    pub async fn import_feedbacks(
+        &self,
+        records: Vec<(ImportRecord, bool)>,
+    ) -> Result<Vec<(Feedback, bool)>> {
+        let mut tx = self
+            .pool
+            .begin()
+            .await
+            .context("Failed to begin import transaction")?;
+
+        let mut feedbacks = Vec::with_capacity(records.len());
+        for (position, (record, upsert)) in records.into_iter().enumerate() {
+            let query = sqlx::query_as::<_, UpsertedFeedback>(IMPORT_FEEDBACK_SQL);
+            let user_email = record.user_email.as_deref();
+            let result = bind_submission(query, &record.user_id, user_email, record.submission)
+                .bind(record.created_at)
+                .bind(upsert)
+                .fetch_one(&mut *tx)
+                .await;
+
+            match result {
+                Ok(row) => feedbacks.push((row.feedback, row.inserted)),
+                Err(e) => {
+                    tx.rollback().await.ok();
+                    if is_read_only_error(&e) {
+                        return Err(e.into());
+                    }
+                    return Err(BatchInsertError {
+                        position,
+                        message: e.to_string(),
+                    }
+                    .into());
+                }
+            }
+        }
+
+        tx.commit()
+            .await
+            .context("Failed to commit import transaction")?;
+
+        Ok(feedbacks)
+    }
+
+    pub async fn get_feedback(&self, id: uuid::Uuid) -> Result<Option<Feedback>> {
         let feedback = sqlx::query_as::<_, Feedback>(
             r#"
-            INSERT INTO feedbacks (user_id, user_email, service, feedback_type, rating, thumbs_up, comment, context)
-            VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
-            RETURNING *
+            SELECT * FROM feedbacks WHERE id = $1
             "#,
         )
-        .bind(user_id)
-        .bind(user_email)
-        .bind(submission.service)
-        .bind(submission.feedback_type)
-        .bind(submission.rating)
-        .bind(submission.thumbs_up)
-        .bind(submission.comment)
-        .bind(submission.context)
-        .fetch_one(&self.pool)
+        .bind(id)
+        .fetch_optional(&self.pool)
         .await
-        .context("Failed to create feedback")?;
+        .context("Failed to get feedback")?;
 
         Ok(feedback)
     }
 
-    pub async fn get_feedback(&self, id: uuid::Uuid) -> Result<Option<Feedback>> {
+    /// Overwrite the editable fields of a feedback with the given values
+    pub async fn update_feedback(
+        &self,
+        id: uuid::Uuid,
+        update: FeedbackUpdate,
+    ) -> Result<Option<Feedback>> {
         let feedback = sqlx::query_as::<_, Feedback>(
             r#"
-            SELECT * FROM feedbacks WHERE id = $1
+            UPDATE feedbacks
+            SET rating = $2, thumbs_up = $3, comment = $4, context = $5, reason = $6
+            WHERE id = $1
+            RETURNING *
             "#,
         )
         .bind(id)
+        .bind(update.rating)
+        .bind(update.thumbs_up)
+        .bind(update.comment)
+        .bind(update.context)
+        .bind(update.reason)
         .fetch_optional(&self.pool)
         .await
-        .context("Failed to get feedback")?;
+        .context("Failed to update feedback")?;
 
         Ok(feedback)
     }
 
+    pub async fn get_children(&self, parent_id: uuid::Uuid) -> Result<Vec<Feedback>> {
+        let feedbacks = sqlx::query_as::<_, Feedback>(
+            r#"
+            SELECT * FROM feedbacks WHERE parent_id = $1 ORDER BY created_at
+            "#,
+        )
+        .bind(parent_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get child feedbacks")?;
+
+        Ok(feedbacks)
+    }
+
+    pub async fn get_feedbacks_by_user(&self, user_id: &str) -> Result<Vec<Feedback>> {
+        let feedbacks = sqlx::query_as::<_, Feedback>(
+            r#"
+            SELECT * FROM feedbacks WHERE user_id = $1 ORDER BY created_at
+            "#,
+        )
+        .bind(user_id)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get feedbacks for user")?;
+
+        Ok(feedbacks)
+    }
+
+    pub async fn get_user_summary(&self, user_id: &str) -> Result<UserFeedbackSummary> {
+        let summary = sqlx::query_as::<_, UserFeedbackSummary>(
+            r#"
+            SELECT
+                COUNT(*) as total_count,
+                COALESCE(ARRAY_AGG(DISTINCT service)::text[], '{}') as services,
+                MAX(created_at) as last_activity
+            FROM feedbacks
+            WHERE user_id = $1
+            "#,
+        )
+        .bind(user_id)
+        .fetch_one(&self.pool)
+        .await
+        .context("Failed to get user feedback summary")?;
+
+        Ok(summary)
+    }
+
     pub async fn query_feedbacks(&self, query: FeedbackQuery) -> Result<Vec<Feedback>> {
-        let mut sql = String::from("SELECT * FROM feedbacks WHERE 1=1");
-        let mut bind_count = 0;
+        self.query_feedbacks_with_timeout(query, None).await
+    }
+
+    /// Query feedbacks, optionally in a transaction whose statement timeout is set to
+    /// `statement_timeout_ms` (transaction-local, so the pooled connection is unaffected)
+    pub async fn query_feedbacks_with_timeout(
+        &self,
+        query: FeedbackQuery,
+        statement_timeout_ms: Option<u64>,
+    ) -> Result<Vec<Feedback>> {
+        // A search binds its text search configuration as $1 and the terms as $2
+        let search_config = query.text_search_config.as_deref().unwrap_or("simple");
+        // With `count`, every row also carries the number of rows matching the filters
+        let columns = if query.count {
+            "*, COUNT(*) OVER () AS total_count"
+        } else {
+            "*"
+        };
+        let (mut sql, mut bind_count) = if query.search.is_some() {
+            let sql = format!(
+                "SELECT {}, ts_headline($1::regconfig, comment, \
+                 websearch_to_tsquery($1::regconfig, $2), '{}') AS highlight \
+                 FROM feedbacks \
+                 WHERE to_tsvector($1::regconfig, COALESCE(comment, '')) \
+                 @@ websearch_to_tsquery($1::regconfig, $2)",
+                columns, HIGHLIGHT_OPTIONS
+            );
+            (sql, 2)
+        } else {
+            (format!("SELECT {} FROM feedbacks WHERE 1=1", columns), 0)
+        };
 
         if query.service.is_some() {
             bind_count += 1;
@@ -86,6 +527,33 @@ impl Database {
             sql.push_str(&format!(" AND user_id = ${}", bind_count));
         }
 
+        if query.language.is_some() {
+            bind_count += 1;
+            sql.push_str(&format!(" AND language = ${}", bind_count));
+        }
+
+        if query.reason.is_some() {
+            bind_count += 1;
+            sql.push_str(&format!(" AND reason = ${}", bind_count));
+        }
+
+        if query.country.is_some() {
+            bind_count += 1;
+            sql.push_str(&format!(" AND country = ${}", bind_count));
+        }
+
+        if query.custom_type.is_some() {
+            bind_count += 1;
+            sql.push_str(&format!(" AND custom_type = ${}", bind_count));
+        }
+
+        // The key is written as a literal (validated to [A-Za-z0-9_]) so the expression
+        // matches, and can use, the index created by `ensure_context_indexes`
+        if let (Some(key), Some(_)) = (&query.context_key, &query.context_value) {
+            bind_count += 1;
+            sql.push_str(&format!(" AND context->>'{}' = ${}", key, bind_count));
+        }
+
         if query.from_date.is_some() {
             bind_count += 1;
             sql.push_str(&format!(" AND created_at >= ${}", bind_count));
@@ -96,7 +564,25 @@ impl Database {
             sql.push_str(&format!(" AND created_at <= ${}", bind_count));
         }
 
-        sql.push_str(" ORDER BY created_at DESC");
+        if query.updated_from.is_some() {
+            bind_count += 1;
+            sql.push_str(&format!(" AND updated_at >= ${}", bind_count));
+        }
+
+        if query.updated_to.is_some() {
+            bind_count += 1;
+            sql.push_str(&format!(" AND updated_at <= ${}", bind_count));
+        }
+
+        // A sample sorts every matching row by random(), which costs a full scan of the
+        // matches: fine for narrow filters, slow over a whole large table. TABLESAMPLE
+        // would be cheaper but samples before the filters, so could come back short.
+        let order = match (query.sample, query.order) {
+            (true, _) => "random()",
+            (false, Some(QueryOrder::Updated)) => "updated_at ASC, id ASC",
+            (false, Some(QueryOrder::Newest) | None) => "created_at DESC",
+        };
+        sql.push_str(&format!(" ORDER BY {}", order));
 
         if query.limit.is_some() {
             bind_count += 1;
@@ -110,6 +596,10 @@ impl Database {
 
         let mut query_builder = sqlx::query_as::<_, Feedback>(&sql);
 
+        if let Some(search) = &query.search {
+            query_builder = query_builder.bind(search_config).bind(search);
+        }
+
         if let Some(service) = &query.service {
             query_builder = query_builder.bind(service);
         }
@@ -122,6 +612,26 @@ impl Database {
             query_builder = query_builder.bind(user_id);
         }
 
+        if let Some(language) = &query.language {
+            query_builder = query_builder.bind(language);
+        }
+
+        if let Some(reason) = &query.reason {
+            query_builder = query_builder.bind(reason);
+        }
+
+        if let Some(country) = &query.country {
+            query_builder = query_builder.bind(country);
+        }
+
+        if let Some(custom_type) = &query.custom_type {
+            query_builder = query_builder.bind(custom_type);
+        }
+
+        if let (Some(_), Some(value)) = (&query.context_key, &query.context_value) {
+            query_builder = query_builder.bind(value);
+        }
+
         if let Some(from_date) = query.from_date {
             query_builder = query_builder.bind(from_date);
         }
@@ -130,6 +640,14 @@ impl Database {
             query_builder = query_builder.bind(to_date);
         }
 
+        if let Some(updated_from) = query.updated_from {
+            query_builder = query_builder.bind(updated_from);
+        }
+
+        if let Some(updated_to) = query.updated_to {
+            query_builder = query_builder.bind(updated_to);
+        }
+
         if let Some(limit) = query.limit {
             query_builder = query_builder.bind(limit);
         }
@@ -138,70 +656,481 @@ impl Database {
             query_builder = query_builder.bind(offset);
         }
 
-        let feedbacks = query_builder
-            .fetch_all(&self.pool)
-            .await
-            .context("Failed to query feedbacks")?;
+        let feedbacks = match statement_timeout_ms {
+            Some(timeout_ms) => {
+                let mut tx = self
+                    .pool
+                    .begin()
+                    .await
+                    .context("Failed to begin query transaction")?;
+                sqlx::query("SELECT set_config('statement_timeout', $1, true)")
+                    .bind(timeout_ms.to_string())
+                    .execute(&mut *tx)
+                    .await
+                    .context("Failed to set statement timeout")?;
+                let feedbacks = query_builder
+                    .fetch_all(&mut *tx)
+                    .await
+                    .context("Failed to query feedbacks")?;
+                tx.commit()
+                    .await
+                    .context("Failed to commit query transaction")?;
+                feedbacks
+            }
+            None => query_builder
+                .fetch_all(&self.pool)
+                .await
+                .context("Failed to query feedbacks")?,
+        };
 
         Ok(feedbacks)
     }
 
     pub async fn get_stats(&self, service: Option<&str>) -> Result<Vec<FeedbackStats>> {
-        let stats = if let Some(service) = service {
-            sqlx::query_as::<_, FeedbackStats>(
-                r#"
-                SELECT
-                    service,
-                    COUNT(*) as total_count,
-                    CAST(AVG(CASE WHEN rating IS NOT NULL THEN rating END) AS float8) as rating_avg,
-                    COUNT(CASE WHEN thumbs_up = true THEN 1 END)::bigint as thumbs_up_count,
-                    COUNT(CASE WHEN thumbs_up = false THEN 1 END)::bigint as thumbs_down_count,
-                    CASE
-                        WHEN COUNT(CASE WHEN thumbs_up IS NOT NULL THEN 1 END) > 0
-                        THEN COUNT(CASE WHEN thumbs_up = true THEN 1 END)::float / COUNT(CASE WHEN thumbs_up IS NOT NULL THEN 1 END)::float
-                        ELSE NULL
-                    END as thumbs_up_ratio,
-                    COUNT(CASE WHEN comment IS NOT NULL THEN 1 END)::bigint as comment_count
-                FROM feedbacks
-                WHERE service = $1
-                GROUP BY service
-                "#,
-            )
+        let sql = stats_sql(
+            "service",
+            "FROM feedbacks WHERE $1::text IS NULL OR service = $1 GROUP BY service",
+        );
+        let stats = sqlx::query_as::<_, FeedbackStats>(&sql)
             .bind(service)
             .fetch_all(&self.pool)
-            .await?
-        } else {
-            sqlx::query_as::<_, FeedbackStats>(
-                r#"
-                SELECT
-                    service,
-                    COUNT(*) as total_count,
-                    CAST(AVG(CASE WHEN rating IS NOT NULL THEN rating END) AS float8) as rating_avg,
-                    COUNT(CASE WHEN thumbs_up = true THEN 1 END)::bigint as thumbs_up_count,
-                    COUNT(CASE WHEN thumbs_up = false THEN 1 END)::bigint as thumbs_down_count,
-                    CASE
-                        WHEN COUNT(CASE WHEN thumbs_up IS NOT NULL THEN 1 END) > 0
-                        THEN COUNT(CASE WHEN thumbs_up = true THEN 1 END)::float / COUNT(CASE WHEN thumbs_up IS NOT NULL THEN 1 END)::float
-                        ELSE NULL
-                    END as thumbs_up_ratio,
-                    COUNT(CASE WHEN comment IS NOT NULL THEN 1 END)::bigint as comment_count
+            .await?;
+
+        Ok(stats)
+    }
+
+    /// Stats with services rolled up into their group (services without a group stand
+    /// alone). Averages are taken over all rows, so each service weighs by its volume.
+    pub async fn get_grouped_stats(
+        &self,
+        service_groups: &HashMap<String, String>,
+        group: Option<&str>,
+    ) -> Result<Vec<FeedbackStats>> {
+        let (services, groups): (Vec<&str>, Vec<&str>) = service_groups
+            .iter()
+            .map(|(service, group)| (service.as_str(), group.as_str()))
+            .unzip();
+
+        let sql = stats_sql(
+            "COALESCE(g.group_name, f.service)",
+            r#"
+            FROM feedbacks f
+            LEFT JOIN UNNEST($1::text[], $2::text[]) AS g(service, group_name)
+                ON g.service = f.service
+            WHERE $3::text IS NULL OR COALESCE(g.group_name, f.service) = $3
+            GROUP BY COALESCE(g.group_name, f.service)
+            "#,
+        );
+        let stats = sqlx::query_as::<_, FeedbackStats>(&sql)
+            .bind(services)
+            .bind(groups)
+            .bind(group)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to get grouped stats")?;
+
+        Ok(stats)
+    }
+
+    /// Get statistics broken down by service and language, optionally for one service
+    pub async fn get_language_stats(&self, service: Option<&str>) -> Result<Vec<LanguageStats>> {
+        let stats = sqlx::query_as::<_, LanguageStats>(
+            r#"
+            SELECT
+                service,
+                language,
+                COUNT(*) as total_count,
+                CAST(AVG(CASE WHEN feedback_type = 'rating' THEN rating END) AS float8) as rating_avg,
+                COUNT(CASE WHEN thumbs_up = true THEN 1 END)::bigint as thumbs_up_count,
+                COUNT(CASE WHEN thumbs_up = false THEN 1 END)::bigint as thumbs_down_count,
+                COUNT(CASE WHEN comment IS NOT NULL THEN 1 END)::bigint as comment_count
+            FROM feedbacks
+            WHERE $1::text IS NULL OR service = $1
+            GROUP BY service, language
+            ORDER BY service, total_count DESC
+            "#,
+        )
+        .bind(service)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get language stats")?;
+
+        Ok(stats)
+    }
+
+    /// Count custom feedback per service and custom type, optionally for one service
+    pub async fn get_custom_type_stats(
+        &self,
+        service: Option<&str>,
+    ) -> Result<Vec<CustomTypeStats>> {
+        let stats = sqlx::query_as::<_, CustomTypeStats>(
+            r#"
+            SELECT
+                service,
+                custom_type,
+                COUNT(*) as total_count,
+                CAST(AVG(rating) AS float8) as rating_avg,
+                COUNT(CASE WHEN comment IS NOT NULL THEN 1 END)::bigint as comment_count
+            FROM feedbacks
+            WHERE custom_type IS NOT NULL AND ($1::text IS NULL OR service = $1)
+            GROUP BY service, custom_type
+            ORDER BY service, total_count DESC
+            "#,
+        )
+        .bind(service)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get custom type stats")?;
+
+        Ok(stats)
+    }
+
+    /// Get statistics broken down by service and country, optionally for one service
+    pub async fn get_country_stats(&self, service: Option<&str>) -> Result<Vec<CountryStats>> {
+        let stats = sqlx::query_as::<_, CountryStats>(
+            r#"
+            SELECT
+                service,
+                country,
+                COUNT(*) as total_count,
+                CAST(AVG(CASE WHEN feedback_type = 'rating' THEN rating END) AS float8) as rating_avg,
+                COUNT(CASE WHEN thumbs_up = true THEN 1 END)::bigint as thumbs_up_count,
+                COUNT(CASE WHEN thumbs_up = false THEN 1 END)::bigint as thumbs_down_count,
+                COUNT(CASE WHEN comment IS NOT NULL THEN 1 END)::bigint as comment_count
+            FROM feedbacks
+            WHERE $1::text IS NULL OR service = $1
+            GROUP BY service, country
+            ORDER BY service, total_count DESC
+            "#,
+        )
+        .bind(service)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get country stats")?;
+
+        Ok(stats)
+    }
+
+    /// Stats of one service's feedback created since `since`. Always one row, with zero
+    /// counts when there is none.
+    pub async fn get_window_stats(
+        &self,
+        service: &str,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<FeedbackStats> {
+        let sql = stats_sql(
+            "$1::text",
+            "FROM feedbacks WHERE service = $1 AND created_at >= $2",
+        );
+        let stats = sqlx::query_as::<_, FeedbackStats>(&sql)
+            .bind(service)
+            .bind(since)
+            .fetch_one(&self.pool)
+            .await
+            .context("Failed to get window stats")?;
+
+        Ok(stats)
+    }
+
+    /// Stats per service of feedback created in `[from, to)`, optionally for one service
+    pub async fn get_range_stats(
+        &self,
+        service: Option<&str>,
+        from: chrono::DateTime<chrono::Utc>,
+        to: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<FeedbackStats>> {
+        let sql = stats_sql(
+            "service",
+            r#"
+            FROM feedbacks
+            WHERE ($1::text IS NULL OR service = $1) AND created_at >= $2 AND created_at < $3
+            GROUP BY service
+            "#,
+        );
+        let stats = sqlx::query_as::<_, FeedbackStats>(&sql)
+            .bind(service)
+            .bind(from)
+            .bind(to)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to get range stats")?;
+
+        Ok(stats)
+    }
+
+    /// Earliest feedback per service and the number received in its first 24 hours,
+    /// optionally for one service
+    pub async fn get_first_feedback_stats(
+        &self,
+        service: Option<&str>,
+    ) -> Result<Vec<FirstFeedbackStats>> {
+        let stats = sqlx::query_as::<_, FirstFeedbackStats>(
+            r#"
+            WITH firsts AS (
+                SELECT service, MIN(created_at) AS first_feedback_at
                 FROM feedbacks
+                WHERE $1::text IS NULL OR service = $1
                 GROUP BY service
-                "#,
             )
-            .fetch_all(&self.pool)
-            .await?
-        };
+            SELECT
+                firsts.service,
+                firsts.first_feedback_at,
+                COUNT(*)::bigint AS first_day_count
+            FROM firsts
+            JOIN feedbacks
+                ON feedbacks.service = firsts.service
+                AND feedbacks.created_at < firsts.first_feedback_at + INTERVAL '24 hours'
+            GROUP BY firsts.service, firsts.first_feedback_at
+            ORDER BY firsts.first_feedback_at
+            "#,
+        )
+        .bind(service)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get first feedback stats")?;
 
         Ok(stats)
     }
 
-    pub async fn refresh_stats(&self) -> Result<()> {
-        sqlx::query("SELECT refresh_feedback_stats()")
-            .execute(&self.pool)
+    /// Newest non-empty comments, optionally for one service
+    pub async fn get_recent_comments(
+        &self,
+        service: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<RecentComment>> {
+        let comments = sqlx::query_as::<_, RecentComment>(
+            r#"
+            SELECT id, service, feedback_type, rating, comment, created_at
+            FROM feedbacks
+            WHERE comment IS NOT NULL AND comment <> ''
+              AND ($1::text IS NULL OR service = $1)
+            ORDER BY created_at DESC
+            LIMIT $2
+            "#,
+        )
+        .bind(service)
+        .bind(limit)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get recent comments")?;
+
+        Ok(comments)
+    }
+
+    /// Count thumbs-down feedback per service and reason, optionally for one service
+    pub async fn get_reason_stats(&self, service: Option<&str>) -> Result<Vec<ReasonStats>> {
+        let stats = sqlx::query_as::<_, ReasonStats>(
+            r#"
+            SELECT service, reason, COUNT(*) as count
+            FROM feedbacks
+            WHERE reason IS NOT NULL AND ($1::text IS NULL OR service = $1)
+            GROUP BY service, reason
+            ORDER BY service, count DESC
+            "#,
+        )
+        .bind(service)
+        .fetch_all(&self.pool)
+        .await
+        .context("Failed to get reason stats")?;
+
+        Ok(stats)
+    }
+
+    pub async fn get_timeseries(
+        &self,
+        query: TimeseriesQuery,
+        timezone: &str,
+    ) -> Result<Vec<TimeseriesPoint>> {
+        // Buckets are computed on the local wall-clock day in `timezone` so that
+        // day boundaries match the viewer's calendar rather than UTC midnight
+        let mut sql = String::from(
+            r#"
+            SELECT
+                DATE_TRUNC('day', created_at AT TIME ZONE $1)::date as date,
+                COUNT(*) as total_count,
+                CAST(AVG(CASE WHEN feedback_type = 'rating' THEN rating END) AS float8) as rating_avg,
+                COUNT(CASE WHEN thumbs_up = true THEN 1 END)::bigint as thumbs_up_count,
+                COUNT(CASE WHEN thumbs_up = false THEN 1 END)::bigint as thumbs_down_count
+            FROM feedbacks
+            WHERE 1=1"#,
+        );
+        let mut bind_count = 1;
+
+        if query.service.is_some() {
+            bind_count += 1;
+            sql.push_str(&format!(" AND service = ${}", bind_count));
+        }
+
+        if query.from_date.is_some() {
+            bind_count += 1;
+            sql.push_str(&format!(" AND created_at >= ${}", bind_count));
+        }
+
+        if query.to_date.is_some() {
+            bind_count += 1;
+            sql.push_str(&format!(" AND created_at <= ${}", bind_count));
+        }
+
+        sql.push_str(" GROUP BY 1 ORDER BY 1");
+
+        let mut query_builder = sqlx::query_as::<_, TimeseriesPoint>(&sql).bind(timezone);
+
+        if let Some(service) = &query.service {
+            query_builder = query_builder.bind(service);
+        }
+
+        if let Some(from_date) = query.from_date {
+            query_builder = query_builder.bind(from_date);
+        }
+
+        if let Some(to_date) = query.to_date {
+            query_builder = query_builder.bind(to_date);
+        }
+
+        let points = query_builder
+            .fetch_all(&self.pool)
             .await
-            .context("Failed to refresh stats")?;
-        Ok(())
+            .context("Failed to get timeseries stats")?;
+
+        Ok(points)
+    }
+
+    /// Most frequent terms across the comments of the `scan_limit` most recent matching
+    /// feedbacks. Comments are tokenized with `to_tsvector` under `text_search_config`,
+    /// which drops stopwords; the lexemes are then counted like `ts_stat` would, but
+    /// with every filter bound as a parameter instead of spliced into a query string.
+    pub async fn get_word_frequencies(
+        &self,
+        query: &WordCloudQuery,
+        text_search_config: &str,
+        scan_limit: i64,
+        limit: i64,
+    ) -> Result<Vec<WordFrequency>> {
+        let mut sql = String::from(
+            "SELECT to_tsvector($1::regconfig, comment) AS document FROM feedbacks \
+             WHERE comment IS NOT NULL",
+        );
+        let mut bind_count = 1;
+
+        if query.service.is_some() {
+            bind_count += 1;
+            sql.push_str(&format!(" AND service = ${}", bind_count));
+        }
+
+        if query.from_date.is_some() {
+            bind_count += 1;
+            sql.push_str(&format!(" AND created_at >= ${}", bind_count));
+        }
+
+        if query.to_date.is_some() {
+            bind_count += 1;
+            sql.push_str(&format!(" AND created_at <= ${}", bind_count));
+        }
+
+        sql.push_str(&format!(
+            " ORDER BY created_at DESC LIMIT ${}",
+            bind_count + 1
+        ));
+        let sql = format!(
+            r#"
+            SELECT
+                term.lexeme as word,
+                SUM(COALESCE(array_length(term.positions, 1), 1))::bigint as count,
+                COUNT(*)::bigint as documents
+            FROM ({}) scanned, unnest(scanned.document) term
+            GROUP BY term.lexeme
+            ORDER BY count DESC, word
+            LIMIT ${}
+            "#,
+            sql,
+            bind_count + 2
+        );
+
+        let mut query_builder =
+            sqlx::query_as::<_, WordFrequency>(&sql).bind(text_search_config);
+
+        if let Some(service) = &query.service {
+            query_builder = query_builder.bind(service);
+        }
+
+        if let Some(from_date) = query.from_date {
+            query_builder = query_builder.bind(from_date);
+        }
+
+        if let Some(to_date) = query.to_date {
+            query_builder = query_builder.bind(to_date);
+        }
+
+        let words = query_builder
+            .bind(scan_limit)
+            .bind(limit)
+            .fetch_all(&self.pool)
+            .await
+            .context("Failed to get comment word frequencies")?;
+
+        Ok(words)
+    }
+
+    /// Delete up to `limit` feedbacks created before `cutoff`, returning how many were
+    /// deleted. Each call is its own short statement so purges never hold long locks.
+    pub async fn delete_feedbacks_before(
+        &self,
+        cutoff: chrono::DateTime<chrono::Utc>,
+        limit: i64,
+    ) -> Result<u64> {
+        let result = sqlx::query(
+            r#"
+            DELETE FROM feedbacks
+            WHERE id IN (
+                SELECT id FROM feedbacks
+                WHERE created_at < $1
+                LIMIT $2
+            )
+            "#,
+        )
+        .bind(cutoff)
+        .bind(limit)
+        .execute(&self.pool)
+        .await
+        .context("Failed to purge feedbacks")?;
+
+        Ok(result.rows_affected())
+    }
+
+    /// Refresh the stats materialized view unless a refresh is already running on any
+    /// instance. Returns `false` without refreshing when another refresh holds the lock.
+    pub async fn refresh_stats(&self) -> Result<bool> {
+        // Advisory locks belong to a session, so lock, refresh and unlock on one connection
+        let mut conn = self
+            .pool
+            .acquire()
+            .await
+            .context("Failed to acquire connection for stats refresh")?;
+
+        let acquired: bool = sqlx::query_scalar("SELECT pg_try_advisory_lock($1)")
+            .bind(STATS_REFRESH_LOCK_KEY)
+            .fetch_one(&mut *conn)
+            .await
+            .context("Failed to take stats refresh lock")?;
+        if !acquired {
+            return Ok(false);
+        }
+
+        let result = sqlx::query("SELECT refresh_feedback_stats()")
+            .execute(&mut *conn)
+            .await;
+
+        let unlocked = sqlx::query("SELECT pg_advisory_unlock($1)")
+            .bind(STATS_REFRESH_LOCK_KEY)
+            .execute(&mut *conn)
+            .await;
+        if unlocked.is_err() {
+            // Never hand a connection still holding the lock back to the pool
+            drop(conn.detach());
+        }
+
+        result.context("Failed to refresh stats")?;
+        Ok(true)
     }
 
     pub async fn get_metrics_aggregates(&self) -> Result<Vec<MetricsAggregate>> {
@@ -1,5 +1,5 @@
 use axum::{
-    http::StatusCode,
+    http::{header::RETRY_AFTER, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
     Json,
 };
@@ -11,6 +11,9 @@ pub enum AppError {
     NotFound(String),
     ValidationError(String),
     AuthenticationError(String),
+    Forbidden(String),
+    Conflict(String),
+    ServiceUnavailable(String),
     InternalError(String),
 }
 
@@ -81,6 +84,33 @@ impl IntoResponse for AppError {
                 );
                 (StatusCode::UNAUTHORIZED, msg.clone(), None, "authentication_error")
             }
+            AppError::Forbidden(msg) => {
+                tracing::warn!(
+                    error_type = "forbidden",
+                    message = %msg,
+                    status_code = %StatusCode::FORBIDDEN.as_u16(),
+                    "Access forbidden"
+                );
+                (StatusCode::FORBIDDEN, msg.clone(), None, "forbidden")
+            }
+            AppError::Conflict(msg) => {
+                tracing::warn!(
+                    error_type = "conflict",
+                    message = %msg,
+                    status_code = %StatusCode::CONFLICT.as_u16(),
+                    "Request conflicts with current state"
+                );
+                (StatusCode::CONFLICT, msg.clone(), None, "conflict")
+            }
+            AppError::ServiceUnavailable(msg) => {
+                tracing::warn!(
+                    error_type = "service_unavailable",
+                    message = %msg,
+                    status_code = %StatusCode::SERVICE_UNAVAILABLE.as_u16(),
+                    "Service temporarily unavailable"
+                );
+                (StatusCode::SERVICE_UNAVAILABLE, msg.clone(), None, "service_unavailable")
+            }
             AppError::InternalError(msg) => {
                 tracing::error!(
                     error_type = "internal_error",
@@ -112,19 +142,52 @@ impl IntoResponse for AppError {
             details,
         };
 
-        (status, Json(body)).into_response()
+        let mut response = (status, Json(body)).into_response();
+        if status == StatusCode::SERVICE_UNAVAILABLE {
+            response
+                .headers_mut()
+                .insert(RETRY_AFTER, HeaderValue::from(RETRY_AFTER_SECS));
+        }
+        response
+    }
+}
+
+/// Seconds clients are asked to wait before retrying a 503
+const RETRY_AFTER_SECS: u64 = 5;
+
+impl AppError {
+    /// Transient database conditions that clients should back off from and retry:
+    /// a read-only database (e.g. during a failover) or an exhausted connection pool
+    fn transient_database_error(err: &sqlx::Error) -> Option<Self> {
+        if crate::db::is_read_only_error(err) {
+            return Some(AppError::ServiceUnavailable(
+                "Service temporarily read-only, please retry the write later".to_string(),
+            ));
+        }
+
+        if matches!(err, sqlx::Error::PoolTimedOut) {
+            crate::metrics::DB_POOL_ACQUIRE_TIMEOUTS.inc();
+            return Some(AppError::ServiceUnavailable(
+                "Service temporarily overloaded, please retry later".to_string(),
+            ));
+        }
+
+        None
     }
 }
 
 impl From<sqlx::Error> for AppError {
     fn from(err: sqlx::Error) -> Self {
-        AppError::DatabaseError(err)
+        AppError::transient_database_error(&err).unwrap_or(AppError::DatabaseError(err))
     }
 }
 
 impl From<anyhow::Error> for AppError {
     fn from(err: anyhow::Error) -> Self {
-        AppError::InternalError(err.to_string())
+        err.chain()
+            .filter_map(|cause| cause.downcast_ref::<sqlx::Error>())
+            .find_map(AppError::transient_database_error)
+            .unwrap_or_else(|| AppError::InternalError(err.to_string()))
     }
 }
 
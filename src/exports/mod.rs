@@ -1,71 +1,369 @@
-use crate::models::{Feedback, ExportFormat};
+use crate::config::WebhookFormat;
+use crate::models::{
+    ExportFormat, Feedback, FeedbackStats, WebhookDeliveryOutcome, WebhookDeliveryReport,
+};
 use anyhow::Result;
+use reqwest::header::{HeaderMap, HeaderName, HeaderValue, CONTENT_TYPE};
+use chrono::format::{Item, StrftimeItems};
 use csv::Writer;
+use hmac::{Hmac, Mac};
+use sha2::{Digest, Sha256};
+use std::borrow::Cow;
+use std::io::{Cursor, Write};
+use std::time::Instant;
+use zip::write::SimpleFileOptions;
+use zip::{CompressionMethod, ZipWriter};
 
-pub fn export_to_json(feedbacks: &[Feedback]) -> Result<String> {
-    Ok(serde_json::to_string_pretty(feedbacks)?)
+/// Longest `date_format` accepted for CSV exports
+const MAX_DATE_FORMAT_LEN: usize = 64;
+
+/// Columns a client may select for an export
+pub const EXPORTABLE_COLUMNS: &[&str] = &[
+    "id",
+    "user_id",
+    "user_email",
+    "service",
+    "feedback_type",
+    "rating",
+    "thumbs_up",
+    "comment",
+    "context",
+    "parent_id",
+    "language",
+    "reason",
+    "country",
+    "custom_type",
+    "created_at",
+    "updated_at",
+];
+
+/// CSV columns when none are selected: the original export header, so existing consumers
+/// keep their column positions; later columns are opted into with `columns`
+const DEFAULT_CSV_COLUMNS: &[&str] = &[
+    "id",
+    "user_id",
+    "user_email",
+    "service",
+    "feedback_type",
+    "rating",
+    "thumbs_up",
+    "comment",
+    "context",
+    "created_at",
+];
+
+/// Options applied to every export regardless of format
+#[derive(Debug, Clone, Default)]
+pub struct ExportOptions {
+    /// Top-level `context` keys removed before serialization
+    pub excluded_context_keys: Vec<String>,
+    /// chrono format applied to `created_at` in CSV; RFC3339 when unset
+    pub csv_date_format: Option<String>,
+    /// Columns to include, in order; every column when unset
+    pub columns: Option<Vec<String>>,
 }
 
-pub fn export_to_csv(feedbacks: &[Feedback]) -> Result<String> {
+/// Parse a comma-separated `columns` list, rejecting unknown and repeated columns
+pub fn parse_columns(columns: &str) -> std::result::Result<Vec<String>, String> {
+    let mut parsed: Vec<String> = Vec::new();
+    for column in columns.split(',').map(str::trim).filter(|c| !c.is_empty()) {
+        if !EXPORTABLE_COLUMNS.contains(&column) {
+            return Err(format!(
+                "Unknown export column '{}' (allowed: {})",
+                column,
+                EXPORTABLE_COLUMNS.join(", ")
+            ));
+        }
+        if parsed.iter().any(|c| c == column) {
+            return Err(format!("Export column '{}' is listed twice", column));
+        }
+        parsed.push(column.to_string());
+    }
+
+    if parsed.is_empty() {
+        return Err("columns must list at least one column".to_string());
+    }
+
+    Ok(parsed)
+}
+
+/// Check that `format` is a chrono format string made only of known specifiers, so
+/// rendering it cannot fail halfway through an export
+pub fn validate_date_format(format: &str) -> std::result::Result<(), String> {
+    if format.is_empty() || format.len() > MAX_DATE_FORMAT_LEN {
+        return Err(format!(
+            "date_format must be between 1 and {} characters",
+            MAX_DATE_FORMAT_LEN
+        ));
+    }
+
+    if StrftimeItems::new(format).any(|item| matches!(item, Item::Error)) {
+        return Err(format!("Invalid date_format '{}'", format));
+    }
+
+    Ok(())
+}
+
+/// Apply export-only transformations; stored rows and API responses are unaffected
+fn prepare<'a>(feedbacks: &'a [Feedback], options: &ExportOptions) -> Cow<'a, [Feedback]> {
+    if options.excluded_context_keys.is_empty() {
+        return Cow::Borrowed(feedbacks);
+    }
+
+    let prepared = feedbacks
+        .iter()
+        .cloned()
+        .map(|mut feedback| {
+            if let Some(serde_json::Value::Object(context)) = feedback.context.as_mut() {
+                for key in &options.excluded_context_keys {
+                    context.remove(key);
+                }
+            }
+            feedback
+        })
+        .collect();
+
+    Cow::Owned(prepared)
+}
+
+/// A feedback restricted to some columns, serialized as an object with keys in that order
+struct SelectedColumns<'a> {
+    feedback: &'a Feedback,
+    columns: &'a [&'a str],
+}
+
+impl serde::Serialize for SelectedColumns<'_> {
+    fn serialize<S>(&self, serializer: S) -> std::result::Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        use serde::ser::SerializeMap;
+
+        let feedback = self.feedback;
+        let mut map = serializer.serialize_map(Some(self.columns.len()))?;
+        for &column in self.columns {
+            match column {
+                "id" => map.serialize_entry(column, &feedback.id)?,
+                "user_id" => map.serialize_entry(column, &feedback.user_id)?,
+                "user_email" => map.serialize_entry(column, &feedback.user_email)?,
+                "service" => map.serialize_entry(column, &feedback.service)?,
+                "feedback_type" => map.serialize_entry(column, &feedback.feedback_type)?,
+                "rating" => map.serialize_entry(column, &feedback.rating)?,
+                "thumbs_up" => map.serialize_entry(column, &feedback.thumbs_up)?,
+                "comment" => map.serialize_entry(column, &feedback.comment)?,
+                "context" => map.serialize_entry(column, &feedback.context)?,
+                "parent_id" => map.serialize_entry(column, &feedback.parent_id)?,
+                "language" => map.serialize_entry(column, &feedback.language)?,
+                "reason" => map.serialize_entry(column, &feedback.reason)?,
+                "country" => map.serialize_entry(column, &feedback.country)?,
+                "custom_type" => map.serialize_entry(column, &feedback.custom_type)?,
+                "created_at" => map.serialize_entry(column, &feedback.created_at)?,
+                "updated_at" => map.serialize_entry(column, &feedback.updated_at)?,
+                _ => {}
+            }
+        }
+        map.end()
+    }
+}
+
+pub fn export_to_json(feedbacks: &[Feedback], columns: Option<&[&str]>) -> Result<String> {
+    match columns {
+        Some(columns) => {
+            let selected: Vec<_> = feedbacks
+                .iter()
+                .map(|feedback| SelectedColumns { feedback, columns })
+                .collect();
+            Ok(serde_json::to_string_pretty(&selected)?)
+        }
+        None => Ok(serde_json::to_string_pretty(feedbacks)?),
+    }
+}
+
+/// One CSV cell; optional values are written as empty cells
+fn csv_value(feedback: &Feedback, column: &str, date_format: Option<&str>) -> String {
+    match column {
+        "id" => feedback.id.to_string(),
+        "user_id" => feedback.user_id.clone(),
+        "user_email" => feedback.user_email.clone().unwrap_or_default(),
+        "service" => feedback.service.clone(),
+        "feedback_type" => format!("{:?}", feedback.feedback_type),
+        "rating" => feedback.rating.map(|r| r.to_string()).unwrap_or_default(),
+        "thumbs_up" => feedback.thumbs_up.map(|t| t.to_string()).unwrap_or_default(),
+        "comment" => feedback.comment.clone().unwrap_or_default(),
+        "context" => feedback.context.as_ref().map(|c| c.to_string()).unwrap_or_default(),
+        "parent_id" => feedback.parent_id.map(|p| p.to_string()).unwrap_or_default(),
+        "language" => feedback.language.clone().unwrap_or_default(),
+        "reason" => feedback.reason.clone().unwrap_or_default(),
+        "country" => feedback.country.clone().unwrap_or_default(),
+        "custom_type" => feedback.custom_type.clone().unwrap_or_default(),
+        "created_at" => match date_format {
+            Some(format) => feedback.created_at.format(format).to_string(),
+            None => feedback.created_at.to_rfc3339(),
+        },
+        "updated_at" => feedback.updated_at.to_rfc3339(),
+        _ => String::new(),
+    }
+}
+
+pub fn export_to_csv(
+    feedbacks: &[Feedback],
+    columns: &[&str],
+    date_format: Option<&str>,
+) -> Result<String> {
     let mut wtr = Writer::from_writer(vec![]);
 
     // Write headers
-    wtr.write_record(&[
-        "id",
-        "user_id",
-        "user_email",
-        "service",
-        "feedback_type",
-        "rating",
-        "thumbs_up",
-        "comment",
-        "context",
-        "created_at",
-    ])?;
+    wtr.write_record(columns)?;
 
     // Write data
     for feedback in feedbacks {
-        wtr.write_record(&[
-            feedback.id.to_string(),
-            feedback.user_id.clone(),
-            feedback.user_email.clone().unwrap_or_default(),
-            feedback.service.clone(),
-            format!("{:?}", feedback.feedback_type),
-            feedback.rating.map(|r| r.to_string()).unwrap_or_default(),
-            feedback.thumbs_up.map(|t| t.to_string()).unwrap_or_default(),
-            feedback.comment.clone().unwrap_or_default(),
-            feedback.context.as_ref().map(|c| c.to_string()).unwrap_or_default(),
-            feedback.created_at.to_rfc3339(),
-        ])?;
+        wtr.write_record(columns.iter().map(|column| csv_value(feedback, column, date_format)))?;
     }
 
     Ok(String::from_utf8(wtr.into_inner()?)?)
 }
 
-pub fn export(feedbacks: &[Feedback], format: ExportFormat) -> Result<String> {
+pub fn export(feedbacks: &[Feedback], format: ExportFormat, options: &ExportOptions) -> Result<String> {
+    let feedbacks = prepare(feedbacks, options);
+    let columns: Option<Vec<&str>> = options
+        .columns
+        .as_ref()
+        .map(|columns| columns.iter().map(String::as_str).collect());
+
     match format {
-        ExportFormat::Json => export_to_json(feedbacks),
-        ExportFormat::Csv => export_to_csv(feedbacks),
+        ExportFormat::Json => export_to_json(&feedbacks, columns.as_deref()),
+        ExportFormat::Csv => export_to_csv(
+            &feedbacks,
+            columns.as_deref().unwrap_or(DEFAULT_CSV_COLUMNS),
+            options.csv_date_format.as_deref(),
+        ),
     }
 }
 
+/// Zip archive bundling an export, as `feedbacks.json` or `feedbacks.csv`, with the
+/// stats of the exported services as `stats.json`
+pub fn export_archive(
+    content: &str,
+    format: &ExportFormat,
+    stats: &[FeedbackStats],
+) -> Result<Vec<u8>> {
+    let export_name = match format {
+        ExportFormat::Json => "feedbacks.json",
+        ExportFormat::Csv => "feedbacks.csv",
+    };
+    let options = SimpleFileOptions::default().compression_method(CompressionMethod::Deflated);
+
+    let mut archive = ZipWriter::new(Cursor::new(Vec::new()));
+    archive.start_file(export_name, options)?;
+    archive.write_all(content.as_bytes())?;
+    archive.start_file("stats.json", options)?;
+    archive.write_all(serde_json::to_string_pretty(stats)?.as_bytes())?;
+
+    Ok(archive.finish()?.into_inner())
+}
+
+/// Hex SHA-256 of an export, so recipients can check it was not altered after download
+pub fn content_sha256(content: impl AsRef<[u8]>) -> String {
+    format!("{:x}", Sha256::digest(content))
+}
+
+/// Hex HMAC-SHA256 of an export under the configured signing key
+pub fn sign_content(content: impl AsRef<[u8]>, key: &str) -> String {
+    let mut mac = Hmac::<Sha256>::new_from_slice(key.as_bytes())
+        .expect("HMAC accepts keys of any length");
+    mac.update(content.as_ref());
+    format!("{:x}", mac.finalize().into_bytes())
+}
+
 #[derive(Debug, serde::Serialize)]
 pub struct WebhookPayload {
     pub event: String,
     pub feedback: Feedback,
 }
 
-pub async fn send_webhook(urls: &[String], payload: WebhookPayload) -> Result<()> {
-    let client = reqwest::Client::new();
+/// `WEBHOOK_HEADERS` as a header map; entries were validated when the config was loaded
+pub fn webhook_header_map(headers: &std::collections::HashMap<String, String>) -> HeaderMap {
+    headers
+        .iter()
+        .filter_map(|(name, value)| {
+            let name = HeaderName::from_bytes(name.as_bytes()).ok()?;
+            let value = HeaderValue::from_str(value).ok()?;
+            Some((name, value))
+        })
+        .collect()
+}
+
+/// Media type of a CloudEvents structured-mode JSON body
+const CLOUDEVENTS_CONTENT_TYPE: &str = "application/cloudevents+json";
+
+/// CloudEvents 1.0 envelope around a webhook's feedback (`WEBHOOK_FORMAT=cloudevents`)
+#[derive(Debug, serde::Serialize)]
+struct CloudEvent<'a> {
+    specversion: &'static str,
+    #[serde(rename = "type")]
+    event_type: String,
+    source: String,
+    id: String,
+    time: chrono::DateTime<chrono::Utc>,
+    datacontenttype: &'static str,
+    data: &'a Feedback,
+}
 
+impl<'a> CloudEvent<'a> {
+    /// One event per delivery attempt: every URL gets the same `id`, so receivers can
+    /// deduplicate. `source` names the feedback's service.
+    fn new(payload: &'a WebhookPayload) -> Self {
+        Self {
+            specversion: "1.0",
+            event_type: format!("gjallarhorn.{}", payload.event),
+            source: format!("/feedback-api/{}", payload.feedback.service),
+            id: uuid::Uuid::new_v4().to_string(),
+            time: chrono::Utc::now(),
+            datacontenttype: "application/json",
+            data: &payload.feedback,
+        }
+    }
+}
+
+/// Deliver `payload` to every URL in the given `format`, with the extra `headers`, and
+/// report each outcome; a receiver answering with a non-2xx status counts as failed.
+/// Unless `allow_insecure`, the client refuses anything but HTTPS, including redirects
+/// to plain HTTP. Delivery lag is measured from `enqueued_at`.
+pub async fn send_webhook(
+    urls: &[String],
+    payload: WebhookPayload,
+    format: WebhookFormat,
+    headers: HeaderMap,
+    allow_insecure: bool,
+    enqueued_at: Instant,
+) -> Result<WebhookDeliveryReport> {
+    let client = reqwest::Client::builder()
+        .https_only(!allow_insecure)
+        .default_headers(headers)
+        .build()?;
+
+    let (body, content_type) = match format {
+        WebhookFormat::Raw => (serde_json::to_vec(&payload)?, "application/json"),
+        WebhookFormat::CloudEvents => (
+            serde_json::to_vec(&CloudEvent::new(&payload))?,
+            CLOUDEVENTS_CONTENT_TYPE,
+        ),
+    };
+
+    let mut report = WebhookDeliveryReport::default();
     for url in urls {
-        match client
+        let request = client
             .post(url)
-            .json(&payload)
-            .send()
-            .await
-        {
-            Ok(_) => {
+            .header(CONTENT_TYPE, content_type)
+            .body(body.clone());
+        let outcome = match request.send().await {
+            Ok(response) if response.status().is_success() => Ok(response.status()),
+            Ok(response) => Err((Some(response.status()), format!("HTTP {}", response.status()))),
+            Err(e) => Err((e.status(), e.to_string())),
+        };
+
+        match outcome {
+            Ok(status) => {
                 tracing::info!(
                     url = %url,
                     event = %payload.event,
@@ -76,22 +374,228 @@ pub async fn send_webhook(urls: &[String], payload: WebhookPayload) -> Result<()
                 crate::metrics::WEBHOOK_DELIVERIES
                     .with_label_values(&["success"])
                     .inc();
+                crate::metrics::WEBHOOK_DELIVERY_LAG
+                    .observe(enqueued_at.elapsed().as_secs_f64());
+                report.succeeded += 1;
+                report.outcomes.push(WebhookDeliveryOutcome {
+                    url: url.clone(),
+                    delivered: true,
+                    status: Some(status.as_u16()),
+                    error: None,
+                });
             }
-            Err(e) => {
+            Err((status, error)) => {
                 tracing::error!(
                     url = %url,
                     event = %payload.event,
                     feedback_id = %payload.feedback.id,
-                    error = %e,
+                    error = %error,
                     "Failed to deliver webhook"
                 );
                 // Record failed webhook delivery
                 crate::metrics::WEBHOOK_DELIVERIES
                     .with_label_values(&["failed"])
                     .inc();
+                report.failed += 1;
+                report.outcomes.push(WebhookDeliveryOutcome {
+                    url: url.clone(),
+                    delivered: false,
+                    status: status.map(|s| s.as_u16()),
+                    error: Some(error),
+                });
             }
         }
     }
 
-    Ok(())
+    Ok(report)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::{FeedbackType, StatsVersion};
+
+    fn feedback_with_context(context: serde_json::Value) -> Feedback {
+        Feedback {
+            id: uuid::Uuid::new_v4(),
+            user_id: "user".to_string(),
+            user_email: None,
+            service: "test-service".to_string(),
+            feedback_type: FeedbackType::Comment,
+            rating: None,
+            thumbs_up: None,
+            comment: Some("hello".to_string()),
+            context: Some(context),
+            parent_id: None,
+            language: None,
+            reason: None,
+            custom_type: None,
+            country: None,
+            created_at: chrono::Utc::now(),
+            updated_at: chrono::Utc::now(),
+            highlight: None,
+            total_count: None,
+        }
+    }
+
+    #[test]
+    fn test_export_strips_excluded_context_keys() {
+        let feedbacks = vec![feedback_with_context(
+            serde_json::json!({"page": "dashboard", "internal_id": "secret"}),
+        )];
+        let options = ExportOptions {
+            excluded_context_keys: vec!["internal_id".to_string()],
+            ..Default::default()
+        };
+
+        let json = export(&feedbacks, ExportFormat::Json, &options).unwrap();
+        let csv = export(&feedbacks, ExportFormat::Csv, &options).unwrap();
+
+        assert!(json.contains("dashboard") && !json.contains("internal_id"));
+        assert!(csv.contains("dashboard") && !csv.contains("internal_id"));
+        // The source rows are left intact
+        assert!(feedbacks[0].context.as_ref().unwrap().get("internal_id").is_some());
+    }
+
+    #[test]
+    fn test_csv_date_format() {
+        let mut feedback = feedback_with_context(serde_json::json!({}));
+        feedback.created_at = "2024-03-05T14:07:09Z".parse().unwrap();
+        let options = ExportOptions {
+            csv_date_format: Some("%Y-%m-%d %H:%M:%S".to_string()),
+            ..Default::default()
+        };
+
+        let csv = export(&[feedback], ExportFormat::Csv, &options).unwrap();
+
+        assert!(csv.contains(",2024-03-05 14:07:09"));
+        assert!(validate_date_format("%Y-%m-%d %H:%M:%S").is_ok());
+        assert!(validate_date_format("%Y-%Q").is_err());
+    }
+
+    #[test]
+    fn test_export_archive_contents() {
+        let stats = vec![FeedbackStats {
+            service: "test-service".to_string(),
+            total_count: 1,
+            rating_avg: None,
+            rating_count: 0,
+            thumbs_up_count: 0,
+            thumbs_down_count: 0,
+            thumbs_up_ratio: None,
+            comment_count: 1,
+            satisfaction_score: None,
+            stats_version: StatsVersion::default(),
+        }];
+
+        let bytes = export_archive("id,service\n", &ExportFormat::Csv, &stats).unwrap();
+        let mut archive = zip::ZipArchive::new(Cursor::new(bytes)).unwrap();
+
+        let mut csv = String::new();
+        std::io::Read::read_to_string(&mut archive.by_name("feedbacks.csv").unwrap(), &mut csv)
+            .unwrap();
+        assert_eq!(csv, "id,service\n");
+        let stats_json: serde_json::Value =
+            serde_json::from_reader(archive.by_name("stats.json").unwrap()).unwrap();
+        assert_eq!(stats_json[0]["service"], "test-service");
+    }
+
+    #[test]
+    fn test_export_integrity() {
+        assert_eq!(
+            content_sha256("abc"),
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        // RFC 4231, test case 2
+        assert_eq!(
+            sign_content("what do ya want for nothing?", "Jefe"),
+            "5bdcc146bf60754e6a042426089575c75a003f089d2739839dec58b964ec3843"
+        );
+    }
+
+    #[test]
+    fn test_export_selected_columns() {
+        let feedbacks = vec![feedback_with_context(serde_json::json!({}))];
+        let options = ExportOptions {
+            columns: Some(parse_columns("service, comment,id").unwrap()),
+            ..Default::default()
+        };
+
+        let csv = export(&feedbacks, ExportFormat::Csv, &options).unwrap();
+        let json = export(&feedbacks, ExportFormat::Json, &options).unwrap();
+
+        let expected_row = format!("test-service,hello,{}", feedbacks[0].id);
+        assert_eq!(csv.lines().collect::<Vec<_>>(), vec!["service,comment,id", &expected_row]);
+        let objects: Vec<serde_json::Value> = serde_json::from_str(&json).unwrap();
+        let keys: Vec<_> = objects[0].as_object().unwrap().keys().cloned().collect();
+        assert_eq!(keys.len(), 3);
+        assert!(!json.contains("user_email") && json.find("service") < json.find("comment"));
+
+        // Without a selection the CSV keeps its original header
+        let csv = export(&feedbacks, ExportFormat::Csv, &ExportOptions::default()).unwrap();
+        assert_eq!(
+            csv.lines().next().unwrap(),
+            concat!(
+                "id,user_id,user_email,service,feedback_type,rating,thumbs_up,",
+                "comment,context,created_at"
+            )
+        );
+
+        assert!(parse_columns("id,password").is_err());
+        assert!(parse_columns("id,id").is_err());
+        assert!(parse_columns(" , ").is_err());
+    }
+
+    #[test]
+    fn test_webhook_header_map() {
+        let configured = std::collections::HashMap::from([
+            ("X-Tenant-Id".to_string(), "acme".to_string()),
+            ("bad header".to_string(), "x".to_string()),
+        ]);
+
+        let headers = webhook_header_map(&configured);
+
+        assert_eq!(headers.len(), 1);
+        assert_eq!(headers["x-tenant-id"], "acme");
+    }
+
+    #[test]
+    fn test_cloud_event_envelope() {
+        let payload = WebhookPayload {
+            event: "feedback.created".to_string(),
+            feedback: feedback_with_context(serde_json::json!({})),
+        };
+
+        let event = serde_json::to_value(CloudEvent::new(&payload)).unwrap();
+
+        assert_eq!(event["specversion"], "1.0");
+        assert_eq!(event["type"], "gjallarhorn.feedback.created");
+        assert_eq!(event["source"], "/feedback-api/test-service");
+        assert_eq!(event["data"]["id"], payload.feedback.id.to_string());
+        assert!(event["id"].as_str().is_some_and(|id| !id.is_empty()));
+        assert!(event["time"].is_string());
+    }
+
+    #[tokio::test]
+    async fn test_send_webhook_reports_each_failed_url() {
+        let urls = vec![
+            "http://127.0.0.1:1/hook".to_string(),
+            "http://127.0.0.1:1/other".to_string(),
+        ];
+        let payload = WebhookPayload {
+            event: "feedback.created".to_string(),
+            feedback: feedback_with_context(serde_json::json!({})),
+        };
+
+        // Plain HTTP is refused before any connection unless insecure URLs are allowed
+        let headers = HeaderMap::new();
+        let report =
+            send_webhook(&urls, payload, WebhookFormat::Raw, headers, false, Instant::now())
+                .await
+                .unwrap();
+
+        assert_eq!((report.succeeded, report.failed), (0, 2));
+        assert_eq!(report.outcomes[1].url, urls[1]);
+        assert!(report.outcomes.iter().all(|o| !o.delivered && o.error.is_some()));
+    }
 }
@@ -23,6 +23,19 @@ pub struct LoginResponse {
     pub expires_in: u64,
 }
 
+// Shape of the Keycloak token endpoint response we rely on
+#[derive(Debug, Deserialize)]
+struct KeycloakTokenResponse {
+    access_token: String,
+    #[serde(default = "default_token_type")]
+    token_type: String,
+    expires_in: u64,
+}
+
+fn default_token_type() -> String {
+    "Bearer".to_string()
+}
+
 // POST /auth/login - Login endpoint (proxy to Keycloak)
 pub async fn login(
     State(state): State<AppState>,
@@ -32,7 +45,7 @@ pub async fn login(
 
     let token_url = format!(
         "{}/protocol/openid-connect/token",
-        state.config.keycloak_url
+        state.config.primary_keycloak_url()
     );
 
     let params = [
@@ -60,21 +73,25 @@ pub async fn login(
         ));
     }
 
-    let token_data: serde_json::Value = response
-        .json()
+    let body = response
+        .text()
         .await
-        .map_err(|e| crate::error::AppError::InternalError(format!("Failed to parse Keycloak response: {}", e)))?;
+        .map_err(|e| crate::error::AppError::InternalError(format!("Failed to read Keycloak response: {}", e)))?;
+
+    // Fail loudly on unexpected shapes rather than handing clients a blank token
+    let token_data: KeycloakTokenResponse = serde_json::from_str(&body)
+        .map_err(|e| crate::error::AppError::InternalError(format!("Unexpected Keycloak token response: {}", e)))?;
+
+    if token_data.access_token.is_empty() {
+        return Err(crate::error::AppError::InternalError(
+            "Unexpected Keycloak token response: empty access_token".to_string(),
+        ));
+    }
 
     let login_response = LoginResponse {
-        access_token: token_data["access_token"]
-            .as_str()
-            .unwrap_or("")
-            .to_string(),
-        token_type: token_data["token_type"]
-            .as_str()
-            .unwrap_or("Bearer")
-            .to_string(),
-        expires_in: token_data["expires_in"].as_u64().unwrap_or(60),
+        access_token: token_data.access_token,
+        token_type: token_data.token_type,
+        expires_in: token_data.expires_in,
     };
 
     // Record successful authentication
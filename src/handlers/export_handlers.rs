@@ -1,41 +1,116 @@
-use crate::error::Result;
-use crate::exports::export;
+use crate::auth::Claims;
+use crate::error::{AppError, Result};
+use crate::exports::{
+    content_sha256, export, export_archive, parse_columns, sign_content, validate_date_format,
+    ExportOptions,
+};
 use crate::models::{ExportQuery, FeedbackQuery};
 use axum::{
-    extract::{Query, State},
-    http::StatusCode,
+    extract::{Extension, Query, State},
+    http::{header::CONTENT_DISPOSITION, HeaderMap, HeaderValue, StatusCode},
     response::{IntoResponse, Response},
 };
 
-use super::AppState;
+use super::{db_timeout_override, AppState};
+
+/// Hex SHA-256 of the export body
+const CONTENT_SHA256_HEADER: &str = "x-content-sha256";
+/// Detached HMAC-SHA256 signature of the export body, when EXPORT_SIGNING_KEY is set
+const CONTENT_SIGNATURE_HEADER: &str = "x-content-signature";
 
 // GET /api/v1/feedbacks/export - Export feedbacks
 pub async fn export_feedbacks(
     State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
     Query(query): Query<ExportQuery>,
 ) -> Result<Response> {
+    let statement_timeout_ms = db_timeout_override(&state, &claims, &headers)?;
+    if let Some(date_format) = &query.date_format {
+        validate_date_format(date_format).map_err(AppError::ValidationError)?;
+    }
+    let columns = query
+        .columns
+        .as_deref()
+        .map(parse_columns)
+        .transpose()
+        .map_err(AppError::ValidationError)?;
+
+    let service = query.service.clone();
     let feedback_query = FeedbackQuery {
         service: query.service,
         feedback_type: None,
         user_id: None,
+        language: None,
+        reason: None,
+        custom_type: None,
+        country: None,
+        search: None,
+        context_key: None,
+        context_value: None,
         from_date: query.from_date,
         to_date: query.to_date,
+        updated_from: None,
+        updated_to: None,
+        order: None,
         limit: Some(state.config.export_max_records as i64),
         offset: None,
+        count: false,
+        sample: false,
+        text_search_config: None,
     };
 
-    let feedbacks = state.service.query_feedbacks(feedback_query).await?;
-    let content = export(&feedbacks, query.format.clone())?;
+    let feedbacks = state
+        .service
+        .query_feedbacks_with_timeout(feedback_query, statement_timeout_ms)
+        .await?;
+    let options = ExportOptions {
+        excluded_context_keys: state.config.export_excluded_context_keys.clone(),
+        csv_date_format: query.date_format.clone(),
+        columns,
+    };
+    let content = export(&feedbacks, query.format.clone(), &options)?;
 
-    let content_type = match query.format {
-        crate::models::ExportFormat::Json => "application/json",
-        crate::models::ExportFormat::Csv => "text/csv",
+    let (content, content_type) = if query.archive {
+        let stats = state.service.get_stats(service.as_deref()).await?;
+        let archive = export_archive(&content, &query.format, &stats)?;
+        (archive, "application/zip")
+    } else {
+        let content_type = match query.format {
+            crate::models::ExportFormat::Json => "application/json",
+            crate::models::ExportFormat::Csv => "text/csv",
+        };
+        (content.into_bytes(), content_type)
     };
 
-    Ok((
+    let digest = content_sha256(&content);
+    let signature = state
+        .config
+        .export_signing_key
+        .as_deref()
+        .map(|key| format!("hmac-sha256={}", sign_content(&content, key)));
+
+    let mut response = (
         StatusCode::OK,
         [(axum::http::header::CONTENT_TYPE, content_type)],
         content,
     )
-        .into_response())
+        .into_response();
+
+    // Hex digests are always valid header values
+    let headers = response.headers_mut();
+    if let Ok(value) = HeaderValue::from_str(&digest) {
+        headers.insert(CONTENT_SHA256_HEADER, value);
+    }
+    if let Some(Ok(value)) = signature.as_deref().map(HeaderValue::from_str) {
+        headers.insert(CONTENT_SIGNATURE_HEADER, value);
+    }
+    if query.archive {
+        headers.insert(
+            CONTENT_DISPOSITION,
+            HeaderValue::from_static("attachment; filename=\"feedbacks-export.zip\""),
+        );
+    }
+
+    Ok(response)
 }
@@ -1,53 +1,412 @@
 use crate::auth::Claims;
-use crate::error::Result;
-use crate::models::{FeedbackQuery, FeedbackResponse, FeedbackStats, FeedbackSubmission};
+use crate::error::{AppError, Result};
+use crate::models::{
+    BulkCreateResponse, CountryStats, CustomTypeStats, Feedback, FeedbackQuery, FeedbackResponse,
+    FeedbackStats, FeedbackSubmission, FeedbackSubmissionForm, FeedbackTypeSchema, FeedbackUpdate,
+    FeedbackView, FirstFeedbackStats, HealthSignal, HealthSignalQuery, ImportResponse,
+    LanguageStats, ReasonStats, RecentComment, RecentCommentsQuery, SparklinePoint, SparklineQuery,
+    StatsRefreshStatus, StatsSchema, TimeseriesPoint, TimeseriesQuery, TodayStatsQuery,
+    ValidationReport, WordCloudQuery, WordFrequency,
+};
 use axum::{
-    extract::{Path, Query, State},
+    extract::{ConnectInfo, Form, FromRequest, Path, Query, RawQuery, Request, State},
+    http::{
+        header::{CACHE_CONTROL, CONTENT_TYPE, ETAG, IF_NONE_MATCH, LOCATION},
+        HeaderMap, HeaderValue, StatusCode,
+    },
+    response::{IntoResponse, Response},
     Extension, Json,
 };
+use sqlx::types::JsonValue;
+use std::net::SocketAddr;
 use uuid::Uuid;
 
-use super::AppState;
+use super::{client_ip, db_timeout_override, AppState};
+
+/// Response header carrying the number of rows matching a `?count=true` query
+const TOTAL_COUNT_HEADER: &str = "x-total-count";
+
+/// Public path of the feedback collection, under which each feedback is addressed by id
+const FEEDBACKS_PATH: &str = "/api/v1/feedbacks";
+
+/// Body of a single submission: JSON, or an HTML form when sent as
+/// `application/x-www-form-urlencoded`. A `context` over CONTEXT_MAX_BYTES is rejected here.
+pub struct SubmissionBody(pub FeedbackSubmission);
+
+#[axum::async_trait]
+impl FromRequest<AppState> for SubmissionBody {
+    type Rejection = Response;
+
+    async fn from_request(
+        req: Request,
+        state: &AppState,
+    ) -> std::result::Result<Self, Self::Rejection> {
+        let is_form = req
+            .headers()
+            .get(CONTENT_TYPE)
+            .and_then(|v| v.to_str().ok())
+            .is_some_and(|v| v.starts_with("application/x-www-form-urlencoded"));
+
+        let submission = if is_form {
+            let Form(form) = Form::<FeedbackSubmissionForm>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            FeedbackSubmission::try_from(form)
+                .map_err(|e| AppError::ValidationError(e).into_response())?
+        } else {
+            let Json(submission) = Json::<FeedbackSubmission>::from_request(req, state)
+                .await
+                .map_err(IntoResponse::into_response)?;
+            submission
+        };
 
-// POST /api/v1/feedbacks - Submit a new feedback
+        check_context_size(submission.context.as_ref(), state.config.context_max_bytes)
+            .map_err(IntoResponse::into_response)?;
+        Ok(SubmissionBody(submission))
+    }
+}
+
+/// Reject a submission or update whose serialized `context` exceeds `max_bytes`, before
+/// any further processing, so oversized blobs never reach validation or the database
+fn check_context_size(context: Option<&JsonValue>, max_bytes: usize) -> Result<()> {
+    let Some(context) = context else {
+        return Ok(());
+    };
+
+    let size = serde_json::to_vec(context).map(|bytes| bytes.len()).unwrap_or(usize::MAX);
+    if size > max_bytes {
+        return Err(AppError::ValidationError(format!(
+            "context is too large ({} bytes, max {})",
+            size, max_bytes
+        )));
+    }
+    Ok(())
+}
+
+// POST /api/v1/feedbacks - Submit a new feedback (JSON or form-encoded). Answers 201 with
+// a Location, or 200 when a repeat submission replaced the feedback for the same message.
 pub async fn create_feedback(
     State(state): State<AppState>,
     Extension(claims): Extension<Claims>,
-    Json(submission): Json<FeedbackSubmission>,
-) -> Result<Json<FeedbackResponse>> {
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    SubmissionBody(mut submission): SubmissionBody,
+) -> Result<Response> {
+    state.service.apply_client_service(claims.client_id(), &mut submission)?;
+    let ip = client_ip(addr.ip(), &headers, state.config.trusted_proxy_hops);
+    submission.country = state.service.resolve_country(ip);
+    let on_behalf_of = impersonated_user(&state, &claims, &mut submission)?;
+    let (user_id, email) = match &on_behalf_of {
+        Some(user_id) => (user_id.as_str(), None),
+        None => (claims.user_id.as_str(), claims.email.as_deref()),
+    };
+
     // Service layer handles all business logic including validation,
     // persistence, metrics recording, and webhook notifications
-    let feedback = state
+    let (feedback, created) = state.service.create_feedback(user_id, email, submission).await?;
+
+    if let Some(on_behalf_of) = &on_behalf_of {
+        tracing::info!(
+            target: "audit",
+            actor = %claims.user_id,
+            client_id = ?claims.client_id(),
+            on_behalf_of = %on_behalf_of,
+            feedback_id = %feedback.id,
+            service = %feedback.service,
+            "Feedback submitted on behalf of another user"
+        );
+    }
+
+    let response: FeedbackResponse = feedback.into();
+    Ok(creation_response(response.id, created, Json(response)))
+}
+
+/// 201 Created with a `Location` header pointing at a new feedback; a plain 200 when an
+/// existing feedback was updated instead
+fn creation_response(id: Uuid, created: bool, body: impl IntoResponse) -> Response {
+    if !created {
+        return body.into_response();
+    }
+    let location = format!("{}/{}", FEEDBACKS_PATH, id);
+    (StatusCode::CREATED, [(LOCATION, location)], body).into_response()
+}
+
+/// Take the submission's `on_behalf_of` user, if any, after checking the token may
+/// impersonate: it must hold the IMPERSONATION_SCOPE scope or realm role (exact match).
+/// Impersonated feedback is stored without an email, as the token's is the caller's own.
+fn impersonated_user(
+    state: &AppState,
+    claims: &Claims,
+    submission: &mut FeedbackSubmission,
+) -> Result<Option<String>> {
+    let Some(on_behalf_of) = submission.on_behalf_of.take() else {
+        return Ok(None);
+    };
+
+    let scope = &state.config.impersonation_scope;
+    if !claims.has_scope(scope) && !claims.has_role(scope) {
+        tracing::warn!(
+            target: "audit",
+            actor = %claims.user_id,
+            client_id = ?claims.client_id(),
+            on_behalf_of = %on_behalf_of,
+            "Rejected on_behalf_of from a token without the impersonation privilege"
+        );
+        return Err(AppError::Forbidden(format!(
+            "Submitting on behalf of another user requires the '{}' scope or role",
+            scope
+        )));
+    }
+
+    let on_behalf_of = on_behalf_of.trim();
+    if on_behalf_of.is_empty() || on_behalf_of.len() > 255 {
+        return Err(AppError::ValidationError(
+            "on_behalf_of must be a user id of 1 to 255 characters".to_string(),
+        ));
+    }
+
+    Ok(Some(on_behalf_of.to_string()))
+}
+
+// POST /api/v1/feedbacks/validate - Check a submission without storing it. Rejections are
+// answered here rather than as AppError, so they are not counted as validation errors.
+pub async fn validate_feedback(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    SubmissionBody(mut submission): SubmissionBody,
+) -> Result<Response> {
+    let outcome = async {
+        state.service.apply_client_service(claims.client_id(), &mut submission)?;
+        let on_behalf_of = impersonated_user(&state, &claims, &mut submission)?;
+        let (user_id, email) = match &on_behalf_of {
+            Some(user_id) => (user_id.as_str(), None),
+            None => (claims.user_id.as_str(), claims.email.as_deref()),
+        };
+        state.service.validate_submission(user_id, email, submission).await
+    }
+    .await;
+
+    validation_response(outcome)
+}
+
+/// 200 with an empty report for an accepted payload, 400 listing the rejection otherwise;
+/// errors other than validation failures are passed on
+fn validation_response(outcome: Result<()>) -> Result<Response> {
+    match outcome {
+        Ok(()) => Ok(Json(ValidationReport { valid: true, errors: vec![] }).into_response()),
+        Err(AppError::ValidationError(message)) => {
+            let report = ValidationReport { valid: false, errors: vec![message] };
+            Ok((StatusCode::BAD_REQUEST, Json(report)).into_response())
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// POST /api/v1/feedbacks/bulk - Submit several feedbacks at once
+pub async fn create_feedbacks_bulk(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    ConnectInfo(addr): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+    Json(mut submissions): Json<Vec<FeedbackSubmission>>,
+) -> Result<Json<BulkCreateResponse>> {
+    let ip = client_ip(addr.ip(), &headers, state.config.trusted_proxy_hops);
+    let country = state.service.resolve_country(ip);
+    for submission in &mut submissions {
+        check_context_size(submission.context.as_ref(), state.config.context_max_bytes)?;
+        if submission.on_behalf_of.is_some() {
+            return Err(AppError::ValidationError(
+                "on_behalf_of is only accepted on single submissions".to_string(),
+            ));
+        }
+        state.service.apply_client_service(claims.client_id(), submission)?;
+        submission.country = country.clone();
+    }
+
+    let response = state
         .service
-        .create_feedback(&claims.sub, claims.email.as_deref(), submission)
+        .create_many(&claims.user_id, claims.email.as_deref(), submissions)
         .await?;
 
-    Ok(Json(feedback.into()))
+    Ok(Json(response))
 }
 
-// GET /api/v1/feedbacks/:id - Get a specific feedback
+// POST /api/v1/feedbacks/import - Import historical feedback as NDJSON (admin)
+pub async fn import_feedbacks(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    body: String,
+) -> Result<Json<ImportResponse>> {
+    if !claims.has_role(&state.config.admin_role) {
+        return Err(AppError::Forbidden(
+            "Importing feedback requires the admin role".to_string(),
+        ));
+    }
+
+    let response = state.service.import_feedbacks(&body).await?;
+    Ok(Json(response))
+}
+
+// GET (and HEAD) /api/v1/feedbacks/:id - Get a specific feedback; answers 304 Not Modified
+// when If-None-Match already holds its ETag
 pub async fn get_feedback(
     State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
     Path(id): Path<Uuid>,
-) -> Result<Json<FeedbackResponse>> {
+    RawQuery(query): RawQuery,
+    headers: HeaderMap,
+) -> Result<Response> {
     let feedback = state.service.get_feedback(id).await?;
+    crate::metrics::record_read_age("get_feedback", std::slice::from_ref(&feedback));
+
+    let is_admin = claims.has_role(&state.config.admin_role);
+    let representation = Representation {
+        admin: is_admin,
+        epoch_millis: crate::models::timestamps::epoch_millis(),
+        pretty: crate::middleware::pretty_requested(query.as_deref(), state.config.json_pretty),
+    };
+    let etag = feedback_etag(&feedback, representation);
+    Ok(conditional_response(
+        etag,
+        headers.get(IF_NONE_MATCH),
+        Json(FeedbackView::for_caller(feedback, is_admin)),
+    ))
+}
+
+/// What sets one representation of a feedback apart from the others
+#[derive(Clone, Copy)]
+struct Representation {
+    admin: bool,
+    epoch_millis: bool,
+    pretty: bool,
+}
+
+/// Strong ETag for one representation of a feedback: changes whenever the feedback is
+/// updated, and differs between the admin and public views, the timestamp formats and
+/// pretty and compact output, since each is a different body
+fn feedback_etag(feedback: &Feedback, representation: Representation) -> String {
+    format!(
+        "\"{}-{}{}{}{}\"",
+        feedback.id,
+        feedback.updated_at.timestamp_micros(),
+        if representation.admin { "-admin" } else { "" },
+        if representation.epoch_millis { "-epoch" } else { "" },
+        if representation.pretty { "-pretty" } else { "" }
+    )
+}
+
+/// `body` with its `etag`, or an empty 304 Not Modified when `if_none_match` lists the tag
+fn conditional_response(
+    etag: String,
+    if_none_match: Option<&HeaderValue>,
+    body: impl IntoResponse,
+) -> Response {
+    if if_none_match.is_some_and(|tags| etag_listed(tags, &etag)) {
+        return (StatusCode::NOT_MODIFIED, [(ETAG, etag)]).into_response();
+    }
+    ([(ETAG, etag)], body).into_response()
+}
+
+/// Whether an If-None-Match value is `*` or lists `etag`, compared weakly as RFC 9110 asks
+fn etag_listed(if_none_match: &HeaderValue, etag: &str) -> bool {
+    let Ok(tags) = if_none_match.to_str() else {
+        return false;
+    };
+    tags.split(',')
+        .map(str::trim)
+        .any(|tag| tag == "*" || tag.trim_start_matches("W/") == etag)
+}
+
+// GET /api/v1/feedbacks/:id/children - Get follow-ups linked to a feedback
+pub async fn get_feedback_children(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+) -> Result<Json<Vec<FeedbackView>>> {
+    let children = state.service.get_children(id).await?;
+
+    // Submitter identity is only shown to admins
+    let is_admin = claims.has_role(&state.config.admin_role);
+    let responses: Vec<FeedbackView> = children
+        .into_iter()
+        .map(|feedback| FeedbackView::for_caller(feedback, is_admin))
+        .collect();
+    Ok(Json(responses))
+}
+
+// PATCH /api/v1/feedbacks/:id - Edit your own feedback, returning the full updated resource
+pub async fn update_feedback(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(id): Path<Uuid>,
+    Json(update): Json<FeedbackUpdate>,
+) -> Result<Json<FeedbackResponse>> {
+    check_context_size(update.context.as_ref(), state.config.context_max_bytes)?;
+    let feedback = state
+        .service
+        .update_feedback(id, &claims.user_id, update)
+        .await?;
+
     Ok(Json(feedback.into()))
 }
 
 // GET /api/v1/feedbacks - Query feedbacks
 pub async fn query_feedbacks(
     State(state): State<AppState>,
-    Query(mut query): Query<FeedbackQuery>,
-) -> Result<Json<Vec<FeedbackResponse>>> {
-    // Apply default limit if not specified
-    if query.limit.is_none() {
-        query.limit = Some(100);
-    }
+    Extension(claims): Extension<Claims>,
+    headers: HeaderMap,
+    Query(query): Query<FeedbackQuery>,
+) -> Result<Response> {
+    let statement_timeout_ms = db_timeout_override(&state, &claims, &headers)?;
+    let count = query.count;
+    let sample = query.sample;
+    let first_page = query.offset.unwrap_or(0) == 0;
 
     // Service layer handles validation
-    let feedbacks = state.service.query_feedbacks(query).await?;
-    let responses: Vec<FeedbackResponse> = feedbacks.into_iter().map(Into::into).collect();
-    Ok(Json(responses))
+    let feedbacks = state
+        .service
+        .query_feedbacks_with_timeout(query, statement_timeout_ms)
+        .await?;
+    crate::metrics::record_read_age("query_feedbacks", &feedbacks);
+
+    // The total rides on every row; an empty first page means nothing matched, while an
+    // empty later page (past the end) carries no total and gets no header
+    let total = match feedbacks.first() {
+        Some(feedback) => feedback.total_count,
+        None => first_page.then_some(0),
+    };
+    let response_headers = query_response_headers(total.filter(|_| count), sample);
+
+    // Some HTTP clients choke on empty 200 bodies; EMPTY_QUERY_NO_CONTENT serves them
+    if feedbacks.is_empty() && state.config.empty_query_no_content {
+        return Ok((StatusCode::NO_CONTENT, response_headers).into_response());
+    }
+
+    // Submitter identity is only shown to admins
+    let is_admin = claims.has_role(&state.config.admin_role);
+    let responses: Vec<FeedbackView> = feedbacks
+        .into_iter()
+        .map(|feedback| FeedbackView::for_caller(feedback, is_admin))
+        .collect();
+    Ok((response_headers, Json(responses)).into_response())
+}
+
+/// Headers for a feedback query page: the `X-Total-Count` when known, and `no-store` on a
+/// sample, which differs on every request and must not be replayed from a cache
+fn query_response_headers(total: Option<i64>, sample: bool) -> HeaderMap {
+    let mut headers = HeaderMap::new();
+    if let Some(total) = total {
+        headers.insert(TOTAL_COUNT_HEADER, total.into());
+    }
+    if sample {
+        headers.insert(CACHE_CONTROL, HeaderValue::from_static("no-store"));
+    }
+    headers
+}
+
+// GET /api/v1/feedback-types - Describe accepted feedback types and their rules
+pub async fn get_feedback_types(State(state): State<AppState>) -> Json<Vec<FeedbackTypeSchema>> {
+    Json(state.service.feedback_type_schemas())
 }
 
 // GET /api/v1/feedbacks/stats - Get feedback statistics
@@ -56,6 +415,301 @@ pub async fn get_stats(
     Query(params): Query<serde_json::Value>,
 ) -> Result<Json<Vec<FeedbackStats>>> {
     let service = params.get("service").and_then(|v| v.as_str());
+
+    // `?group=true` rolls services up into their configured groups; `service` then
+    // names a group
+    let grouped = params.get("group").and_then(|v| v.as_str()) == Some("true");
+    let stats = if grouped {
+        state.service.get_grouped_stats(service).await?
+    } else {
+        state.service.get_stats(service).await?
+    };
+    Ok(Json(stats))
+}
+
+// GET /api/v1/feedbacks/stats/schema - Describe the fields of the stats entries
+pub async fn get_stats_schema() -> Json<StatsSchema> {
+    Json(StatsSchema::current())
+}
+
+// GET /api/v1/feedbacks/stats/today - Get statistics for the current day in a timezone
+pub async fn get_today_stats(
+    State(state): State<AppState>,
+    Query(query): Query<TodayStatsQuery>,
+) -> Result<Json<Vec<FeedbackStats>>> {
+    let stats = state.service.get_today_stats(query).await?;
+    Ok(Json(stats))
+}
+
+// GET /api/v1/feedbacks/stats/prometheus - Per-service stats as Prometheus text exposition,
+// for external scrapers
+pub async fn get_stats_prometheus(
+    State(state): State<AppState>,
+    Query(params): Query<serde_json::Value>,
+) -> Result<Response> {
+    let service = params.get("service").and_then(|v| v.as_str());
     let stats = state.service.get_stats(service).await?;
+    let body = crate::metrics::render_stats(&stats)?;
+    Ok(([(CONTENT_TYPE, "text/plain; version=0.0.4")], body).into_response())
+}
+
+// GET /api/v1/feedbacks/stats/languages - Get feedback statistics per service and language
+pub async fn get_language_stats(
+    State(state): State<AppState>,
+    Query(params): Query<serde_json::Value>,
+) -> Result<Json<Vec<LanguageStats>>> {
+    let service = params.get("service").and_then(|v| v.as_str());
+    let stats = state.service.get_language_stats(service).await?;
+    Ok(Json(stats))
+}
+
+// GET /api/v1/feedbacks/stats/countries - Get feedback statistics per service and country
+pub async fn get_country_stats(
+    State(state): State<AppState>,
+    Query(params): Query<serde_json::Value>,
+) -> Result<Json<Vec<CountryStats>>> {
+    let service = params.get("service").and_then(|v| v.as_str());
+    let stats = state.service.get_country_stats(service).await?;
     Ok(Json(stats))
 }
+
+// GET /api/v1/feedbacks/stats/custom-types - Count feedback per service and custom type
+pub async fn get_custom_type_stats(
+    State(state): State<AppState>,
+    Query(params): Query<serde_json::Value>,
+) -> Result<Json<Vec<CustomTypeStats>>> {
+    let service = params.get("service").and_then(|v| v.as_str());
+    let stats = state.service.get_custom_type_stats(service).await?;
+    Ok(Json(stats))
+}
+
+// GET /api/v1/feedbacks/stats/reasons - Count thumbs-down feedback per service and reason
+pub async fn get_reason_stats(
+    State(state): State<AppState>,
+    Query(params): Query<serde_json::Value>,
+) -> Result<Json<Vec<ReasonStats>>> {
+    let service = params.get("service").and_then(|v| v.as_str());
+    let stats = state.service.get_reason_stats(service).await?;
+    Ok(Json(stats))
+}
+
+// GET /api/v1/feedbacks/stats/first-feedback - When each service got its first feedback
+pub async fn get_first_feedback_stats(
+    State(state): State<AppState>,
+    Query(params): Query<serde_json::Value>,
+) -> Result<Json<Vec<FirstFeedbackStats>>> {
+    let service = params.get("service").and_then(|v| v.as_str());
+    let stats = state.service.get_first_feedback_stats(service).await?;
+    Ok(Json(stats))
+}
+
+// GET /api/v1/feedbacks/stats/refresh - When the stats view was last refreshed
+pub async fn get_stats_refresh_status(
+    State(state): State<AppState>,
+) -> Json<StatsRefreshStatus> {
+    Json(state.service.stats_refresh_status())
+}
+
+// POST /api/v1/feedbacks/stats/refresh - Refresh the stats view (admin)
+pub async fn refresh_stats(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<StatsRefreshStatus>> {
+    if !claims.has_role(&state.config.admin_role) {
+        return Err(AppError::Forbidden(
+            "Refreshing stats requires the admin role".to_string(),
+        ));
+    }
+
+    let status = state.service.refresh_stats().await?;
+    Ok(Json(status))
+}
+
+// GET /api/v1/feedbacks/stats/timeseries - Get daily feedback statistics
+pub async fn get_timeseries(
+    State(state): State<AppState>,
+    Query(query): Query<TimeseriesQuery>,
+) -> Result<Json<Vec<TimeseriesPoint>>> {
+    let points = state.service.get_timeseries(query).await?;
+    Ok(Json(points))
+}
+
+// GET /api/v1/feedbacks/sparkline - Get daily feedback counts only, for sparklines
+pub async fn get_sparkline(
+    State(state): State<AppState>,
+    Query(query): Query<SparklineQuery>,
+) -> Result<Json<Vec<SparklinePoint>>> {
+    let points = state.service.get_sparkline(query).await?;
+    Ok(Json(points))
+}
+
+// GET /api/v1/feedbacks/health-signal - Good/warning/critical status of a service's recent
+// feedback
+pub async fn get_health_signal(
+    State(state): State<AppState>,
+    Query(query): Query<HealthSignalQuery>,
+) -> Result<Json<HealthSignal>> {
+    let signal = state.service.get_health_signal(query).await?;
+    Ok(Json(signal))
+}
+
+// GET /api/v1/feedbacks/recent-comments - Get the newest non-empty comments
+pub async fn get_recent_comments(
+    State(state): State<AppState>,
+    Query(query): Query<RecentCommentsQuery>,
+) -> Result<Json<Vec<RecentComment>>> {
+    let comments = state.service.get_recent_comments(query).await?;
+    Ok(Json(comments))
+}
+
+// GET /api/v1/feedbacks/wordcloud - Get the most frequent words in comments
+pub async fn get_word_cloud(
+    State(state): State<AppState>,
+    Query(query): Query<WordCloudQuery>,
+) -> Result<Json<Vec<WordFrequency>>> {
+    let words = state.service.get_word_cloud(query).await?;
+    Ok(Json(words))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_created_points_at_new_feedback() {
+        let id = Uuid::new_v4();
+
+        let response = creation_response(id, true, "{}");
+
+        assert_eq!(response.status(), StatusCode::CREATED);
+        assert_eq!(
+            response.headers()[LOCATION],
+            format!("/api/v1/feedbacks/{}", id).as_str()
+        );
+    }
+
+    #[test]
+    fn test_check_context_size() {
+        let context = serde_json::json!({"call_id": "abc"});
+        let size = serde_json::to_vec(&context).unwrap().len();
+
+        assert!(check_context_size(Some(&context), size).is_ok());
+        assert!(check_context_size(None, 2).is_ok());
+        match check_context_size(Some(&context), size - 1) {
+            Err(AppError::ValidationError(message)) => {
+                assert!(message.contains(&format!("{} bytes, max {}", size, size - 1)))
+            }
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_replaced_feedback_is_not_created() {
+        let response = creation_response(Uuid::new_v4(), false, "{}");
+
+        assert_eq!(response.status(), StatusCode::OK);
+        assert!(!response.headers().contains_key(LOCATION));
+    }
+
+    #[test]
+    fn test_sample_is_never_cached() {
+        let headers = query_response_headers(Some(3), true);
+        assert_eq!(headers[TOTAL_COUNT_HEADER], "3");
+        assert_eq!(headers[CACHE_CONTROL], "no-store");
+
+        let headers = query_response_headers(None, false);
+        assert!(headers.is_empty());
+    }
+
+    fn feedback_at(updated_at: &str) -> Feedback {
+        let updated_at = chrono::DateTime::parse_from_rfc3339(updated_at)
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        Feedback {
+            id: Uuid::nil(),
+            user_id: "user".to_string(),
+            user_email: None,
+            service: "test-service".to_string(),
+            feedback_type: crate::models::FeedbackType::Thumbs,
+            rating: None,
+            thumbs_up: Some(true),
+            comment: None,
+            context: None,
+            parent_id: None,
+            language: None,
+            reason: None,
+            country: None,
+            custom_type: None,
+            created_at: updated_at,
+            updated_at,
+            highlight: None,
+            total_count: None,
+        }
+    }
+
+    #[test]
+    fn test_etag_differs_per_representation() {
+        let feedback = feedback_at("2024-03-01T10:00:00Z");
+        let plain = Representation { admin: false, epoch_millis: false, pretty: false };
+        let variants = [
+            plain,
+            Representation { admin: true, ..plain },
+            Representation { epoch_millis: true, ..plain },
+            Representation { pretty: true, ..plain },
+        ];
+
+        let etags: std::collections::HashSet<_> =
+            variants.iter().map(|r| feedback_etag(&feedback, *r)).collect();
+        assert_eq!(etags.len(), variants.len());
+        assert_ne!(
+            feedback_etag(&feedback, plain),
+            feedback_etag(&feedback_at("2024-03-01T10:00:01Z"), plain)
+        );
+    }
+
+    #[tokio::test]
+    async fn test_matching_etag_is_not_modified() {
+        let etag = "\"abc-1\"".to_string();
+        let body = || Json(serde_json::json!({"id": "abc"}));
+
+        let tags = HeaderValue::from_static("\"other\", W/\"abc-1\"");
+        let response = conditional_response(etag.clone(), Some(&tags), body());
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+        assert_eq!(response.headers()[ETAG], "\"abc-1\"");
+        let bytes = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        assert!(bytes.is_empty());
+
+        let any = HeaderValue::from_static("*");
+        let response = conditional_response(etag.clone(), Some(&any), body());
+        assert_eq!(response.status(), StatusCode::NOT_MODIFIED);
+
+        let stale = HeaderValue::from_static("\"abc-0\"");
+        let response = conditional_response(etag.clone(), Some(&stale), body());
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(response.headers()[ETAG], "\"abc-1\"");
+        assert_eq!(conditional_response(etag, None, body()).status(), StatusCode::OK);
+    }
+
+    async fn report_of(response: Response) -> serde_json::Value {
+        let body = axum::body::to_bytes(response.into_body(), usize::MAX).await.unwrap();
+        serde_json::from_slice(&body).unwrap()
+    }
+
+    #[tokio::test]
+    async fn test_validation_report_shape() {
+        let response = validation_response(Ok(())).unwrap();
+        assert_eq!(response.status(), StatusCode::OK);
+        assert_eq!(report_of(response).await, serde_json::json!({"valid": true, "errors": []}));
+
+        let rejected = Err(AppError::ValidationError("Rating out of range".to_string()));
+        let response = validation_response(rejected).unwrap();
+        assert_eq!(response.status(), StatusCode::BAD_REQUEST);
+        assert_eq!(
+            report_of(response).await,
+            serde_json::json!({"valid": false, "errors": ["Rating out of range"]})
+        );
+
+        let forbidden = Err(AppError::Forbidden("nope".to_string()));
+        assert!(matches!(validation_response(forbidden), Err(AppError::Forbidden(_))));
+    }
+}
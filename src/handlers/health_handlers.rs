@@ -1,9 +1,13 @@
-use crate::error::Result;
+use crate::auth::Claims;
+use crate::error::{AppError, Result};
+use crate::models::{
+    MetricsReinitialization, WebhookDeliveryReport, WebhookDeliveryStatus, WebhookTestQuery,
+};
 use axum::{
-    extract::State,
+    extract::{Query, State},
     http::StatusCode,
     response::{IntoResponse, Response},
-    Json,
+    Extension, Json,
 };
 
 use super::AppState;
@@ -22,6 +26,65 @@ pub async fn metrics_handler() -> Result<Response> {
         .into_response())
 }
 
+// POST /api/v1/admin/metrics/reinitialize - Reset feedback counters from the database (admin)
+pub async fn reinitialize_metrics(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<MetricsReinitialization>> {
+    if !claims.has_role(&state.config.admin_role) {
+        return Err(AppError::Forbidden(
+            "Reinitializing metrics requires the admin role".to_string(),
+        ));
+    }
+
+    let outcome = state.service.reinitialize_metrics().await?;
+    Ok(Json(outcome))
+}
+
+// POST /api/v1/admin/webhooks/pause - Stop delivering webhooks on this instance (admin)
+pub async fn pause_webhooks(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<WebhookDeliveryStatus>> {
+    if !claims.has_role(&state.config.admin_role) {
+        return Err(AppError::Forbidden(
+            "Pausing webhooks requires the admin role".to_string(),
+        ));
+    }
+
+    Ok(Json(state.service.set_webhooks_paused(true)))
+}
+
+// POST /api/v1/admin/webhooks/resume - Deliver webhooks again on this instance (admin)
+pub async fn resume_webhooks(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+) -> Result<Json<WebhookDeliveryStatus>> {
+    if !claims.has_role(&state.config.admin_role) {
+        return Err(AppError::Forbidden(
+            "Resuming webhooks requires the admin role".to_string(),
+        ));
+    }
+
+    Ok(Json(state.service.set_webhooks_paused(false)))
+}
+
+// POST /api/v1/admin/webhooks/test - Send a test webhook and report each URL's outcome (admin)
+pub async fn test_webhooks(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Query(query): Query<WebhookTestQuery>,
+) -> Result<Json<WebhookDeliveryReport>> {
+    if !claims.has_role(&state.config.admin_role) {
+        return Err(AppError::Forbidden(
+            "Testing webhooks requires the admin role".to_string(),
+        ));
+    }
+
+    let report = state.service.test_webhooks(query.service.as_deref()).await?;
+    Ok(Json(report))
+}
+
 // GET /health - Health check endpoint
 pub async fn health_check(
     State(state): State<AppState>,
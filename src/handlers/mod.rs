@@ -18,9 +18,14 @@
 //! - `feedback_handlers`: Core feedback CRUD operations
 //! - `export_handlers`: Data export functionality
 //! - `health_handlers`: Health checks and metrics
+//! - `user_handlers`: Per-user data access (subject access exports, admin timelines)
 
+use crate::auth::Claims;
 use crate::config::Config;
+use crate::error::{AppError, Result};
 use crate::services::FeedbackService;
+use axum::http::{HeaderMap, HeaderValue};
+use std::net::IpAddr;
 use std::sync::Arc;
 
 // Handler modules
@@ -28,12 +33,24 @@ mod auth_handlers;
 mod export_handlers;
 mod feedback_handlers;
 mod health_handlers;
+mod user_handlers;
 
 // Re-export handler functions
 pub use auth_handlers::{login, LoginRequest, LoginResponse};
 pub use export_handlers::export_feedbacks;
-pub use feedback_handlers::{create_feedback, get_feedback, get_stats, query_feedbacks};
-pub use health_handlers::{health_check, metrics_handler};
+pub use feedback_handlers::{
+    create_feedback, create_feedbacks_bulk, get_country_stats, get_custom_type_stats, get_feedback,
+    get_feedback_children, get_feedback_types, get_first_feedback_stats, get_health_signal,
+    get_language_stats, get_reason_stats, get_recent_comments, get_sparkline, get_stats,
+    get_stats_prometheus, get_stats_refresh_status, get_stats_schema, get_timeseries,
+    get_today_stats, get_word_cloud, import_feedbacks, query_feedbacks, refresh_stats,
+    update_feedback, validate_feedback,
+};
+pub use health_handlers::{
+    health_check, metrics_handler, pause_webhooks, reinitialize_metrics, resume_webhooks,
+    test_webhooks,
+};
+pub use user_handlers::{export_user_data, get_user_feedbacks};
 
 // Application state shared across handlers
 #[derive(Clone)]
@@ -41,3 +58,150 @@ pub struct AppState {
     pub service: Arc<FeedbackService>,
     pub config: Arc<Config>,
 }
+
+/// Header through which admins raise the statement timeout of one heavy query
+const DB_TIMEOUT_HEADER: &str = "x-db-timeout-ms";
+
+/// The statement timeout requested with `X-DB-Timeout-Ms`, if any; only admins may set it
+pub(crate) fn db_timeout_override(
+    state: &AppState,
+    claims: &Claims,
+    headers: &HeaderMap,
+) -> Result<Option<u64>> {
+    let Some(value) = headers.get(DB_TIMEOUT_HEADER) else {
+        return Ok(None);
+    };
+
+    if !claims.has_role(&state.config.admin_role) {
+        return Err(AppError::Forbidden(
+            "Only admins can set X-DB-Timeout-Ms".to_string(),
+        ));
+    }
+
+    parse_timeout_ms(value).map(Some)
+}
+
+/// The milliseconds of an `X-DB-Timeout-Ms` value
+fn parse_timeout_ms(value: &HeaderValue) -> Result<u64> {
+    value
+        .to_str()
+        .ok()
+        .and_then(|v| v.trim().parse().ok())
+        .ok_or_else(|| {
+            AppError::ValidationError(
+                "X-DB-Timeout-Ms must be a number of milliseconds".to_string(),
+            )
+        })
+}
+
+/// Address of the submitter: with `trusted_hops` proxies in front, the entry they added
+/// for it to `Forwarded` (or, without one, `X-Forwarded-For`); the peer address when
+/// no proxy is trusted or the header holds no usable address
+pub(crate) fn client_ip(peer: IpAddr, headers: &HeaderMap, trusted_hops: usize) -> IpAddr {
+    if trusted_hops == 0 {
+        return peer;
+    }
+
+    let forwarded: Vec<&str> = headers
+        .get_all("forwarded")
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(','))
+        .filter_map(|element| {
+            element.split(';').find_map(|pair| {
+                let (name, value) = pair.split_once('=')?;
+                name.trim().eq_ignore_ascii_case("for").then_some(value.trim())
+            })
+        })
+        .collect();
+    let hops = if forwarded.is_empty() {
+        headers
+            .get_all("x-forwarded-for")
+            .iter()
+            .filter_map(|value| value.to_str().ok())
+            .flat_map(|value| value.split(','))
+            .map(str::trim)
+            .collect()
+    } else {
+        forwarded
+    };
+
+    // Each trusted proxy appended the address it was reached from; entries further left
+    // come from the client and can be forged
+    hops.get(hops.len().saturating_sub(trusted_hops))
+        .and_then(|node| parse_forwarded_node(node))
+        .unwrap_or(peer)
+}
+
+/// The address of a `Forwarded` node (`192.0.2.1`, `"[2001:db8::1]:443"`) or of an
+/// `X-Forwarded-For` entry; `None` for `unknown` or obfuscated identifiers
+fn parse_forwarded_node(node: &str) -> Option<IpAddr> {
+    let node = node.trim_matches('"');
+    if let Some(rest) = node.strip_prefix('[') {
+        return rest.split_once(']')?.0.parse().ok();
+    }
+    node.parse()
+        .ok()
+        .or_else(|| node.rsplit_once(':')?.0.parse().ok())
+}
+
+// Any endpoint turned off by an ENABLE_* flag
+pub async fn endpoint_disabled() -> Result<()> {
+    Err(AppError::NotFound("This endpoint is disabled".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn headers(pairs: &[(&'static str, &'static str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in pairs {
+            headers.append(*name, value.parse().unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn test_parse_timeout_ms() {
+        assert_eq!(parse_timeout_ms(&HeaderValue::from_static(" 5000 ")).unwrap(), 5000);
+        for invalid in ["", "5s", "-1", "1.5"] {
+            assert!(parse_timeout_ms(&HeaderValue::from_static(invalid)).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_client_ip_ignores_headers_without_trusted_proxies() {
+        let peer: IpAddr = "10.0.0.1".parse().unwrap();
+        let forged = headers(&[("x-forwarded-for", "203.0.113.7")]);
+
+        assert_eq!(client_ip(peer, &forged, 0), peer);
+    }
+
+    #[test]
+    fn test_client_ip_skips_forged_entries() {
+        let peer: IpAddr = "10.0.0.1".parse().unwrap();
+        let chain = headers(&[("x-forwarded-for", "198.51.100.9, 203.0.113.7, 10.0.0.2")]);
+
+        assert_eq!(client_ip(peer, &chain, 1), "10.0.0.2".parse::<IpAddr>().unwrap());
+        assert_eq!(client_ip(peer, &chain, 2), "203.0.113.7".parse::<IpAddr>().unwrap());
+        // More proxies than entries: the leftmost was still added by one of them
+        assert_eq!(client_ip(peer, &chain, 5), "198.51.100.9".parse::<IpAddr>().unwrap());
+        assert_eq!(client_ip(peer, &HeaderMap::new(), 1), peer);
+    }
+
+    #[test]
+    fn test_client_ip_prefers_forwarded() {
+        let peer: IpAddr = "10.0.0.1".parse().unwrap();
+        let both = headers(&[
+            ("x-forwarded-for", "198.51.100.9"),
+            ("forwarded", "for=192.0.2.60;proto=https, for=\"[2001:db8::1]:4711\""),
+        ]);
+
+        assert_eq!(client_ip(peer, &both, 1), "2001:db8::1".parse::<IpAddr>().unwrap());
+        assert_eq!(client_ip(peer, &both, 2), "192.0.2.60".parse::<IpAddr>().unwrap());
+
+        let unknown = headers(&[("forwarded", "for=unknown")]);
+        assert_eq!(client_ip(peer, &unknown, 1), peer);
+    }
+}
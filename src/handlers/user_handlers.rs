@@ -0,0 +1,64 @@
+use crate::auth::Claims;
+use crate::error::{AppError, Result};
+use crate::models::{FeedbackQuery, UserFeedbackTimeline};
+use axum::{
+    extract::{Path, Query, State},
+    http::{header, StatusCode},
+    response::{IntoResponse, Response},
+    Extension, Json,
+};
+
+use super::AppState;
+
+// GET /api/v1/users/:user_id/export - Export everything held about a user (admin or self)
+pub async fn export_user_data(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(user_id): Path<String>,
+) -> Result<Response> {
+    if claims.user_id != user_id && !claims.has_role(&state.config.admin_role) {
+        return Err(AppError::Forbidden(
+            "You can only export your own data".to_string(),
+        ));
+    }
+
+    let bundle = state.service.export_user_data(&user_id).await?;
+
+    // Keep the filename header-safe whatever the user id looks like
+    let safe_user_id: String = user_id
+        .chars()
+        .map(|c| if c.is_ascii_alphanumeric() || c == '-' || c == '_' { c } else { '_' })
+        .collect();
+    let disposition = format!("attachment; filename=\"user-{}-export.json\"", safe_user_id);
+
+    Ok((
+        StatusCode::OK,
+        [(header::CONTENT_DISPOSITION, disposition)],
+        Json(bundle),
+    )
+        .into_response())
+}
+
+// GET /api/v1/users/:user_id/feedbacks - A user's feedback across services, newest first (admin)
+pub async fn get_user_feedbacks(
+    State(state): State<AppState>,
+    Extension(claims): Extension<Claims>,
+    Path(user_id): Path<String>,
+    Query(query): Query<FeedbackQuery>,
+) -> Result<Json<UserFeedbackTimeline>> {
+    if !claims.has_role(&state.config.admin_role) {
+        return Err(AppError::Forbidden(
+            "Listing another user's feedback requires the admin role".to_string(),
+        ));
+    }
+
+    let feedbacks = state.service.get_user_feedbacks(&user_id, query).await?;
+    crate::metrics::record_read_age("get_user_feedbacks", &feedbacks);
+    let summary = state.service.get_user_summary(&user_id).await?;
+
+    Ok(Json(UserFeedbackTimeline {
+        user_id,
+        summary,
+        feedbacks: feedbacks.into_iter().map(Into::into).collect(),
+    }))
+}
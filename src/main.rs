@@ -1,15 +1,25 @@
-use feedback_api::auth::{auth_middleware, AuthState};
+use feedback_api::auth::{
+    auth_middleware, require_fresh_auth, require_scope, AuthState, SCOPE_EXPORT, SCOPE_READ,
+    SCOPE_WRITE,
+};
 use feedback_api::config::Config;
 use feedback_api::db::Database;
 use feedback_api::handlers::{
-    create_feedback, export_feedbacks, get_feedback, get_stats, health_check, login,
-    metrics_handler, query_feedbacks, AppState,
+    create_feedback, create_feedbacks_bulk, endpoint_disabled, export_feedbacks, export_user_data,
+    get_country_stats, get_custom_type_stats, get_feedback, get_feedback_children,
+    get_feedback_types, get_first_feedback_stats, get_health_signal, get_language_stats,
+    get_reason_stats, get_recent_comments, get_sparkline, get_stats, get_stats_prometheus,
+    get_stats_refresh_status, get_stats_schema, get_timeseries, get_today_stats, get_user_feedbacks,
+    get_word_cloud, health_check, import_feedbacks, login, metrics_handler, pause_webhooks,
+    query_feedbacks, refresh_stats, reinitialize_metrics, resume_webhooks, test_webhooks,
+    update_feedback, validate_feedback, AppState,
 };
+use feedback_api::middleware::{cache_control_middleware, CachePolicy, RequestLogging};
 use feedback_api::repositories::PostgresFeedbackRepository;
 use feedback_api::services::FeedbackService;
 use axum::{
-    http::{header::{AUTHORIZATION, CONTENT_TYPE}, HeaderValue, Method},
-    routing::{get, post},
+    http::{header::{AUTHORIZATION, CONTENT_TYPE}, HeaderName, HeaderValue, Method},
+    routing::{any, get, patch, post, MethodRouter},
     Router,
 };
 use std::net::SocketAddr;
@@ -19,6 +29,52 @@ use tower_http::cors::CorsLayer;
 use tower_http::limit::RequestBodyLimitLayer;
 use tower_http::trace::TraceLayer;
 
+/// Serve `route` when its feature is enabled; otherwise answer 404 on every method, so a
+/// disabled path does not fall through to a neighbouring route like `/feedbacks/:id`
+fn optional(enabled: bool, route: MethodRouter<AppState>) -> MethodRouter<AppState> {
+    if enabled {
+        route
+    } else {
+        any(endpoint_disabled)
+    }
+}
+
+/// Require the OAuth `scope` on `route` when scope enforcement is on
+fn scoped(
+    enforce: bool,
+    scope: &'static str,
+    route: MethodRouter<AppState>,
+) -> MethodRouter<AppState> {
+    if enforce {
+        route.route_layer(axum::middleware::from_fn_with_state(scope, require_scope))
+    } else {
+        route
+    }
+}
+
+/// Let clients cache successful reads of `route` for `max_age_secs` (0 = not cached)
+fn cached(
+    max_age_secs: u64,
+    shared: bool,
+    route: MethodRouter<AppState>,
+) -> MethodRouter<AppState> {
+    if max_age_secs > 0 {
+        let policy = CachePolicy { max_age_secs, shared };
+        route.route_layer(axum::middleware::from_fn_with_state(policy, cache_control_middleware))
+    } else {
+        route
+    }
+}
+
+/// Require a recent login on `route` when `max_age_secs` is set (0 = any valid token)
+fn fresh(max_age_secs: u64, route: MethodRouter<AppState>) -> MethodRouter<AppState> {
+    if max_age_secs > 0 {
+        route.route_layer(axum::middleware::from_fn_with_state(max_age_secs, require_fresh_auth))
+    } else {
+        route
+    }
+}
+
 #[tokio::main]
 async fn main() -> anyhow::Result<()> {
     // Initialize structured logging
@@ -29,25 +85,61 @@ async fn main() -> anyhow::Result<()> {
     tracing::info!("Configuration loaded successfully");
 
     // Connect to database
-    let db = Database::new(&config.database_url).await?;
+    let seconds = |secs: u64| (secs > 0).then(|| Duration::from_secs(secs));
+    let db = Database::connect(
+        &config.database_url,
+        config.db_warmup_connections,
+        seconds(config.db_idle_timeout_secs),
+        seconds(config.db_max_lifetime_secs),
+    )
+    .await?;
     tracing::info!("Database connected successfully");
 
+    if config.db_warmup_connections > 0 {
+        db.warm_up(config.db_warmup_connections).await?;
+        tracing::info!(
+            connections = config.db_warmup_connections,
+            "Database connection pool warmed up"
+        );
+    }
+
     // Run migrations
     db.run_migrations().await?;
     tracing::info!("Database migrations completed");
 
+    if !config.indexed_context_keys.is_empty() {
+        db.ensure_context_indexes(&config.indexed_context_keys).await?;
+        tracing::info!(keys = ?config.indexed_context_keys, "Context key indexes ready");
+    }
+
     // Create repository layer
     let repository = Arc::new(PostgresFeedbackRepository::new(db));
 
+    if let Some(namespace) = &config.metrics_namespace {
+        feedback_api::metrics::set_namespace(namespace);
+    }
+
     // Initialize metrics from database via repository
-    feedback_api::metrics::initialize_metrics_from_db(repository.as_ref()).await?;
-    tracing::info!("Metrics initialized from database");
+    match feedback_api::metrics::initialize_metrics_with_retry(
+        repository.as_ref(),
+        config.metrics_init_retries,
+    )
+    .await
+    {
+        Ok(()) => tracing::info!("Metrics initialized from database"),
+        Err(e) if !config.metrics_init_required => tracing::error!(
+            error = %e,
+            "Failed to initialize metrics from database, starting with empty counters"
+        ),
+        Err(e) => return Err(e),
+    }
 
     // Create auth state
     let auth_state = AuthState::new(
-        config.keycloak_url.clone(),
+        config.keycloak_urls.clone(),
         config.keycloak_realm.clone(),
         config.keycloak_jwks_cache_ttl,
+        config.user_id_claim.clone(),
     );
 
     // Create app state configuration
@@ -56,6 +148,32 @@ async fn main() -> anyhow::Result<()> {
     // Create service layer with repository and config
     let feedback_service = Arc::new(FeedbackService::new(repository, config_arc.clone()));
 
+    // Pre-compute stats for the busiest services so the first dashboard load is fast
+    if config.enable_stats && !config.stats_warmup_services.is_empty() {
+        feedback_service
+            .warm_stats_cache(&config.stats_warmup_services)
+            .await;
+    }
+
+    // Purge feedback past its retention period in the background
+    if config.retention_days > 0 {
+        let service = feedback_service.clone();
+        let interval = Duration::from_secs(config.retention_purge_interval_secs.max(1));
+        tokio::spawn(async move {
+            let mut ticker = tokio::time::interval(interval);
+            loop {
+                ticker.tick().await;
+                if let Err(e) = service.purge_expired_feedback().await {
+                    tracing::error!(error = ?e, "Retention purge failed");
+                }
+            }
+        });
+        tracing::info!(
+            retention_days = config.retention_days,
+            "Retention purge job started"
+        );
+    }
+
     // Create app state
     let app_state = AppState {
         service: feedback_service,
@@ -63,12 +181,108 @@ async fn main() -> anyhow::Result<()> {
     };
 
     // Build protected routes (require authentication + rate limiting)
+    // Scope each endpoint for REQUIRE_SCOPES; checked after authentication
+    let read = |route| scoped(config.require_scopes, SCOPE_READ, route);
+    let write = |route| scoped(config.require_scopes, SCOPE_WRITE, route);
+    let export = |route| scoped(config.require_scopes, SCOPE_EXPORT, route);
+    // Stats are the same for every caller; query results depend on the caller's role
+    let shared_cache = |route| cached(config.response_cache_max_age_secs, true, route);
+    let private_cache = |route| cached(config.response_cache_max_age_secs, false, route);
+
     let protected_routes = Router::new()
-        .route("/feedbacks", post(create_feedback))
-        .route("/feedbacks", get(query_feedbacks))
-        .route("/feedbacks/:id", get(get_feedback))
-        .route("/feedbacks/stats", get(get_stats))
-        .route("/feedbacks/export", get(export_feedbacks))
+        .route(
+            "/feedbacks",
+            private_cache(read(get(query_feedbacks))).merge(write(post(create_feedback))),
+        )
+        .route("/feedbacks/validate", write(post(validate_feedback)))
+        .route(
+            "/feedbacks/bulk",
+            optional(config.enable_bulk, write(post(create_feedbacks_bulk))),
+        )
+        .route(
+            "/feedbacks/import",
+            optional(config.enable_import, write(post(import_feedbacks))),
+        )
+        .route(
+            "/feedbacks/:id",
+            read(get(get_feedback)).merge(write(patch(update_feedback))),
+        )
+        .route("/feedbacks/:id/children", read(get(get_feedback_children)))
+        .route("/feedbacks/recent-comments", read(get(get_recent_comments)))
+        .route("/feedback-types", read(get(get_feedback_types)))
+        .route(
+            "/feedbacks/stats",
+            optional(config.enable_stats, shared_cache(read(get(get_stats)))),
+        )
+        .route(
+            "/feedbacks/stats/schema",
+            optional(config.enable_stats, shared_cache(read(get(get_stats_schema)))),
+        )
+        // The running day changes with every submission, so it is never cached
+        .route(
+            "/feedbacks/stats/today",
+            optional(config.enable_stats, read(get(get_today_stats))),
+        )
+        .route(
+            "/feedbacks/stats/timeseries",
+            optional(config.enable_stats, shared_cache(read(get(get_timeseries)))),
+        )
+        .route(
+            "/feedbacks/stats/prometheus",
+            optional(config.enable_stats, shared_cache(read(get(get_stats_prometheus)))),
+        )
+        .route(
+            "/feedbacks/stats/languages",
+            optional(config.enable_stats, shared_cache(read(get(get_language_stats)))),
+        )
+        .route(
+            "/feedbacks/stats/countries",
+            optional(config.enable_stats, shared_cache(read(get(get_country_stats)))),
+        )
+        .route(
+            "/feedbacks/stats/custom-types",
+            optional(config.enable_stats, shared_cache(read(get(get_custom_type_stats)))),
+        )
+        .route(
+            "/feedbacks/stats/reasons",
+            optional(config.enable_stats, shared_cache(read(get(get_reason_stats)))),
+        )
+        .route(
+            "/feedbacks/stats/first-feedback",
+            optional(config.enable_stats, shared_cache(read(get(get_first_feedback_stats)))),
+        )
+        .route(
+            "/feedbacks/sparkline",
+            optional(config.enable_stats, shared_cache(read(get(get_sparkline)))),
+        )
+        .route(
+            "/feedbacks/health-signal",
+            optional(config.enable_stats, shared_cache(read(get(get_health_signal)))),
+        )
+        .route(
+            "/feedbacks/wordcloud",
+            optional(config.enable_stats, shared_cache(read(get(get_word_cloud)))),
+        )
+        .route(
+            "/feedbacks/stats/refresh",
+            optional(
+                config.enable_stats,
+                read(get(get_stats_refresh_status)).merge(write(post(refresh_stats))),
+            ),
+        )
+        .route(
+            "/feedbacks/export",
+            optional(
+                config.enable_export,
+                fresh(config.export_max_auth_age_secs, export(get(export_feedbacks))),
+            ),
+        )
+        .route("/users/:user_id/export", export(get(export_user_data)))
+        .route("/users/:user_id/feedbacks", read(get(get_user_feedbacks)))
+        .route("/admin/metrics/reinitialize", write(post(reinitialize_metrics)))
+        .route("/admin/webhooks/pause", write(post(pause_webhooks)))
+        .route("/admin/webhooks/resume", write(post(resume_webhooks)))
+        .route("/admin/webhooks/test", write(post(test_webhooks)))
         .route_layer(axum::middleware::from_fn_with_state(
             auth_state.clone(),
             auth_middleware,
@@ -90,6 +304,9 @@ async fn main() -> anyhow::Result<()> {
     // Combine public and auth routes
     let public_routes = health_routes.merge(auth_routes);
 
+    // Header carrying the request ID in and out (validated when the config was loaded)
+    let request_id_header = HeaderName::from_bytes(config.request_id_header.as_bytes())?;
+
     // Configure CORS with specific allowed origins
     let allowed_origins = config.allowed_origins.iter()
         .filter_map(|origin| origin.parse::<HeaderValue>().ok())
@@ -102,8 +319,20 @@ async fn main() -> anyhow::Result<()> {
         tracing::info!("CORS configured with {} allowed origins", allowed_origins.len());
         CorsLayer::new()
             .allow_origin(allowed_origins)
-            .allow_methods([Method::GET, Method::POST, Method::OPTIONS])
-            .allow_headers([AUTHORIZATION, CONTENT_TYPE])
+            .allow_methods([Method::GET, Method::POST, Method::PATCH, Method::OPTIONS])
+            .allow_headers([
+                AUTHORIZATION,
+                CONTENT_TYPE,
+                request_id_header.clone(),
+                HeaderName::from_static("x-db-timeout-ms"),
+                HeaderName::from_static("x-timestamp-format"),
+            ])
+            .expose_headers([
+                request_id_header.clone(),
+                HeaderName::from_static("x-content-sha256"),
+                HeaderName::from_static("x-content-signature"),
+                HeaderName::from_static("x-total-count"),
+            ])
             .allow_credentials(true)
             .max_age(Duration::from_secs(3600))
     };
@@ -112,7 +341,20 @@ async fn main() -> anyhow::Result<()> {
     let app = Router::new()
         .nest("/api/v1", protected_routes)
         .merge(public_routes)
-        .layer(axum::middleware::from_fn(feedback_api::middleware::request_logging_middleware))
+        .layer(axum::middleware::from_fn_with_state(
+            RequestLogging {
+                request_id_header,
+                log_error_bodies: config.log_error_request_bodies,
+                slow_request_threshold: (config.slow_request_ms > 0)
+                    .then(|| Duration::from_millis(config.slow_request_ms)),
+            },
+            feedback_api::middleware::request_logging_middleware,
+        ))
+        .layer(axum::middleware::from_fn_with_state(
+            config.json_pretty,
+            feedback_api::middleware::json_pretty_middleware,
+        ))
+        .layer(axum::middleware::from_fn(feedback_api::middleware::timestamp_format_middleware))
         .layer(axum::middleware::from_fn(feedback_api::middleware::metrics_middleware))
         .layer(RequestBodyLimitLayer::new(1024 * 1024)) // 1MB max request size
         .layer(cors)
@@ -1,7 +1,10 @@
+use chrono::{DateTime, Timelike, Utc};
+use chrono_tz::Tz;
 use lazy_static::lazy_static;
 use prometheus::{
-    register_counter_vec, register_histogram_vec, register_int_gauge_vec, CounterVec,
-    HistogramVec, IntGaugeVec, TextEncoder, Encoder,
+    register_counter_vec, register_histogram, register_histogram_vec, register_int_counter,
+    register_int_gauge, register_int_gauge_vec, CounterVec, GaugeVec, Histogram, HistogramVec,
+    proto::MetricFamily, IntCounter, IntGauge, IntGaugeVec, Opts, TextEncoder, Encoder,
 };
 
 lazy_static! {
@@ -36,11 +39,18 @@ lazy_static! {
 
     pub static ref FEEDBACK_COMMENTS: CounterVec = register_counter_vec!(
         "feedback_comments_total",
-        "Total number of comments",
+        "Total number of feedbacks carrying a comment, whatever their type",
         &["service"]
     )
     .unwrap();
 
+    pub static ref FEEDBACK_BY_HOUR: CounterVec = register_counter_vec!(
+        "feedback_by_hour_total",
+        "Total number of feedbacks by local hour of day (METRICS_TIMEZONE) of submission",
+        &["hour"]
+    )
+    .unwrap();
+
     pub static ref ACTIVE_USERS: IntGaugeVec = register_int_gauge_vec!(
         "feedback_active_users",
         "Number of active users providing feedback",
@@ -62,6 +72,28 @@ lazy_static! {
     )
     .unwrap();
 
+    pub static ref REQUEST_BODY_BYTES: HistogramVec = register_histogram_vec!(
+        "feedback_request_body_bytes",
+        "Declared size (Content-Length) of request bodies in bytes, by route template",
+        &["method", "endpoint"],
+        // 100 B, 1 KiB, 10 KiB, 64 KiB, 256 KiB, 512 KiB, 1 MiB (the body limit)
+        vec![100.0, 1024.0, 10240.0, 65536.0, 262144.0, 524288.0, 1048576.0]
+    )
+    .unwrap();
+
+    pub static ref REQUESTS_IN_FLIGHT: IntGauge = register_int_gauge!(
+        "feedback_requests_in_flight",
+        "Number of HTTP requests currently being processed"
+    )
+    .unwrap();
+
+    pub static ref REQUESTS_SHED: CounterVec = register_counter_vec!(
+        "feedback_requests_shed_total",
+        "Total number of requests rejected to shed load",
+        &["limiter"]
+    )
+    .unwrap();
+
     // Enhanced metrics for Phase 3 - Robustness & Observability
     pub static ref VALIDATION_ERRORS: CounterVec = register_counter_vec!(
         "feedback_validation_errors_total",
@@ -84,6 +116,36 @@ lazy_static! {
     )
     .unwrap();
 
+    pub static ref DB_POOL_ACQUIRE_TIMEOUTS: IntCounter = register_int_counter!(
+        "feedback_db_pool_acquire_timeouts_total",
+        "Requests answered 503 because no database connection was free in time"
+    )
+    .unwrap();
+
+    pub static ref SERVICE_LATENCY: HistogramVec = register_histogram_vec!(
+        "feedback_service_method_duration_seconds",
+        "Service layer method latency in seconds, excluding HTTP overhead",
+        &["method"]
+    )
+    .unwrap();
+
+    pub static ref FEEDBACK_CREATE_DURATION: Histogram = register_histogram!(
+        "feedback_create_duration_seconds",
+        "End-to-end time of successful feedback creations in the service layer, in seconds \
+         (validation, insert, metrics; webhook delivery runs detached and is excluded)",
+        vec![0.005, 0.01, 0.025, 0.05, 0.1, 0.25, 0.5, 1.0, 2.5, 5.0]
+    )
+    .unwrap();
+
+    pub static ref FEEDBACK_READ_AGE: HistogramVec = register_histogram_vec!(
+        "feedback_read_age_seconds",
+        "Age of feedbacks returned by read endpoints (now - created_at) in seconds",
+        &["endpoint"],
+        // 1 minute, 1 hour, 1 day, 1 week, 30 days, 90 days, 1 year
+        vec![60.0, 3600.0, 86400.0, 604800.0, 2592000.0, 7776000.0, 31536000.0]
+    )
+    .unwrap();
+
     pub static ref WEBHOOK_DELIVERIES: CounterVec = register_counter_vec!(
         "feedback_webhook_deliveries_total",
         "Total number of webhook deliveries",
@@ -91,19 +153,59 @@ lazy_static! {
     )
     .unwrap();
 
+    pub static ref WEBHOOK_DELIVERY_LAG: Histogram = register_histogram!(
+        "feedback_webhook_delivery_lag_seconds",
+        "Time from queueing a webhook to its successful delivery in seconds",
+        vec![0.1, 0.5, 1.0, 5.0, 30.0, 60.0, 300.0, 3600.0]
+    )
+    .unwrap();
+
     pub static ref AUTH_ATTEMPTS: CounterVec = register_counter_vec!(
         "feedback_auth_attempts_total",
         "Total number of authentication attempts",
         &["status"]
     )
     .unwrap();
+
+    pub static ref AUTH_REJECTIONS: CounterVec = register_counter_vec!(
+        "feedback_auth_rejections_total",
+        "Total number of requests rejected by the auth middleware",
+        &["reason"]
+    )
+    .unwrap();
+
+    pub static ref STATS_LAST_REFRESH: IntGauge = register_int_gauge!(
+        "feedback_stats_last_refresh_timestamp_seconds",
+        "Unix time of the last successful stats view refresh by this instance"
+    )
+    .unwrap();
+
+    pub static ref JWKS_NEW_KEYS: CounterVec = register_counter_vec!(
+        "feedback_jwks_new_keys_total",
+        "Signing keys seen for the first time on a JWKS refresh (key rotation)",
+        &["issuer"]
+    )
+    .unwrap();
 }
 
-pub fn record_feedback(service: &str, feedback_type: &str, rating: Option<i32>, thumbs_up: Option<bool>, has_comment: bool) {
+pub fn record_feedback(
+    service: &str,
+    feedback_type: &str,
+    rating: Option<i32>,
+    thumbs_up: Option<bool>,
+    has_comment: bool,
+    created_at: DateTime<Utc>,
+    timezone: Tz,
+) {
     FEEDBACK_COUNTER
         .with_label_values(&[service, feedback_type])
         .inc();
 
+    let hour = created_at.with_timezone(&timezone).hour();
+    FEEDBACK_BY_HOUR
+        .with_label_values(&[&hour.to_string()])
+        .inc();
+
     if let Some(rating) = rating {
         FEEDBACK_RATING
             .with_label_values(&[service])
@@ -129,18 +231,173 @@ pub fn record_feedback(service: &str, feedback_type: &str, rating: Option<i32>,
     }
 }
 
-pub fn gather_metrics() -> Result<String, Box<dyn std::error::Error>> {
-    let encoder = TextEncoder::new();
-    let metric_families = prometheus::gather();
+/// Observe how old the feedbacks returned by a read endpoint are
+pub fn record_read_age(endpoint: &str, feedbacks: &[crate::models::Feedback]) {
+    let now = chrono::Utc::now();
+    let histogram = FEEDBACK_READ_AGE.with_label_values(&[endpoint]);
+    for feedback in feedbacks {
+        let age = (now - feedback.created_at).num_milliseconds().max(0) as f64 / 1000.0;
+        histogram.observe(age);
+    }
+}
+
+/// Tracks one in-flight request: increments the gauge on creation and decrements it on
+/// drop, so the gauge stays accurate even if the request future panics or is cancelled
+pub struct InFlightGuard;
+
+impl InFlightGuard {
+    pub fn new() -> Self {
+        REQUESTS_IN_FLIGHT.inc();
+        Self
+    }
+}
+
+impl Default for InFlightGuard {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl Drop for InFlightGuard {
+    fn drop(&mut self) {
+        REQUESTS_IN_FLIGHT.dec();
+    }
+}
+
+/// Prefix put before every exposed metric name (METRICS_NAMESPACE), so instances serving
+/// different tenants can be scraped by one Prometheus; set once at startup
+static NAMESPACE: std::sync::OnceLock<String> = std::sync::OnceLock::new();
+
+/// Expose every metric as `<namespace>_<name>` from now on, e.g. `tenantx_feedback_total`.
+/// Only the first call has any effect.
+pub fn set_namespace(namespace: &str) {
+    if NAMESPACE.set(namespace.to_string()).is_err() {
+        tracing::warn!(namespace = %namespace, "Metrics namespace already set, ignoring");
+    }
+}
+
+/// Prepend `namespace` to the name of every family
+fn apply_namespace(families: &mut [MetricFamily], namespace: Option<&str>) {
+    let Some(namespace) = namespace else {
+        return;
+    };
+    for family in families {
+        let name = format!("{}_{}", namespace, family.get_name());
+        family.set_name(name);
+    }
+}
+
+/// Encode families in the Prometheus text format, under the configured namespace
+fn encode(mut families: Vec<MetricFamily>) -> Result<String, Box<dyn std::error::Error>> {
+    apply_namespace(&mut families, NAMESPACE.get().map(String::as_str));
     let mut buffer = Vec::new();
-    encoder.encode(&metric_families, &mut buffer)?;
+    TextEncoder::new().encode(&families, &mut buffer)?;
     Ok(String::from_utf8(buffer)?)
 }
 
+pub fn gather_metrics() -> Result<String, Box<dyn std::error::Error>> {
+    encode(prometheus::gather())
+}
+
+/// Render per-service stats in the Prometheus text format, under stable
+/// `feedback_stats_*` names kept apart from the internal `/metrics` registry. Averages and
+/// ratios are left out for services without any ratings or thumbs.
+pub fn render_stats(
+    stats: &[crate::models::FeedbackStats],
+) -> Result<String, Box<dyn std::error::Error>> {
+    let registry = prometheus::Registry::new();
+    let gauge = |name: &str, help: &str| -> Result<GaugeVec, prometheus::Error> {
+        let gauge = GaugeVec::new(Opts::new(name, help), &["service"])?;
+        registry.register(Box::new(gauge.clone()))?;
+        Ok(gauge)
+    };
+
+    let total = gauge("feedback_stats_total_count", "Feedbacks received by the service")?;
+    let rating = gauge("feedback_stats_rating_average", "Average rating of the service")?;
+    let ratio = gauge(
+        "feedback_stats_thumbs_up_ratio",
+        "Share of the service's thumbs feedback that is thumbs-up (0-1)",
+    )?;
+    let thumbs_up = gauge("feedback_stats_thumbs_up_count", "Thumbs-up feedbacks")?;
+    let thumbs_down = gauge("feedback_stats_thumbs_down_count", "Thumbs-down feedbacks")?;
+    let comments = gauge("feedback_stats_comment_count", "Feedbacks with a comment")?;
+
+    for service_stats in stats {
+        let labels = [service_stats.service.as_str()];
+        total.with_label_values(&labels).set(service_stats.total_count as f64);
+        thumbs_up.with_label_values(&labels).set(service_stats.thumbs_up_count as f64);
+        thumbs_down.with_label_values(&labels).set(service_stats.thumbs_down_count as f64);
+        comments.with_label_values(&labels).set(service_stats.comment_count as f64);
+        if let Some(rating_avg) = service_stats.rating_avg {
+            rating.with_label_values(&labels).set(rating_avg);
+        }
+        if let Some(thumbs_up_ratio) = service_stats.thumbs_up_ratio {
+            ratio.with_label_values(&labels).set(thumbs_up_ratio);
+        }
+    }
+
+    encode(registry.gather())
+}
+
 pub async fn initialize_metrics_from_db(repository: &dyn crate::repositories::FeedbackRepository) -> anyhow::Result<()> {
     // Fetch aggregated metrics from database via repository
     let aggregates = repository.get_metrics_aggregates().await?;
+    apply_aggregates(aggregates);
 
+    Ok(())
+}
+
+/// Longest wait between two attempts of `initialize_metrics_with_retry`
+const METRICS_INIT_MAX_BACKOFF: std::time::Duration = std::time::Duration::from_secs(30);
+
+/// `initialize_metrics_from_db`, retried up to `retries` more times on failure, waiting
+/// 1s, 2s, 4s... (at most 30s) between attempts
+pub async fn initialize_metrics_with_retry(
+    repository: &dyn crate::repositories::FeedbackRepository,
+    retries: u32,
+) -> anyhow::Result<()> {
+    let mut delay = std::time::Duration::from_secs(1);
+    let mut attempt = 0;
+    loop {
+        match initialize_metrics_from_db(repository).await {
+            Ok(()) => return Ok(()),
+            Err(e) if attempt < retries => {
+                attempt += 1;
+                tracing::warn!(
+                    error = %e,
+                    attempt = attempt,
+                    retries = retries,
+                    delay_secs = delay.as_secs(),
+                    "Failed to initialize metrics from database, retrying"
+                );
+                tokio::time::sleep(delay).await;
+                delay = (delay * 2).min(METRICS_INIT_MAX_BACKOFF);
+            }
+            Err(e) => return Err(e),
+        }
+    }
+}
+
+/// Reset the feedback counters restored at startup and re-seed them from the database,
+/// returning the number of feedbacks they now account for. Aggregates are read before the
+/// reset so the counters are never left empty on a database error; feedback recorded
+/// between the read and the reset is not counted.
+pub async fn reinitialize_metrics_from_db(
+    repository: &dyn crate::repositories::FeedbackRepository,
+) -> anyhow::Result<i64> {
+    let aggregates = repository.get_metrics_aggregates().await?;
+
+    FEEDBACK_COUNTER.reset();
+    FEEDBACK_RATING.reset();
+    FEEDBACK_THUMBS_UP.reset();
+    FEEDBACK_THUMBS_DOWN.reset();
+    FEEDBACK_COMMENTS.reset();
+
+    Ok(apply_aggregates(aggregates))
+}
+
+/// Add database aggregates to the feedback counters, returning the total feedback count
+fn apply_aggregates(aggregates: Vec<crate::models::MetricsAggregate>) -> i64 {
     let aggregate_count = aggregates.len();
     let mut total_feedbacks = 0i64;
 
@@ -194,5 +451,47 @@ pub async fn initialize_metrics_from_db(repository: &dyn crate::repositories::Fe
     tracing::info!("Metrics initialized from database aggregates ({} total feedbacks across {} service/type combinations)",
         total_feedbacks, aggregate_count);
 
-    Ok(())
+    total_feedbacks
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_namespace_prefixes_every_family() {
+        let registry = prometheus::Registry::new();
+        let counter = IntCounter::new("feedback_total", "Feedbacks").unwrap();
+        registry.register(Box::new(counter)).unwrap();
+
+        let mut families = registry.gather();
+        apply_namespace(&mut families, None);
+        assert_eq!(families[0].get_name(), "feedback_total");
+
+        apply_namespace(&mut families, Some("tenantx"));
+        assert_eq!(families[0].get_name(), "tenantx_feedback_total");
+    }
+
+    #[test]
+    fn test_comment_counted_for_any_feedback_type() {
+        // Counters are process-wide, so the service label is unique to this test
+        let service = "metrics-test-rating-with-comment";
+        let now = Utc::now();
+
+        record_feedback(service, "Rating", Some(4), None, true, now, chrono_tz::UTC);
+        record_feedback(service, "Thumbs", None, Some(false), true, now, chrono_tz::UTC);
+        record_feedback(service, "Rating", Some(5), None, false, now, chrono_tz::UTC);
+
+        let comments = FEEDBACK_COMMENTS.with_label_values(&[service]).get();
+        let total: f64 = ["Rating", "Thumbs"]
+            .iter()
+            .map(|feedback_type| {
+                FEEDBACK_COUNTER
+                    .with_label_values(&[service, feedback_type])
+                    .get()
+            })
+            .sum();
+        assert_eq!(comments, 2.0);
+        assert_eq!(total, 3.0);
+    }
 }
@@ -1,6 +1,6 @@
 use axum::{
-    extract::{ConnectInfo, Request},
-    http::{HeaderValue, StatusCode},
+    extract::{ConnectInfo, MatchedPath, Request, State},
+    http::{header, HeaderName, HeaderValue, Method, StatusCode},
     middleware::Next,
     response::{IntoResponse, Response},
 };
@@ -13,9 +13,28 @@ use std::time::{Duration, Instant};
 use crate::observability::RequestId;
 
 pub async fn metrics_middleware(req: Request, next: Next) -> Response {
+    let _in_flight = crate::metrics::InFlightGuard::new();
     let start = Instant::now();
     let method = req.method().to_string();
     let uri = req.uri().path().to_string();
+    // The route template (`/api/v1/feedbacks/:id`), so ids do not each get a series
+    let endpoint = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map_or("unmatched", MatchedPath::as_str)
+        .to_string();
+
+    // Bodies sent without a Content-Length (chunked) are not measured
+    let body_bytes = req
+        .headers()
+        .get(axum::http::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<u64>().ok());
+    if let Some(body_bytes) = body_bytes {
+        crate::metrics::REQUEST_BODY_BYTES
+            .with_label_values(&[&method, &endpoint])
+            .observe(body_bytes as f64);
+    }
 
     let response = next.run(req).await;
 
@@ -34,6 +53,168 @@ pub async fn metrics_middleware(req: Request, next: Next) -> Response {
     response
 }
 
+/// How long, and by whom, a successful read may be cached
+#[derive(Clone, Copy)]
+pub struct CachePolicy {
+    pub max_age_secs: u64,
+    /// `public` (shared caches may store it) for responses that are the same for every
+    /// caller; `private` (browser only) otherwise
+    pub shared: bool,
+}
+
+/// Add `Cache-Control` and `Vary` to successful GET/HEAD responses, unless the handler
+/// already chose a `Cache-Control`. `Vary` keeps caches keyed on the caller's credentials
+/// and requested formats.
+pub async fn cache_control_middleware(
+    State(policy): State<CachePolicy>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let cacheable = matches!(*req.method(), Method::GET | Method::HEAD);
+    let mut response = next.run(req).await;
+
+    if cacheable
+        && response.status().is_success()
+        && !response.headers().contains_key(header::CACHE_CONTROL)
+    {
+        let visibility = if policy.shared { "public" } else { "private" };
+        let value = format!("{}, max-age={}", visibility, policy.max_age_secs);
+        if let Ok(value) = HeaderValue::from_str(&value) {
+            let headers = response.headers_mut();
+            headers.insert(header::CACHE_CONTROL, value);
+            headers.insert(
+                header::VARY,
+                HeaderValue::from_static("Accept, Authorization, X-Timestamp-Format"),
+            );
+        }
+    }
+
+    response
+}
+
+/// Serialize response timestamps as epoch milliseconds when the request sends
+/// `X-Timestamp-Format: epoch_millis`; RFC 3339 otherwise
+pub async fn timestamp_format_middleware(req: Request, next: Next) -> Response {
+    use crate::models::timestamps::{self, EPOCH_MILLIS, TIMESTAMP_FORMAT_HEADER};
+
+    let epoch_millis = req
+        .headers()
+        .get(TIMESTAMP_FORMAT_HEADER)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.trim().eq_ignore_ascii_case(EPOCH_MILLIS));
+
+    timestamps::scope(epoch_millis, next.run(req)).await
+}
+
+/// Pretty-print JSON responses when `JSON_PRETTY` is set (the state) or the request asks
+/// with `?pretty=true`; `?pretty=false` forces compact output. Responses carrying a
+/// content digest (exports) are left byte-for-byte as hashed and signed.
+pub async fn json_pretty_middleware(
+    State(default_pretty): State<bool>,
+    req: Request,
+    next: Next,
+) -> Response {
+    let pretty = pretty_requested(req.uri().query(), default_pretty);
+    let response = next.run(req).await;
+
+    let is_json = response
+        .headers()
+        .get(header::CONTENT_TYPE)
+        .and_then(|v| v.to_str().ok())
+        .is_some_and(|v| v.starts_with("application/json"));
+    if !pretty || !is_json || response.headers().contains_key("x-content-sha256") {
+        return response;
+    }
+
+    // JSON responses are already serialized in memory, so buffering adds no new bound
+    let (mut parts, body) = response.into_parts();
+    match axum::body::to_bytes(body, usize::MAX).await {
+        Ok(bytes) => {
+            parts.headers.remove(header::CONTENT_LENGTH);
+            Response::from_parts(parts, axum::body::Body::from(indent_json(&bytes)))
+        }
+        Err(_) => StatusCode::INTERNAL_SERVER_ERROR.into_response(),
+    }
+}
+
+/// Whether the query string's `pretty` parameter asks for pretty output, else `default`
+pub(crate) fn pretty_requested(query: Option<&str>, default: bool) -> bool {
+    query
+        .into_iter()
+        .flat_map(|query| query.split('&'))
+        .find_map(|pair| match pair.split_once('=') {
+            Some(("pretty", value)) => Some(value),
+            None if pair == "pretty" => Some(""),
+            _ => None,
+        })
+        .map(|value| !matches!(value, "false" | "0"))
+        .unwrap_or(default)
+}
+
+/// Re-indent compact JSON with two spaces per level, keeping the original key order
+/// (re-serializing through `serde_json::Value` would sort object keys)
+fn indent_json(compact: &[u8]) -> Vec<u8> {
+    fn newline(out: &mut Vec<u8>, depth: usize) {
+        out.push(b'\n');
+        out.extend(std::iter::repeat_n(b' ', depth * 2));
+    }
+
+    let mut out = Vec::with_capacity(compact.len() * 2);
+    let mut depth = 0usize;
+    let mut in_string = false;
+    let mut escaped = false;
+    let mut bytes = compact.iter().copied().peekable();
+
+    while let Some(byte) = bytes.next() {
+        if in_string {
+            out.push(byte);
+            if escaped {
+                escaped = false;
+            } else if byte == b'\\' {
+                escaped = true;
+            } else if byte == b'"' {
+                in_string = false;
+            }
+            continue;
+        }
+
+        match byte {
+            b'"' => {
+                in_string = true;
+                out.push(byte);
+            }
+            b'{' | b'[' => {
+                out.push(byte);
+                match bytes.peek() {
+                    // Empty objects and arrays stay on one line
+                    Some(&close @ (b'}' | b']')) => {
+                        out.push(close);
+                        bytes.next();
+                    }
+                    _ => {
+                        depth += 1;
+                        newline(&mut out, depth);
+                    }
+                }
+            }
+            b'}' | b']' => {
+                depth = depth.saturating_sub(1);
+                newline(&mut out, depth);
+                out.push(byte);
+            }
+            b',' => {
+                out.push(byte);
+                newline(&mut out, depth);
+            }
+            b':' => out.extend_from_slice(b": "),
+            b' ' | b'\n' | b'\r' | b'\t' => {}
+            _ => out.push(byte),
+        }
+    }
+
+    out
+}
+
 // Rate limiter state: IP -> (request_count, window_start)
 lazy_static! {
     static ref RATE_LIMIT_MAP: Arc<DashMap<String, (u32, Instant)>> =
@@ -60,6 +241,9 @@ pub async fn rate_limit_middleware(
 
     // Check if limit exceeded (100 requests per second)
     if *count >= 100 {
+        crate::metrics::REQUESTS_SHED
+            .with_label_values(&["rate_limit"])
+            .inc();
         return Err((
             StatusCode::TOO_MANY_REQUESTS,
             "Rate limit exceeded. Please try again later.",
@@ -93,6 +277,9 @@ pub async fn auth_rate_limit_middleware(
     // Check if limit exceeded (5 requests per minute)
     if *count >= 5 {
         tracing::warn!("Rate limit exceeded for auth endpoint from IP: {}", addr.ip());
+        crate::metrics::REQUESTS_SHED
+            .with_label_values(&["auth_rate_limit"])
+            .inc();
         return Err((
             StatusCode::TOO_MANY_REQUESTS,
             "Too many login attempts. Please try again later.",
@@ -105,21 +292,51 @@ pub async fn auth_rate_limit_middleware(
     Ok(next.run(req).await)
 }
 
+/// Largest request body buffered for error logging; bigger bodies are never captured
+const MAX_CAPTURED_BODY_BYTES: usize = 64 * 1024;
+
+/// Characters of a captured request body written to the log
+const LOGGED_BODY_CHARS: usize = 512;
+
+/// JSON fields whose values are replaced before a request body is logged
+const REDACTED_BODY_FIELDS: &[&str] = &["comment", "user_email", "email", "password"];
+
+/// Settings for `request_logging_middleware`
+#[derive(Clone)]
+pub struct RequestLogging {
+    /// Header the request ID is read from and echoed in
+    pub request_id_header: HeaderName,
+    /// Log a redacted, truncated copy of the request body for 4xx/5xx responses
+    pub log_error_bodies: bool,
+    /// Requests taking longer than this are additionally logged at warn, whatever the status
+    pub slow_request_threshold: Option<Duration>,
+}
+
 /// Request logging middleware with correlation IDs
 ///
 /// This middleware:
-/// - Generates a unique request ID for each request
-/// - Adds the request ID to response headers (X-Request-ID)
+/// - Reuses the caller's request ID from the configured header (`REQUEST_ID_HEADER`),
+///   or generates a unique one
+/// - Echoes the request ID in the same response header
 /// - Logs structured request/response information
 /// - Tracks request duration
 /// - Includes client IP and user agent
+/// - With `LOG_ERROR_REQUEST_BODIES`, logs a redacted excerpt of the body of failed requests
 pub async fn request_logging_middleware(
+    State(logging): State<RequestLogging>,
     ConnectInfo(addr): ConnectInfo<SocketAddr>,
     req: Request,
     next: Next,
 ) -> Response {
+    let request_id_header = logging.request_id_header;
     let start = Instant::now();
-    let request_id = RequestId::new();
+    let request_id = req
+        .headers()
+        .get(&request_id_header)
+        .and_then(|v| v.to_str().ok())
+        .filter(|id| is_propagatable_request_id(id))
+        .map(str::to_string)
+        .unwrap_or_else(|| RequestId::new().to_string());
 
     // Extract request details
     let method = req.method().clone();
@@ -142,6 +359,13 @@ pub async fn request_logging_middleware(
         "Incoming request"
     );
 
+    // Keep a copy of small bodies in case the request fails
+    let (req, captured_body) = if logging.log_error_bodies {
+        capture_body(req).await
+    } else {
+        (req, None)
+    };
+
     // Process request
     let mut response = next.run(req).await;
 
@@ -150,8 +374,8 @@ pub async fn request_logging_middleware(
     let status = response.status();
 
     // Add request ID to response headers
-    if let Ok(header_value) = HeaderValue::from_str(&request_id.to_string()) {
-        response.headers_mut().insert("X-Request-ID", header_value);
+    if let Ok(header_value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(request_id_header, header_value);
     }
 
     // Log response with structured fields based on status
@@ -184,5 +408,147 @@ pub async fn request_logging_middleware(
         );
     }
 
+    if logging.slow_request_threshold.is_some_and(|threshold| duration > threshold) {
+        tracing::warn!(
+            request_id = %request_id,
+            method = %method,
+            path = %path,
+            status = %status.as_u16(),
+            duration_ms = duration.as_millis(),
+            slow_request = true,
+            "Slow request"
+        );
+    }
+
+    if status.is_client_error() || status.is_server_error() {
+        if let Some(body) = captured_body {
+            tracing::info!(
+                request_id = %request_id,
+                status = %status.as_u16(),
+                body = %body_excerpt(&body),
+                "Failed request body"
+            );
+        }
+    }
+
     response
 }
+
+/// Buffer the request body when its declared length is small enough, returning the
+/// request rebuilt with the same bytes. Bodies without a length, or over the cap, are
+/// passed through untouched.
+async fn capture_body(req: Request) -> (Request, Option<axum::body::Bytes>) {
+    let declared_len = req
+        .headers()
+        .get(axum::http::header::CONTENT_LENGTH)
+        .and_then(|v| v.to_str().ok())
+        .and_then(|v| v.parse::<usize>().ok());
+    if !declared_len.is_some_and(|len| len > 0 && len <= MAX_CAPTURED_BODY_BYTES) {
+        return (req, None);
+    }
+
+    let (parts, body) = req.into_parts();
+    match axum::body::to_bytes(body, MAX_CAPTURED_BODY_BYTES).await {
+        Ok(bytes) => (
+            Request::from_parts(parts, axum::body::Body::from(bytes.clone())),
+            Some(bytes),
+        ),
+        // The body could not be read; let the handler see an empty one and fail
+        Err(_) => (Request::from_parts(parts, axum::body::Body::empty()), None),
+    }
+}
+
+/// A loggable excerpt of a request body: JSON has its free-text and identity fields
+/// redacted, anything else is only described by its size
+fn body_excerpt(body: &[u8]) -> String {
+    match serde_json::from_slice::<serde_json::Value>(body) {
+        Ok(mut json) => {
+            redact_body_fields(&mut json);
+            crate::validation::sanitize_for_log(&json.to_string(), LOGGED_BODY_CHARS)
+        }
+        Err(_) => format!("<{} bytes, not JSON>", body.len()),
+    }
+}
+
+fn redact_body_fields(value: &mut serde_json::Value) {
+    match value {
+        serde_json::Value::Object(map) => {
+            for (key, field) in map.iter_mut() {
+                if REDACTED_BODY_FIELDS.contains(&key.as_str()) && !field.is_null() {
+                    *field = serde_json::Value::String("[redacted]".to_string());
+                } else {
+                    redact_body_fields(field);
+                }
+            }
+        }
+        serde_json::Value::Array(items) => items.iter_mut().for_each(redact_body_fields),
+        _ => {}
+    }
+}
+
+/// Inbound request IDs are only trusted when short and made of log-safe characters
+fn is_propagatable_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= 128
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.' | ':'))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_body_excerpt_redacts_free_text() {
+        let body = br#"[{"service": "visio", "comment": "call me at 555-0100", "rating": 4}]"#;
+
+        let excerpt = body_excerpt(body);
+
+        assert!(excerpt.contains("visio") && excerpt.contains("[redacted]"));
+        assert!(!excerpt.contains("555-0100"));
+        assert_eq!(body_excerpt(b"not json"), "<8 bytes, not JSON>");
+    }
+
+    #[test]
+    fn test_indent_json_matches_serde_pretty() {
+        let compact = br#"{"a":[1,{},[]],"b":"x, {y}: \"z\" \\","c":{"d":null,"e":true}}"#;
+        let value: serde_json::Value = serde_json::from_slice(compact).unwrap();
+
+        let pretty = String::from_utf8(indent_json(compact)).unwrap();
+
+        assert_eq!(pretty, serde_json::to_string_pretty(&value).unwrap());
+        // Key order is kept, not sorted
+        assert_eq!(indent_json(br#"{"z":1,"a":2}"#), b"{\n  \"z\": 1,\n  \"a\": 2\n}");
+    }
+
+    #[tokio::test]
+    async fn test_body_size_labelled_by_route() {
+        use axum::{body::Body, routing::post, Router};
+        use tower::ServiceExt;
+
+        let app = Router::new()
+            .route("/things/:id", post(|| async {}))
+            .layer(axum::middleware::from_fn(metrics_middleware));
+        let request = Request::post(format!("/things/{}", uuid::Uuid::new_v4()))
+            .header(header::CONTENT_LENGTH, "2")
+            .body(Body::from("{}"))
+            .unwrap();
+
+        app.oneshot(request).await.unwrap();
+
+        let observed = crate::metrics::REQUEST_BODY_BYTES
+            .with_label_values(&["POST", "/things/:id"])
+            .get_sample_count();
+        assert_eq!(observed, 1);
+    }
+
+    #[test]
+    fn test_pretty_requested() {
+        assert!(pretty_requested(Some("service=x&pretty=true"), false));
+        assert!(pretty_requested(Some("pretty"), false));
+        assert!(!pretty_requested(Some("pretty=false"), true));
+        assert!(pretty_requested(None, true));
+        assert!(!pretty_requested(Some("service=pretty"), false));
+    }
+}
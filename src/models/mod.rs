@@ -1,8 +1,10 @@
-use chrono::{DateTime, Utc};
+use chrono::{DateTime, NaiveDate, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::types::JsonValue;
 use uuid::Uuid;
 
+pub mod timestamps;
+
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::Type)]
 #[sqlx(type_name = "feedback_type", rename_all = "lowercase")]
 #[serde(rename_all = "lowercase")]
@@ -11,16 +13,56 @@ pub enum FeedbackType {
     Thumbs,    // up/down
     Comment,   // text comment
     Nps,       // Net Promoter Score 0-10
+    Custom,    // Deployment-defined, named by `custom_type` (CUSTOM_FEEDBACK_TYPES)
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct FeedbackSubmission {
+    // A missing service is read as empty: DEFAULT_SERVICE applies, or validation rejects it
+    #[serde(default)]
     pub service: String,           // e.g., "visio", "chatbot", "console"
     pub feedback_type: FeedbackType,
     pub rating: Option<i32>,       // For rating (1-5) or NPS (0-10)
     pub thumbs_up: Option<bool>,   // For thumbs feedback
     pub comment: Option<String>,   // Optional comment
     pub context: Option<JsonValue>, // Flexible context (call_id, message_id, etc.)
+    pub parent_id: Option<Uuid>,   // Feedback this one follows up on (same user)
+    pub language: Option<String>,  // BCP-47 tag, e.g. "fr" or "en-GB"
+    pub reason: Option<String>,    // Why a thumbs-down was given, from THUMBS_DOWN_REASONS
+    pub custom_type: Option<String>, // Name of the custom type, for `custom` feedback only
+    // User to attribute the feedback to; needs the IMPERSONATION_SCOPE scope or role
+    #[serde(default)]
+    pub on_behalf_of: Option<String>,
+    // Resolved server-side from the source IP, never taken from the client
+    #[serde(skip)]
+    pub country: Option<String>,
+}
+
+/// A submission posted as an HTML form (`application/x-www-form-urlencoded`): every field
+/// is text, blank fields count as absent and `context` is a JSON-encoded string
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FeedbackSubmissionForm {
+    pub service: Option<String>,
+    pub feedback_type: Option<String>,
+    pub rating: Option<String>,
+    pub thumbs_up: Option<String>,
+    pub comment: Option<String>,
+    pub context: Option<String>,
+    pub parent_id: Option<String>,
+    pub language: Option<String>,
+    pub reason: Option<String>,
+    pub custom_type: Option<String>,
+    pub on_behalf_of: Option<String>,
+}
+
+/// Partial update of an existing feedback; omitted fields are left unchanged
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct FeedbackUpdate {
+    pub rating: Option<i32>,
+    pub thumbs_up: Option<bool>,
+    pub comment: Option<String>,
+    pub context: Option<JsonValue>,
+    pub reason: Option<String>,    // Dropped when the feedback is no longer a thumbs-down
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -34,8 +76,21 @@ pub struct Feedback {
     pub thumbs_up: Option<bool>,
     pub comment: Option<String>,
     pub context: Option<JsonValue>,
+    pub parent_id: Option<Uuid>,
+    pub language: Option<String>,
+    pub reason: Option<String>,
+    pub country: Option<String>,
+    pub custom_type: Option<String>,
     pub created_at: DateTime<Utc>,
     pub updated_at: DateTime<Utc>,
+    /// Matched fragments of the comment, only when querying with `search`
+    #[sqlx(default)]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub highlight: Option<String>,
+    /// Rows matching the query across all pages, only when querying with `count`
+    #[sqlx(default)]
+    #[serde(skip)]
+    pub total_count: Option<i64>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -47,7 +102,115 @@ pub struct FeedbackResponse {
     pub thumbs_up: Option<bool>,
     pub comment: Option<String>,
     pub context: Option<JsonValue>,
+    pub parent_id: Option<Uuid>,
+    pub language: Option<String>,
+    pub reason: Option<String>,
+    // Shown to every reader, like the country filter and stats: a country alone does not
+    // identify the submitter
+    pub country: Option<String>,
+    pub custom_type: Option<String>,
+    #[serde(serialize_with = "timestamps::serialize")]
     pub created_at: DateTime<Utc>,
+    #[serde(serialize_with = "timestamps::serialize")]
+    pub updated_at: DateTime<Utc>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub highlight: Option<String>,
+}
+
+/// Feedback as shown to admins: the regular response plus who submitted it
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct AdminFeedbackResponse {
+    #[serde(flatten)]
+    pub feedback: FeedbackResponse,
+    pub user_id: String,
+    pub user_email: Option<String>,
+}
+
+/// Role-aware feedback representation: submitter identity (user id and email) is only
+/// included for admins
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum FeedbackView {
+    Admin(AdminFeedbackResponse),
+    Public(FeedbackResponse),
+}
+
+impl FeedbackView {
+    pub fn for_caller(feedback: Feedback, is_admin: bool) -> Self {
+        if is_admin {
+            FeedbackView::Admin(feedback.into())
+        } else {
+            FeedbackView::Public(feedback.into())
+        }
+    }
+}
+
+/// Outcome of one entry of a bulk create, reported at the entry's input index
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(untagged)]
+pub enum BulkCreateResult {
+    Created {
+        index: usize,
+        id: Uuid,
+        // Set when, in message_id upsert mode, the entry replaced an existing feedback
+        #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+        replaced: bool,
+    },
+    Failed { index: usize, error: String },
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BulkCreateResponse {
+    pub created: usize,
+    pub failed: usize,
+    pub results: Vec<BulkCreateResult>, // Same order as the input array
+}
+
+/// One line of an NDJSON import of historical feedback
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportRecord {
+    pub user_id: String,
+    pub user_email: Option<String>,
+    pub created_at: DateTime<Utc>,  // Original submission time
+    #[serde(flatten)]
+    pub submission: FeedbackSubmission,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportRejection {
+    pub line: usize,               // 1-based line number in the uploaded file
+    pub error: String,
+}
+
+/// Outcome of a dry-run validation; `errors` is empty when the payload would be accepted
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ValidationReport {
+    pub valid: bool,
+    pub errors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ImportResponse {
+    pub imported: usize,
+    pub rejected: Vec<ImportRejection>, // Sorted by line
+    pub batches: usize, // Transactions started to write the valid rows
+}
+
+/// What a client must send for one feedback type, mirroring server-side validation
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct FeedbackTypeSchema {
+    pub feedback_type: FeedbackType,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub custom_type: Option<String>,       // Only for `custom` types
+    pub required_fields: Vec<String>,
+    pub rating_range: Option<RatingRange>, // Only for types that carry a rating
+    pub max_comment_length: usize,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RatingRange {
+    pub min: i32,
+    pub max: i32,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -55,10 +218,38 @@ pub struct FeedbackQuery {
     pub service: Option<String>,
     pub feedback_type: Option<FeedbackType>,
     pub user_id: Option<String>,
+    pub language: Option<String>,
+    pub reason: Option<String>,
+    pub country: Option<String>,
+    pub custom_type: Option<String>,
+    pub search: Option<String>,    // Full-text search over comments
+    pub context_key: Option<String>,    // With context_value: context->>key = value
+    pub context_value: Option<String>,
     pub from_date: Option<DateTime<Utc>>,
     pub to_date: Option<DateTime<Utc>>,
+    pub updated_from: Option<DateTime<Utc>>,
+    pub updated_to: Option<DateTime<Utc>>,
+    pub order: Option<QueryOrder>, // Default: newest first
     pub limit: Option<i64>,
     pub offset: Option<i64>,
+    #[serde(default)]
+    pub count: bool,               // Also count all matching rows (X-Total-Count)
+    #[serde(default)]
+    pub sample: bool,              // Random rows matching the filters instead of the newest
+    /// Text search configuration for `search`, set by the service from
+    /// `COMMENT_TEXT_SEARCH_CONFIG`
+    #[serde(skip)]
+    pub text_search_config: Option<String>,
+}
+
+/// Order of query results
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum QueryOrder {
+    /// Newest `created_at` first
+    Newest,
+    /// Oldest `updated_at` first (ties by id), for polling changes since a point in time
+    Updated,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
@@ -66,10 +257,300 @@ pub struct FeedbackStats {
     pub service: String,
     pub total_count: i64,
     pub rating_avg: Option<f64>,
+    pub rating_count: i64,
     pub thumbs_up_count: i64,
     pub thumbs_down_count: i64,
     pub thumbs_up_ratio: Option<f64>,
     pub comment_count: i64,
+    /// Ratings and thumbs averaged together, thumbs mapped to `THUMBS_UP_SCORE` and
+    /// `THUMBS_DOWN_SCORE`; filled in by the service, null when there is neither
+    #[sqlx(default)]
+    pub satisfaction_score: Option<f64>,
+    /// Shape of this entry, see `STATS_VERSION`
+    #[sqlx(skip)]
+    #[serde(default)]
+    pub stats_version: StatsVersion,
+}
+
+/// Version of the `FeedbackStats` shape, bumped whenever its fields or their meaning
+/// change, so clients can feature-detect (`/feedbacks/stats/schema` describes the current
+/// fields). 2: `rating_avg` and `rating_count` leave out NPS scores.
+pub const STATS_VERSION: u32 = 2;
+
+/// `stats_version` of a stats entry; built by the server, it is always `STATS_VERSION`
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(transparent)]
+pub struct StatsVersion(pub u32);
+
+impl Default for StatsVersion {
+    fn default() -> Self {
+        Self(STATS_VERSION)
+    }
+}
+
+/// One field of a `FeedbackStats` entry
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsField {
+    pub name: String,
+    #[serde(rename = "type")]
+    pub field_type: String,        // JSON type: string, integer or number
+    pub nullable: bool,
+    pub description: String,
+}
+
+/// Fields of the stats response at the current `stats_version`
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsSchema {
+    pub stats_version: u32,
+    pub fields: Vec<StatsField>,
+}
+
+impl StatsSchema {
+    /// Describe `FeedbackStats`; keep in step with the struct and `STATS_VERSION`
+    pub fn current() -> Self {
+        let fields = [
+            ("service", "string", false, "Service (or service group) the entry covers"),
+            ("total_count", "integer", false, "Number of feedbacks"),
+            ("rating_avg", "number", true, "Average 1-5 rating, null without ratings"),
+            ("rating_count", "integer", false, "Number of rating feedbacks"),
+            ("thumbs_up_count", "integer", false, "Number of thumbs up"),
+            ("thumbs_down_count", "integer", false, "Number of thumbs down"),
+            ("thumbs_up_ratio", "number", true, "Thumbs up over all thumbs, null without thumbs"),
+            ("comment_count", "integer", false, "Number of feedbacks with a comment"),
+            ("satisfaction_score", "number", true, "Ratings and thumbs averaged together"),
+            ("stats_version", "integer", false, "Version of this shape"),
+        ];
+
+        Self {
+            stats_version: STATS_VERSION,
+            fields: fields
+                .into_iter()
+                .map(|(name, field_type, nullable, description)| StatsField {
+                    name: name.to_string(),
+                    field_type: field_type.to_string(),
+                    nullable,
+                    description: description.to_string(),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// Stats for one service and language; `language` is null for untagged feedback
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct LanguageStats {
+    pub service: String,
+    pub language: Option<String>,
+    pub total_count: i64,
+    pub rating_avg: Option<f64>, // Over `rating` feedback only, as in `FeedbackStats`
+    pub thumbs_up_count: i64,
+    pub thumbs_down_count: i64,
+    pub comment_count: i64,
+}
+
+/// Stats for one service and country; `country` is null when it could not be resolved
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct CountryStats {
+    pub service: String,
+    pub country: Option<String>,
+    pub total_count: i64,
+    pub rating_avg: Option<f64>, // Over `rating` feedback only, as in `FeedbackStats`
+    pub thumbs_up_count: i64,
+    pub thumbs_down_count: i64,
+    pub comment_count: i64,
+}
+
+/// Stats for one custom feedback type of a service
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct CustomTypeStats {
+    pub service: String,
+    pub custom_type: String,
+    pub total_count: i64,
+    pub rating_avg: Option<f64>,
+    pub comment_count: i64,
+}
+
+/// Number of thumbs-down feedbacks given for one reason of a service
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct ReasonStats {
+    pub service: String,
+    pub reason: String,
+    pub count: i64,
+}
+
+/// When a service got its first feedback, and how much in the 24 hours that followed
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct FirstFeedbackStats {
+    pub service: String,
+    #[serde(serialize_with = "timestamps::serialize")]
+    pub first_feedback_at: DateTime<Utc>,
+    pub first_day_count: i64,      // Including the first feedback itself
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct StatsRefreshStatus {
+    #[serde(serialize_with = "timestamps::serialize_option")]
+    pub last_refreshed_at: Option<DateTime<Utc>>,  // Last success seen by this instance
+}
+
+/// Outcome of re-seeding the feedback counters from the database
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct MetricsReinitialization {
+    pub total_feedbacks: i64,      // Feedbacks the counters now account for
+}
+
+/// Webhook delivery state of this instance after a pause or resume
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDeliveryStatus {
+    pub paused: bool,
+    pub skipped: u64,              // Feedbacks whose webhooks were dropped since the last pause
+}
+
+/// Outcome of delivering one webhook to one URL
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WebhookDeliveryOutcome {
+    pub url: String,
+    pub delivered: bool,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub status: Option<u16>,       // HTTP status answered by the receiver, if it answered
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+/// Per-URL outcomes of a webhook fan-out, so partial failures are not hidden
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebhookDeliveryReport {
+    pub succeeded: usize,
+    pub failed: usize,
+    pub outcomes: Vec<WebhookDeliveryOutcome>,
+}
+
+/// Which service's webhook URLs a test delivery goes to (default: WEBHOOK_URLS)
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct WebhookTestQuery {
+    pub service: Option<String>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TimeseriesQuery {
+    pub service: Option<String>,
+    pub from_date: Option<DateTime<Utc>>,
+    pub to_date: Option<DateTime<Utc>>,
+    pub timezone: Option<String>,  // IANA name, e.g. "Europe/Paris" (defaults to UTC)
+}
+
+/// Stats over the current calendar day in the viewer's timezone
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TodayStatsQuery {
+    pub service: Option<String>,
+    pub tz: Option<String>,        // IANA name (defaults to UTC)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct TimeseriesPoint {
+    pub date: NaiveDate,           // Local day in the requested timezone
+    pub total_count: i64,
+    pub rating_avg: Option<f64>,   // Over `rating` feedback only, as in `FeedbackStats`
+    pub thumbs_up_count: i64,
+    pub thumbs_down_count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SparklineQuery {
+    pub service: Option<String>,
+    pub days: Option<i64>,         // Trailing UTC days including today (defaults to 30)
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthSignalQuery {
+    pub service: String,
+}
+
+/// Traffic-light status of a service's recent feedback, ordered from best to worst
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum HealthStatus {
+    Good,
+    Warning,
+    Critical,
+}
+
+/// A service's feedback over the rolling window, classified against the configured
+/// thresholds; signals without data in the window do not affect the status
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct HealthSignal {
+    pub service: String,
+    pub window_hours: i64,
+    pub total_count: i64,
+    pub thumbs_up_ratio: Option<f64>,
+    pub rating_avg: Option<f64>,   // Over `rating` feedback only, so NPS scores don't skew it
+    pub status: HealthStatus,
+}
+
+/// Daily volume only, for sparklines; days without feedback are reported with count 0
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SparklinePoint {
+    pub date: NaiveDate,
+    pub count: i64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WordCloudQuery {
+    pub service: Option<String>,
+    pub from_date: Option<DateTime<Utc>>,
+    pub to_date: Option<DateTime<Utc>>,
+    pub limit: Option<i64>,        // Number of words returned (defaults to 50)
+}
+
+/// How often a (stemmed, stopword-filtered) term appears across comments
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct WordFrequency {
+    pub word: String,
+    pub count: i64,                // Occurrences across all scanned comments
+    pub documents: i64,            // Comments containing the word
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RecentCommentsQuery {
+    pub service: Option<String>,
+    pub limit: Option<i64>,        // Number of comments returned (defaults to 10)
+}
+
+/// A recent non-empty comment, for "latest comments" tickers; carries no submitter identity
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct RecentComment {
+    pub id: Uuid,
+    pub service: String,
+    pub feedback_type: FeedbackType,
+    pub rating: Option<i32>,
+    pub comment: String,
+    #[serde(serialize_with = "timestamps::serialize")]
+    pub created_at: DateTime<Utc>,
+}
+
+/// Everything held about one user, for data-access (subject access) requests
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserDataExport {
+    pub user_id: String,
+    pub exported_at: DateTime<Utc>,
+    pub feedback_count: usize,
+    pub feedbacks: Vec<Feedback>,
+}
+
+/// One page of a user's feedback across services, newest first, for support dashboards
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct UserFeedbackTimeline {
+    pub user_id: String,
+    pub summary: UserFeedbackSummary,  // Covers all of the user's feedback, not just this page
+    pub feedbacks: Vec<AdminFeedbackResponse>, // Admin-only, so with the submitter's identity
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, sqlx::FromRow)]
+pub struct UserFeedbackSummary {
+    pub total_count: i64,
+    pub services: Vec<String>,
+    #[serde(serialize_with = "timestamps::serialize_option")]
+    pub last_activity: Option<DateTime<Utc>>,
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -78,6 +559,10 @@ pub struct ExportQuery {
     pub service: Option<String>,
     pub from_date: Option<DateTime<Utc>>,
     pub to_date: Option<DateTime<Utc>>,
+    pub date_format: Option<String>,  // chrono format for CSV `created_at` (defaults to RFC3339)
+    pub columns: Option<String>,   // Comma-separated columns to include, in order (defaults to all)
+    #[serde(default)]
+    pub archive: bool,             // Zip the export together with the services' stats.json
 }
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -108,7 +593,147 @@ impl From<Feedback> for FeedbackResponse {
             thumbs_up: feedback.thumbs_up,
             comment: feedback.comment,
             context: feedback.context,
+            parent_id: feedback.parent_id,
+            language: feedback.language,
+            reason: feedback.reason,
+            country: feedback.country,
+            custom_type: feedback.custom_type,
             created_at: feedback.created_at,
+            updated_at: feedback.updated_at,
+            highlight: feedback.highlight,
+        }
+    }
+}
+
+impl From<Feedback> for AdminFeedbackResponse {
+    fn from(feedback: Feedback) -> Self {
+        let user_id = feedback.user_id.clone();
+        let user_email = feedback.user_email.clone();
+        AdminFeedbackResponse {
+            feedback: feedback.into(),
+            user_id,
+            user_email,
         }
     }
 }
+
+impl TryFrom<FeedbackSubmissionForm> for FeedbackSubmission {
+    type Error = String;
+
+    fn try_from(form: FeedbackSubmissionForm) -> Result<Self, Self::Error> {
+        // HTML forms send unfilled inputs as empty strings
+        fn present(value: Option<String>) -> Option<String> {
+            value.filter(|v| !v.trim().is_empty())
+        }
+
+        let feedback_type = present(form.feedback_type)
+            .ok_or_else(|| "feedback_type is required".to_string())?;
+        let feedback_type =
+            serde_json::from_value(JsonValue::String(feedback_type.trim().to_ascii_lowercase()))
+                .map_err(|_| {
+                    "feedback_type must be rating, thumbs, comment, nps or custom".to_string()
+                })?;
+        let rating = present(form.rating)
+            .map(|r| r.trim().parse().map_err(|_| "rating must be a whole number".to_string()))
+            .transpose()?;
+        let thumbs_up = present(form.thumbs_up)
+            .map(|t| match t.trim() {
+                "true" | "1" => Ok(true),
+                "false" | "0" => Ok(false),
+                _ => Err("thumbs_up must be true or false".to_string()),
+            })
+            .transpose()?;
+        let context = present(form.context)
+            .map(|c| serde_json::from_str(&c).map_err(|_| "context must be valid JSON".to_string()))
+            .transpose()?;
+        let parent_id = present(form.parent_id)
+            .map(|p| p.trim().parse().map_err(|_| "parent_id must be a UUID".to_string()))
+            .transpose()?;
+
+        Ok(FeedbackSubmission {
+            service: form.service.unwrap_or_default(),
+            feedback_type,
+            rating,
+            thumbs_up,
+            comment: present(form.comment),
+            context,
+            parent_id,
+            language: present(form.language),
+            reason: present(form.reason),
+            custom_type: present(form.custom_type),
+            on_behalf_of: present(form.on_behalf_of),
+            country: None,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_stats_schema_matches_stats_fields() {
+        let stats = FeedbackStats {
+            service: "visio".to_string(),
+            total_count: 0,
+            rating_avg: None,
+            rating_count: 0,
+            thumbs_up_count: 0,
+            thumbs_down_count: 0,
+            thumbs_up_ratio: None,
+            comment_count: 0,
+            satisfaction_score: None,
+            stats_version: StatsVersion::default(),
+        };
+        let serialized = serde_json::to_value(&stats).unwrap();
+        let keys: Vec<&String> = serialized.as_object().unwrap().keys().collect();
+
+        let schema = StatsSchema::current();
+        let mut names: Vec<&String> = schema.fields.iter().map(|f| &f.name).collect();
+        names.sort();
+
+        assert_eq!(keys, names);
+        assert_eq!(serialized["stats_version"], STATS_VERSION);
+    }
+
+    #[test]
+    fn test_form_submission_conversion() {
+        let form = FeedbackSubmissionForm {
+            service: Some("visio".to_string()),
+            feedback_type: Some("Rating".to_string()),
+            rating: Some("4".to_string()),
+            comment: Some(String::new()),
+            context: Some(r#"{"call_id": "abc"}"#.to_string()),
+            ..Default::default()
+        };
+
+        let submission = FeedbackSubmission::try_from(form).unwrap();
+
+        assert!(matches!(submission.feedback_type, FeedbackType::Rating));
+        assert_eq!(submission.rating, Some(4));
+        assert_eq!(submission.comment, None);
+        assert_eq!(submission.context.unwrap()["call_id"], "abc");
+
+        let bad_context = FeedbackSubmissionForm {
+            feedback_type: Some("comment".to_string()),
+            context: Some("{not json".to_string()),
+            ..Default::default()
+        };
+        assert!(FeedbackSubmission::try_from(bad_context).is_err());
+        assert!(FeedbackSubmission::try_from(FeedbackSubmissionForm::default()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_epoch_millis_timestamps() {
+        let status = StatsRefreshStatus {
+            last_refreshed_at: Some("2024-01-15T10:30:00Z".parse().unwrap()),
+        };
+
+        let default = serde_json::to_value(&status).unwrap();
+        assert_eq!(default["last_refreshed_at"], "2024-01-15T10:30:00Z");
+
+        let millis =
+            timestamps::scope(true, async { serde_json::to_value(&status).unwrap() }).await;
+        assert_eq!(millis["last_refreshed_at"], 1_705_314_600_000_i64);
+    }
+}
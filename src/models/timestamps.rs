@@ -0,0 +1,49 @@
+//! Per-request timestamp format for API responses.
+//!
+//! Timestamps serialize as RFC 3339 strings unless the request asked for epoch
+//! milliseconds (`X-Timestamp-Format: epoch_millis`); the choice is carried in a
+//! task-local set by `timestamp_format_middleware` for the duration of the request.
+
+use chrono::{DateTime, Utc};
+use serde::{Serialize, Serializer};
+use std::future::Future;
+
+/// Header through which clients choose the timestamp format
+pub const TIMESTAMP_FORMAT_HEADER: &str = "x-timestamp-format";
+
+/// `TIMESTAMP_FORMAT_HEADER` value selecting epoch milliseconds
+pub const EPOCH_MILLIS: &str = "epoch_millis";
+
+tokio::task_local! {
+    static USE_EPOCH_MILLIS: bool;
+}
+
+/// Run `f` with timestamps serialized as epoch milliseconds when `epoch_millis` is set
+pub async fn scope<F: Future>(epoch_millis: bool, f: F) -> F::Output {
+    USE_EPOCH_MILLIS.scope(epoch_millis, f).await
+}
+
+/// Whether the current request asked for epoch milliseconds
+pub fn epoch_millis() -> bool {
+    USE_EPOCH_MILLIS.try_with(|enabled| *enabled).unwrap_or(false)
+}
+
+/// `serialize_with` for `DateTime<Utc>` fields of responses
+pub fn serialize<S: Serializer>(value: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+    if epoch_millis() {
+        serializer.serialize_i64(value.timestamp_millis())
+    } else {
+        value.serialize(serializer)
+    }
+}
+
+/// `serialize_with` for `Option<DateTime<Utc>>` fields of responses
+pub fn serialize_option<S: Serializer>(
+    value: &Option<DateTime<Utc>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    match value {
+        Some(value) => serialize(value, serializer),
+        None => serializer.serialize_none(),
+    }
+}
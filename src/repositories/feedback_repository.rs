@@ -1,7 +1,14 @@
-use crate::db::Database;
-use crate::models::{Feedback, FeedbackQuery, FeedbackStats, FeedbackSubmission, MetricsAggregate};
+use crate::db::{is_read_only_error, BatchInsertError, Database, DbTransaction};
+use crate::models::{
+    CountryStats, CustomTypeStats, Feedback, FeedbackQuery, FeedbackStats, FeedbackSubmission,
+    FeedbackUpdate, FirstFeedbackStats, ImportRecord, LanguageStats, MetricsAggregate, ReasonStats,
+    RecentComment, TimeseriesPoint, TimeseriesQuery, UserFeedbackSummary, WordCloudQuery,
+    WordFrequency,
+};
 use anyhow::Result;
 use async_trait::async_trait;
+use chrono::{DateTime, Utc};
+use std::collections::HashMap;
 use uuid::Uuid;
 
 /// Repository trait for feedback operations
@@ -17,15 +24,136 @@ pub trait FeedbackRepository: Send + Sync {
         submission: FeedbackSubmission,
     ) -> Result<Feedback>;
 
+    /// Create a feedback, or update the user's feedback for the same service and
+    /// `context.message_id`. Returns the row and `true` when it was newly created.
+    async fn upsert(
+        &self,
+        user_id: &str,
+        user_email: Option<&str>,
+        submission: FeedbackSubmission,
+    ) -> Result<(Feedback, bool)>;
+
+    /// Start a transaction, so several writes can be made atomically
+    async fn begin(&self) -> Result<Box<dyn FeedbackTransaction>>;
+
+    /// Create several feedbacks in a single transaction, returned in input order. Each
+    /// submission is paired with whether it upserts by `context.message_id` (see `upsert`),
+    /// and each row is returned with whether it was newly created.
+    /// On failure nothing is persisted and the error carries the failing position
+    /// as a `BatchInsertError`.
+    async fn create_many(
+        &self,
+        user_id: &str,
+        user_email: Option<&str>,
+        submissions: Vec<(FeedbackSubmission, bool)>,
+    ) -> Result<Vec<(Feedback, bool)>> {
+        let tx = self.begin().await?;
+        create_in_transaction(tx, user_id, user_email, submissions).await
+    }
+
+    /// Insert historical feedbacks keeping their original `created_at`, in a single
+    /// transaction. Records are paired and rows returned as in `create_many`.
+    /// Failures carry the failing position as a `BatchInsertError`.
+    async fn import(&self, records: Vec<(ImportRecord, bool)>) -> Result<Vec<(Feedback, bool)>>;
+
     /// Get a feedback by ID
     async fn get_by_id(&self, id: Uuid) -> Result<Option<Feedback>>;
 
+    /// Replace the editable fields of a feedback, returning the updated row
+    async fn update(&self, id: Uuid, update: FeedbackUpdate) -> Result<Option<Feedback>>;
+
+    /// Get the follow-ups linked to a feedback, oldest first
+    async fn get_children(&self, parent_id: Uuid) -> Result<Vec<Feedback>>;
+
+    /// Get every feedback held for a user, oldest first
+    async fn get_by_user(&self, user_id: &str) -> Result<Vec<Feedback>>;
+
+    /// Count and list the services of everything a user has submitted
+    async fn get_user_summary(&self, user_id: &str) -> Result<UserFeedbackSummary>;
+
     /// Query feedbacks with filters
     async fn query(&self, query: FeedbackQuery) -> Result<Vec<Feedback>>;
 
+    /// Query feedbacks with filters under a statement timeout for this query only
+    async fn query_with_timeout(
+        &self,
+        query: FeedbackQuery,
+        statement_timeout_ms: u64,
+    ) -> Result<Vec<Feedback>>;
+
     /// Get statistics for feedbacks
     async fn get_stats(&self, service: Option<&str>) -> Result<Vec<FeedbackStats>>;
 
+    /// Get statistics with services rolled up into their configured group, optionally
+    /// restricted to one group (or ungrouped service)
+    async fn get_grouped_stats(
+        &self,
+        service_groups: &HashMap<String, String>,
+        group: Option<&str>,
+    ) -> Result<Vec<FeedbackStats>>;
+
+    /// Get statistics per service and language
+    async fn get_language_stats(&self, service: Option<&str>) -> Result<Vec<LanguageStats>>;
+
+    /// Count custom feedback per service and custom type
+    async fn get_custom_type_stats(&self, service: Option<&str>) -> Result<Vec<CustomTypeStats>>;
+
+    /// Get statistics per service and country
+    async fn get_country_stats(&self, service: Option<&str>) -> Result<Vec<CountryStats>>;
+
+    /// Earliest feedback per service and the number received in its first 24 hours
+    async fn get_first_feedback_stats(
+        &self,
+        service: Option<&str>,
+    ) -> Result<Vec<FirstFeedbackStats>>;
+
+    /// Newest non-empty comments, optionally for one service
+    async fn get_recent_comments(
+        &self,
+        service: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<RecentComment>>;
+
+    /// Count thumbs-down feedback per service and reason
+    async fn get_reason_stats(&self, service: Option<&str>) -> Result<Vec<ReasonStats>>;
+
+    /// Get daily statistics bucketed on local days in the given timezone
+    async fn get_timeseries(
+        &self,
+        query: TimeseriesQuery,
+        timezone: &str,
+    ) -> Result<Vec<TimeseriesPoint>>;
+
+    /// Stats of one service's feedback created since `since`
+    async fn get_window_stats(
+        &self,
+        service: &str,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<FeedbackStats>;
+
+    /// Stats per service of feedback created in `[from, to)`
+    async fn get_range_stats(
+        &self,
+        service: Option<&str>,
+        from: chrono::DateTime<chrono::Utc>,
+        to: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<FeedbackStats>>;
+
+    /// Most frequent comment terms over at most `scan_limit` of the latest matching feedbacks
+    async fn get_word_frequencies(
+        &self,
+        query: &WordCloudQuery,
+        text_search_config: &str,
+        scan_limit: i64,
+        limit: i64,
+    ) -> Result<Vec<WordFrequency>>;
+
+    /// Delete one batch of at most `limit` feedbacks created before `cutoff`
+    async fn delete_before(&self, cutoff: DateTime<Utc>, limit: i64) -> Result<u64>;
+
+    /// Refresh the precomputed stats; `false` when a refresh was already in progress
+    async fn refresh_stats(&self) -> Result<bool>;
+
     /// Get aggregated metrics for Prometheus initialization
     async fn get_metrics_aggregates(&self) -> Result<Vec<MetricsAggregate>>;
 
@@ -33,6 +161,74 @@ pub trait FeedbackRepository: Send + Sync {
     async fn health_check(&self) -> Result<()>;
 }
 
+/// Writes grouped in one transaction. Nothing is persisted until `commit`; dropping the
+/// handle without committing rolls everything back.
+#[async_trait]
+pub trait FeedbackTransaction: Send {
+    /// Create a new feedback within the transaction
+    async fn create(
+        &mut self,
+        user_id: &str,
+        user_email: Option<&str>,
+        submission: FeedbackSubmission,
+    ) -> Result<Feedback>;
+
+    /// Create a feedback, or update the user's feedback for the same `context.message_id`,
+    /// within the transaction
+    async fn upsert(
+        &mut self,
+        user_id: &str,
+        user_email: Option<&str>,
+        submission: FeedbackSubmission,
+    ) -> Result<(Feedback, bool)>;
+
+    /// Delete every feedback of a user, returning how many were deleted
+    async fn delete_by_user(&mut self, user_id: &str) -> Result<u64>;
+
+    async fn commit(self: Box<Self>) -> Result<()>;
+
+    async fn rollback(self: Box<Self>) -> Result<()>;
+}
+
+/// Write `submissions` through `tx` and commit, or roll back at the first failure,
+/// reported as a `BatchInsertError` at its position (see `create_many`)
+async fn create_in_transaction(
+    mut tx: Box<dyn FeedbackTransaction>,
+    user_id: &str,
+    user_email: Option<&str>,
+    submissions: Vec<(FeedbackSubmission, bool)>,
+) -> Result<Vec<(Feedback, bool)>> {
+    let mut feedbacks = Vec::with_capacity(submissions.len());
+    for (position, (submission, upsert)) in submissions.into_iter().enumerate() {
+        let result = if upsert {
+            tx.upsert(user_id, user_email, submission).await
+        } else {
+            tx.create(user_id, user_email, submission)
+                .await
+                .map(|feedback| (feedback, true))
+        };
+        match result {
+            Ok(row) => feedbacks.push(row),
+            Err(e) => {
+                // Undo the rows inserted so far so the batch is all-or-nothing
+                tx.rollback().await.ok();
+                // A read-only database rejects the whole batch, not this row
+                if e.downcast_ref::<sqlx::Error>().is_some_and(is_read_only_error) {
+                    return Err(e);
+                }
+                return Err(BatchInsertError {
+                    position,
+                    message: format!("{:#}", e),
+                }
+                .into());
+            }
+        }
+    }
+
+    tx.commit().await?;
+    Ok(feedbacks)
+}
+
 /// PostgreSQL implementation of FeedbackRepository
 pub struct PostgresFeedbackRepository {
     db: Database,
@@ -55,18 +251,145 @@ impl FeedbackRepository for PostgresFeedbackRepository {
         self.db.create_feedback(user_id, user_email, submission).await
     }
 
+    async fn upsert(
+        &self,
+        user_id: &str,
+        user_email: Option<&str>,
+        submission: FeedbackSubmission,
+    ) -> Result<(Feedback, bool)> {
+        self.db.upsert_feedback(user_id, user_email, submission).await
+    }
+
+    async fn begin(&self) -> Result<Box<dyn FeedbackTransaction>> {
+        Ok(Box::new(self.db.begin().await?))
+    }
+
+    async fn import(&self, records: Vec<(ImportRecord, bool)>) -> Result<Vec<(Feedback, bool)>> {
+        self.db.import_feedbacks(records).await
+    }
+
     async fn get_by_id(&self, id: Uuid) -> Result<Option<Feedback>> {
         self.db.get_feedback(id).await
     }
 
+    async fn update(&self, id: Uuid, update: FeedbackUpdate) -> Result<Option<Feedback>> {
+        self.db.update_feedback(id, update).await
+    }
+
+    async fn get_children(&self, parent_id: Uuid) -> Result<Vec<Feedback>> {
+        self.db.get_children(parent_id).await
+    }
+
+    async fn get_by_user(&self, user_id: &str) -> Result<Vec<Feedback>> {
+        self.db.get_feedbacks_by_user(user_id).await
+    }
+
+    async fn get_user_summary(&self, user_id: &str) -> Result<UserFeedbackSummary> {
+        self.db.get_user_summary(user_id).await
+    }
+
     async fn query(&self, query: FeedbackQuery) -> Result<Vec<Feedback>> {
         self.db.query_feedbacks(query).await
     }
 
+    async fn query_with_timeout(
+        &self,
+        query: FeedbackQuery,
+        statement_timeout_ms: u64,
+    ) -> Result<Vec<Feedback>> {
+        self.db
+            .query_feedbacks_with_timeout(query, Some(statement_timeout_ms))
+            .await
+    }
+
     async fn get_stats(&self, service: Option<&str>) -> Result<Vec<FeedbackStats>> {
         self.db.get_stats(service).await
     }
 
+    async fn get_grouped_stats(
+        &self,
+        service_groups: &HashMap<String, String>,
+        group: Option<&str>,
+    ) -> Result<Vec<FeedbackStats>> {
+        self.db.get_grouped_stats(service_groups, group).await
+    }
+
+    async fn get_language_stats(&self, service: Option<&str>) -> Result<Vec<LanguageStats>> {
+        self.db.get_language_stats(service).await
+    }
+
+    async fn get_country_stats(&self, service: Option<&str>) -> Result<Vec<CountryStats>> {
+        self.db.get_country_stats(service).await
+    }
+
+    async fn get_custom_type_stats(&self, service: Option<&str>) -> Result<Vec<CustomTypeStats>> {
+        self.db.get_custom_type_stats(service).await
+    }
+
+    async fn get_first_feedback_stats(
+        &self,
+        service: Option<&str>,
+    ) -> Result<Vec<FirstFeedbackStats>> {
+        self.db.get_first_feedback_stats(service).await
+    }
+
+    async fn get_recent_comments(
+        &self,
+        service: Option<&str>,
+        limit: i64,
+    ) -> Result<Vec<RecentComment>> {
+        self.db.get_recent_comments(service, limit).await
+    }
+
+    async fn get_reason_stats(&self, service: Option<&str>) -> Result<Vec<ReasonStats>> {
+        self.db.get_reason_stats(service).await
+    }
+
+    async fn get_timeseries(
+        &self,
+        query: TimeseriesQuery,
+        timezone: &str,
+    ) -> Result<Vec<TimeseriesPoint>> {
+        self.db.get_timeseries(query, timezone).await
+    }
+
+    async fn get_window_stats(
+        &self,
+        service: &str,
+        since: chrono::DateTime<chrono::Utc>,
+    ) -> Result<FeedbackStats> {
+        self.db.get_window_stats(service, since).await
+    }
+
+    async fn get_range_stats(
+        &self,
+        service: Option<&str>,
+        from: chrono::DateTime<chrono::Utc>,
+        to: chrono::DateTime<chrono::Utc>,
+    ) -> Result<Vec<FeedbackStats>> {
+        self.db.get_range_stats(service, from, to).await
+    }
+
+    async fn get_word_frequencies(
+        &self,
+        query: &WordCloudQuery,
+        text_search_config: &str,
+        scan_limit: i64,
+        limit: i64,
+    ) -> Result<Vec<WordFrequency>> {
+        self.db
+            .get_word_frequencies(query, text_search_config, scan_limit, limit)
+            .await
+    }
+
+    async fn delete_before(&self, cutoff: DateTime<Utc>, limit: i64) -> Result<u64> {
+        self.db.delete_feedbacks_before(cutoff, limit).await
+    }
+
+    async fn refresh_stats(&self) -> Result<bool> {
+        self.db.refresh_stats().await
+    }
+
     async fn get_metrics_aggregates(&self) -> Result<Vec<MetricsAggregate>> {
         self.db.get_metrics_aggregates().await
     }
@@ -75,3 +398,162 @@ impl FeedbackRepository for PostgresFeedbackRepository {
         self.db.health_check().await
     }
 }
+
+#[async_trait]
+impl FeedbackTransaction for DbTransaction {
+    async fn create(
+        &mut self,
+        user_id: &str,
+        user_email: Option<&str>,
+        submission: FeedbackSubmission,
+    ) -> Result<Feedback> {
+        self.create_feedback(user_id, user_email, submission).await
+    }
+
+    async fn upsert(
+        &mut self,
+        user_id: &str,
+        user_email: Option<&str>,
+        submission: FeedbackSubmission,
+    ) -> Result<(Feedback, bool)> {
+        self.upsert_feedback(user_id, user_email, submission).await
+    }
+
+    async fn delete_by_user(&mut self, user_id: &str) -> Result<u64> {
+        self.delete_feedbacks_by_user(user_id).await
+    }
+
+    async fn commit(self: Box<Self>) -> Result<()> {
+        DbTransaction::commit(*self).await
+    }
+
+    async fn rollback(self: Box<Self>) -> Result<()> {
+        DbTransaction::rollback(*self).await
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::models::FeedbackType;
+    use std::sync::{Arc, Mutex};
+
+    /// Transaction whose writes fail from `fail_at` on, recording every call
+    struct FakeTransaction {
+        fail_at: usize,
+        writes: usize,
+        calls: Arc<Mutex<Vec<&'static str>>>,
+    }
+
+    impl FakeTransaction {
+        fn write(&mut self, submission: FeedbackSubmission) -> Result<Feedback> {
+            self.calls.lock().unwrap().push("write");
+            self.writes += 1;
+            if self.writes > self.fail_at {
+                anyhow::bail!("violates check constraint");
+            }
+            Ok(Feedback {
+                id: Uuid::new_v4(),
+                user_id: "user".to_string(),
+                user_email: None,
+                service: submission.service,
+                feedback_type: submission.feedback_type,
+                rating: submission.rating,
+                thumbs_up: submission.thumbs_up,
+                comment: submission.comment,
+                context: submission.context,
+                parent_id: submission.parent_id,
+                language: submission.language,
+                reason: submission.reason,
+                country: submission.country,
+                custom_type: submission.custom_type,
+                created_at: Utc::now(),
+                updated_at: Utc::now(),
+                highlight: None,
+                total_count: None,
+            })
+        }
+    }
+
+    #[async_trait]
+    impl FeedbackTransaction for FakeTransaction {
+        async fn create(
+            &mut self,
+            _user_id: &str,
+            _user_email: Option<&str>,
+            submission: FeedbackSubmission,
+        ) -> Result<Feedback> {
+            self.write(submission)
+        }
+
+        async fn upsert(
+            &mut self,
+            _user_id: &str,
+            _user_email: Option<&str>,
+            submission: FeedbackSubmission,
+        ) -> Result<(Feedback, bool)> {
+            self.write(submission).map(|feedback| (feedback, false))
+        }
+
+        async fn delete_by_user(&mut self, _user_id: &str) -> Result<u64> {
+            Ok(0)
+        }
+
+        async fn commit(self: Box<Self>) -> Result<()> {
+            self.calls.lock().unwrap().push("commit");
+            Ok(())
+        }
+
+        async fn rollback(self: Box<Self>) -> Result<()> {
+            self.calls.lock().unwrap().push("rollback");
+            Ok(())
+        }
+    }
+
+    fn thumbs() -> FeedbackSubmission {
+        FeedbackSubmission {
+            service: "test-service".to_string(),
+            feedback_type: FeedbackType::Thumbs,
+            rating: None,
+            thumbs_up: Some(true),
+            comment: None,
+            context: None,
+            parent_id: None,
+            language: None,
+            reason: None,
+            custom_type: None,
+            on_behalf_of: None,
+            country: None,
+        }
+    }
+
+    async fn run(fail_at: usize) -> (Result<Vec<(Feedback, bool)>>, Vec<&'static str>) {
+        let calls = Arc::new(Mutex::new(Vec::new()));
+        let tx = Box::new(FakeTransaction { fail_at, writes: 0, calls: Arc::clone(&calls) });
+        let submissions = vec![(thumbs(), false), (thumbs(), true), (thumbs(), false)];
+        let result = create_in_transaction(tx, "user", None, submissions).await;
+        let calls = calls.lock().unwrap().clone();
+        (result, calls)
+    }
+
+    #[tokio::test]
+    async fn test_failed_write_rolls_back_batch() {
+        let (result, calls) = run(1).await;
+
+        let error = result.unwrap_err();
+        let batch_error = error.downcast_ref::<BatchInsertError>().unwrap();
+        assert_eq!(batch_error.position, 1);
+        assert!(batch_error.message.contains("violates check constraint"));
+        // Nothing is written after the failure, and the batch is never committed
+        assert_eq!(calls, vec!["write", "write", "rollback"]);
+    }
+
+    #[tokio::test]
+    async fn test_successful_batch_commits() {
+        let (result, calls) = run(usize::MAX).await;
+
+        let created: Vec<bool> = result.unwrap().into_iter().map(|(_, c)| c).collect();
+        assert_eq!(created, vec![true, false, true]);
+        assert_eq!(calls, vec!["write", "write", "write", "commit"]);
+    }
+}
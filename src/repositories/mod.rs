@@ -17,4 +17,6 @@
 
 mod feedback_repository;
 
-pub use feedback_repository::{FeedbackRepository, PostgresFeedbackRepository};
+pub use feedback_repository::{
+    FeedbackRepository, FeedbackTransaction, PostgresFeedbackRepository,
+};
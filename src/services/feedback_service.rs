@@ -1,37 +1,293 @@
-use crate::config::Config;
+use crate::config::{CommentOverflow, Config, RatingOutOfRange};
+use crate::db::BatchInsertError;
 use crate::error::{AppError, Result};
-use crate::exports::{send_webhook, WebhookPayload};
-use crate::models::{Feedback, FeedbackQuery, FeedbackStats, FeedbackSubmission};
+use crate::exports::{send_webhook, webhook_header_map, WebhookPayload};
+use crate::models::{
+    BulkCreateResponse, BulkCreateResult, CountryStats, CustomTypeStats, Feedback, FeedbackQuery,
+    FeedbackStats, FeedbackSubmission, FeedbackType, FeedbackTypeSchema, FeedbackUpdate,
+    FirstFeedbackStats, HealthSignal, HealthSignalQuery, HealthStatus, ImportRecord,
+    ImportRejection, ImportResponse, LanguageStats, MetricsReinitialization, RatingRange,
+    ReasonStats, RecentComment, RecentCommentsQuery, SparklinePoint, SparklineQuery,
+    StatsRefreshStatus, TimeseriesPoint, TimeseriesQuery, TodayStatsQuery, UserDataExport,
+    UserFeedbackSummary, WebhookDeliveryReport, WebhookDeliveryStatus, WordCloudQuery,
+    WordFrequency,
+};
 use crate::repositories::FeedbackRepository;
-use crate::validation::Validate;
-use std::sync::Arc;
+use crate::validation::{
+    reject_submission, sanitize_for_log, Validate, MAX_COMMENT_LENGTH, NPS_RANGE, RATING_RANGE,
+};
+use rand::Rng;
+use sqlx::types::JsonValue;
+use std::collections::{HashMap, HashSet};
+use std::future::Future;
+use std::net::IpAddr;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+use tokio::sync::Semaphore;
+use tokio::task::JoinSet;
 use uuid::Uuid;
 
+/// Maximum number of submissions accepted in a single bulk create
+const MAX_BULK_SIZE: usize = 1000;
+
+/// Page size for a user's feedback timeline when the caller sets no limit
+const DEFAULT_USER_PAGE_SIZE: i64 = 100;
+
+/// Page size for a feedback query when the caller sets no limit (samples use SAMPLE_SIZE)
+const DEFAULT_QUERY_LIMIT: i64 = 100;
+
+/// Sparkline length when the caller sets no `days`
+const DEFAULT_SPARKLINE_DAYS: i64 = 30;
+
+/// Words in a word cloud when the caller sets no `limit`
+const DEFAULT_WORD_CLOUD_LIMIT: i64 = 50;
+
+/// Most recent comments scanned for a word cloud, bounding the cost of tokenizing them
+const WORD_CLOUD_SCAN_LIMIT: i64 = 10_000;
+
+/// Recent comments returned when the query sets no limit
+const DEFAULT_RECENT_COMMENTS_LIMIT: i64 = 10;
+
+/// Appended to comments shortened under `COMMENT_OVERFLOW=truncate`
+const TRUNCATION_MARKER: &str = " [truncated]";
+
 /// Service layer for feedback operations
 /// Handles business logic, orchestration, and coordination between components
 pub struct FeedbackService {
     repository: Arc<dyn FeedbackRepository>,
     config: Arc<Config>,
+    stats_cache: StatsCache,
+    metrics_timezone: chrono_tz::Tz,
+    last_stats_refresh: RwLock<Option<chrono::DateTime<chrono::Utc>>>,
+    context_validators: HashMap<String, jsonschema::Validator>,
+    geoip: Option<maxminddb::Reader<Vec<u8>>>,
+    webhook_permits: Arc<Semaphore>,
+    webhook_headers: reqwest::header::HeaderMap,
+    webhooks_paused: AtomicBool,
+    webhooks_skipped: AtomicU64,
+    health_cache: HealthCache,
+}
+
+/// Outcome of the last database health check and when it started, reused by probes for
+/// `ttl` so they do not each query the database
+struct HealthCache {
+    ttl: Duration,
+    last: RwLock<Option<(Instant, bool)>>,
+}
+
+/// Stats computed at an instant
+type CachedStats = (Instant, Vec<FeedbackStats>);
+
+/// Recently computed stats, keyed by service filter (`None` = all services)
+struct StatsCache {
+    ttl: Duration,
+    entries: RwLock<HashMap<Option<String>, CachedStats>>,
+}
+
+impl StatsCache {
+    fn new(ttl_secs: u64) -> Self {
+        Self {
+            ttl: Duration::from_secs(ttl_secs),
+            entries: RwLock::new(HashMap::new()),
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        !self.ttl.is_zero()
+    }
+
+    fn get(&self, service: Option<&str>) -> Option<Vec<FeedbackStats>> {
+        let entries = self.entries.read().unwrap_or_else(|e| e.into_inner());
+        entries
+            .get(&service.map(str::to_string))
+            .filter(|(cached_at, _)| cached_at.elapsed() < self.ttl)
+            .map(|(_, stats)| stats.clone())
+    }
+
+    fn insert(&self, service: Option<&str>, stats: Vec<FeedbackStats>) {
+        let mut entries = self.entries.write().unwrap_or_else(|e| e.into_inner());
+        entries.insert(service.map(str::to_string), (Instant::now(), stats));
+    }
 }
 
 impl FeedbackService {
     pub fn new(repository: Arc<dyn FeedbackRepository>, config: Arc<Config>) -> Self {
-        Self { repository, config }
+        let stats_cache = StatsCache::new(config.stats_cache_ttl_secs);
+        // Validated when the config was loaded
+        let metrics_timezone = config.metrics_timezone.parse().unwrap_or(chrono_tz::UTC);
+        // Compiled once; schemas were checked when the config was loaded
+        let context_validators = config
+            .context_schemas
+            .iter()
+            .filter_map(|(service, schema)| {
+                jsonschema::validator_for(schema)
+                    .ok()
+                    .map(|validator| (service.clone(), validator))
+            })
+            .collect();
+        // Opened once; the file was checked when the config was loaded
+        let geoip = config
+            .geoip_db_path
+            .as_deref()
+            .and_then(|path| maxminddb::Reader::open_readfile(path).ok());
+        let webhook_permits = Arc::new(Semaphore::new(config.webhook_max_concurrency));
+        // Parsed once; the headers were checked when the config was loaded
+        let webhook_headers = webhook_header_map(&config.webhook_headers);
+        let health_cache = HealthCache {
+            ttl: Duration::from_millis(config.health_cache_ttl_ms),
+            last: RwLock::new(None),
+        };
+        Self {
+            repository,
+            config,
+            stats_cache,
+            metrics_timezone,
+            last_stats_refresh: RwLock::new(None),
+            context_validators,
+            geoip,
+            webhook_permits,
+            webhook_headers,
+            webhooks_paused: AtomicBool::new(false),
+            webhooks_skipped: AtomicU64::new(0),
+            health_cache,
+        }
+    }
+
+    /// ISO country code of `ip` from the GeoIP database; `None` when no database is
+    /// configured or the address is not in it
+    pub fn resolve_country(&self, ip: IpAddr) -> Option<String> {
+        lookup_country(self.geoip.as_ref()?, ip)
+    }
+
+    /// Stop (or restart) webhook delivery on this instance. While paused, deliveries are
+    /// dropped and counted rather than sent; pausing again restarts the count.
+    pub fn set_webhooks_paused(&self, paused: bool) -> WebhookDeliveryStatus {
+        let was_paused = self.webhooks_paused.swap(paused, Ordering::SeqCst);
+        let skipped = if paused && !was_paused {
+            self.webhooks_skipped.store(0, Ordering::SeqCst);
+            0
+        } else {
+            self.webhooks_skipped.load(Ordering::SeqCst)
+        };
+
+        match (was_paused, paused) {
+            (false, true) => tracing::warn!("Webhook delivery paused"),
+            (true, false) => tracing::info!(skipped = skipped, "Webhook delivery resumed"),
+            _ => {}
+        }
+        WebhookDeliveryStatus { paused, skipped }
+    }
+
+    /// Send a `webhook.test` event with a sample feedback to `service`'s webhook URLs (or
+    /// WEBHOOK_URLS) right away, even while delivery is paused, and report each outcome
+    pub async fn test_webhooks(&self, service: Option<&str>) -> Result<WebhookDeliveryReport> {
+        let webhook_urls = service
+            .and_then(|service| self.config.service_webhook_urls.get(service))
+            .unwrap_or(&self.config.webhook_urls);
+        let now = chrono::Utc::now();
+        let payload = WebhookPayload {
+            event: "webhook.test".to_string(),
+            feedback: Feedback {
+                id: Uuid::new_v4(),
+                user_id: "webhook-test".to_string(),
+                user_email: None,
+                service: service.unwrap_or("webhook-test").to_string(),
+                feedback_type: FeedbackType::Comment,
+                rating: None,
+                thumbs_up: None,
+                comment: Some("Test delivery".to_string()),
+                context: None,
+                parent_id: None,
+                language: None,
+                reason: None,
+                country: None,
+                custom_type: None,
+                created_at: now,
+                updated_at: now,
+                highlight: None,
+                total_count: None,
+            },
+        };
+
+        let config = &self.config;
+        let headers = self.webhook_headers.clone();
+        send_webhook(
+            webhook_urls,
+            payload,
+            config.webhook_format,
+            headers,
+            config.webhook_allow_insecure,
+            Instant::now(),
+        )
+        .await
+        .map_err(|e| AppError::InternalError(format!("Failed to send webhooks: {}", e)))
     }
 
-    /// Health check - verify the service and its dependencies are accessible
+    /// Health check - verify the service and its dependencies are accessible. A result
+    /// younger than HEALTH_CACHE_TTL_MS is reused; its age counts from when the check
+    /// started, so a cached "healthy" never outlives the TTL.
     pub async fn health_check(&self) -> Result<()> {
-        self.repository.health_check().await.map_err(Into::into)
+        let cache = &self.health_cache;
+        if !cache.ttl.is_zero() {
+            let last = *cache.last.read().unwrap_or_else(|e| e.into_inner());
+            if let Some((checked_at, healthy)) = last {
+                if checked_at.elapsed() < cache.ttl {
+                    return if healthy {
+                        Ok(())
+                    } else {
+                        Err(AppError::ServiceUnavailable("Database is unhealthy".to_string()))
+                    };
+                }
+            }
+        }
+
+        let checked_at = Instant::now();
+        let result = self.repository.health_check().await.map_err(Into::into);
+        if !cache.ttl.is_zero() {
+            *cache.last.write().unwrap_or_else(|e| e.into_inner()) =
+                Some((checked_at, result.is_ok()));
+        }
+        result
+    }
+
+    /// Reset the feedback counters to match the database, e.g. to reconcile drift
+    pub async fn reinitialize_metrics(&self) -> Result<MetricsReinitialization> {
+        let total_feedbacks =
+            crate::metrics::reinitialize_metrics_from_db(self.repository.as_ref()).await?;
+        tracing::info!(total_feedbacks = total_feedbacks, "Metrics reinitialized from database");
+        Ok(MetricsReinitialization { total_feedbacks })
+    }
+
+    /// Run every check `create_feedback` applies to `submission` without storing it or
+    /// touching metrics and webhooks, for clients testing their payloads. The checks are not
+    /// timed either, so the validation latency only reflects real submissions.
+    pub async fn validate_submission(
+        &self,
+        user_id: &str,
+        user_email: Option<&str>,
+        mut submission: FeedbackSubmission,
+    ) -> Result<()> {
+        self.prepare_submission(user_id, user_email, &mut submission, true).await
     }
 
     /// Create a new feedback with full business logic orchestration
-    /// This includes validation, persistence, metrics recording, and webhook notifications
+    /// This includes validation, persistence, metrics recording, and webhook notifications.
+    /// Returns the stored feedback and `false` when, in message_id upsert mode, it replaced
+    /// the user's earlier feedback instead of creating one.
     pub async fn create_feedback(
         &self,
         user_id: &str,
         user_email: Option<&str>,
-        submission: FeedbackSubmission,
-    ) -> Result<Feedback> {
+        mut submission: FeedbackSubmission,
+    ) -> Result<(Feedback, bool)> {
+        // Observed on drop, so failed creations are timed as well
+        let _timer = crate::metrics::SERVICE_LATENCY
+            .with_label_values(&["create_feedback"])
+            .start_timer();
+        // Only observed once the feedback is stored, for alerting on creation slowness
+        let started = Instant::now();
+
         // Log with structured context
         tracing::debug!(
             user_id = %user_id,
@@ -42,13 +298,33 @@ impl FeedbackService {
         );
 
         // 1. Validate input according to business rules
-        self.validate_feedback_submission(&submission)?;
+        self.prepare_submission(user_id, user_email, &mut submission, false).await?;
+        self.apply_context_storage_policy(&mut submission);
 
-        // 2. Persist feedback via repository
-        let feedback = self
-            .repository
-            .create(user_id, user_email, submission.clone())
-            .await?;
+        // 2. Persist feedback via repository; in upsert mode a repeat submission for the
+        // same message replaces the user's earlier feedback
+        let (feedback, created) = if self.upserts_by_message_id(&submission) {
+            self.repository
+                .upsert(user_id, user_email, submission.clone())
+                .await?
+        } else {
+            let feedback = self
+                .repository
+                .create(user_id, user_email, submission.clone())
+                .await?;
+            (feedback, true)
+        };
+
+        if !created {
+            tracing::info!(
+                feedback_id = %feedback.id,
+                user_id = %user_id,
+                service = %feedback.service,
+                "Feedback replaced by repeat submission for the same message"
+            );
+            crate::metrics::FEEDBACK_CREATE_DURATION.observe(started.elapsed().as_secs_f64());
+            return Ok((feedback, false));
+        }
 
         // Log successful creation with feedback ID
         tracing::info!(
@@ -60,12 +336,281 @@ impl FeedbackService {
         );
 
         // 3. Record metrics asynchronously (fire and forget)
-        self.record_feedback_metrics(&submission);
+        self.record_feedback_metrics(&feedback);
 
         // 4. Send webhook notifications asynchronously if configured
         self.trigger_webhook_notifications(feedback.clone()).await;
 
-        Ok(feedback)
+        crate::metrics::FEEDBACK_CREATE_DURATION.observe(started.elapsed().as_secs_f64());
+        Ok((feedback, true))
+    }
+
+    /// Create many feedbacks at once, reporting a result per input entry in input order.
+    /// Invalid entries are reported individually; valid entries are persisted in a single
+    /// transaction, so a database error leaves nothing half-written.
+    pub async fn create_many(
+        &self,
+        user_id: &str,
+        user_email: Option<&str>,
+        submissions: Vec<FeedbackSubmission>,
+    ) -> Result<BulkCreateResponse> {
+        if submissions.is_empty() {
+            return Err(AppError::ValidationError(
+                "Bulk create requires at least one feedback".to_string(),
+            ));
+        }
+
+        if submissions.len() > MAX_BULK_SIZE {
+            return Err(AppError::ValidationError(format!(
+                "Too many feedbacks in one request (max {})",
+                MAX_BULK_SIZE
+            )));
+        }
+
+        // The submitter is the same for every entry
+        self.validate_user_email(user_email)?;
+
+        // 1. Validate every entry, remembering the input index of the valid ones
+        let mut results: Vec<Option<BulkCreateResult>> = vec![None; submissions.len()];
+        let mut valid_indices = Vec::new();
+        let mut valid_submissions = Vec::new();
+
+        for (index, mut submission) in submissions.into_iter().enumerate() {
+            self.apply_default_service(&mut submission);
+            self.apply_comment_overflow_policy(&mut submission.comment);
+            self.apply_rating_range_policy(
+                &submission.feedback_type,
+                submission.custom_type.as_deref(),
+                &mut submission.rating,
+            );
+            let validation = match self
+                .validate_service_enabled(&submission)
+                .and_then(|()| self.validate_feedback_submission(&submission))
+            {
+                Ok(()) => self.validate_parent(user_id, submission.parent_id).await,
+                Err(e) => Err(e),
+            };
+
+            match validation {
+                Ok(()) => {
+                    self.apply_context_storage_policy(&mut submission);
+                    let upsert = self.upserts_by_message_id(&submission);
+                    valid_indices.push(index);
+                    valid_submissions.push((submission, upsert));
+                }
+                Err(AppError::ValidationError(error)) => {
+                    results[index] = Some(BulkCreateResult::Failed { index, error });
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        // 2. Persist all valid entries atomically; in upsert mode a repeat submission for
+        // the same message replaces the user's earlier feedback, as for a single create
+        if !valid_submissions.is_empty() {
+            match self
+                .repository
+                .create_many(user_id, user_email, valid_submissions.clone())
+                .await
+            {
+                Ok(feedbacks) => {
+                    for (index, (feedback, created)) in valid_indices.iter().zip(feedbacks) {
+                        results[*index] = Some(BulkCreateResult::Created {
+                            index: *index,
+                            id: feedback.id,
+                            replaced: !created,
+                        });
+                        if created {
+                            self.record_feedback_metrics(&feedback);
+                            self.trigger_webhook_notifications(feedback).await;
+                        }
+                    }
+                }
+                Err(e) => {
+                    let Some(batch_error) = e.downcast_ref::<BatchInsertError>() else {
+                        return Err(e.into());
+                    };
+
+                    // The transaction was rolled back: report the entry that failed and
+                    // mark every other valid entry as not created
+                    let failed_index = valid_indices[batch_error.position];
+                    tracing::error!(
+                        user_id = %user_id,
+                        failed_index = failed_index,
+                        error = %batch_error.message,
+                        "Bulk create rolled back after database error"
+                    );
+                    for index in &valid_indices {
+                        let error = if *index == failed_index {
+                            "Database error while creating feedback".to_string()
+                        } else {
+                            format!(
+                                "Not created: batch rolled back after database error at index {}",
+                                failed_index
+                            )
+                        };
+                        results[*index] = Some(BulkCreateResult::Failed {
+                            index: *index,
+                            error,
+                        });
+                    }
+                }
+            }
+        }
+
+        let results: Vec<BulkCreateResult> = results.into_iter().flatten().collect();
+        let created = results
+            .iter()
+            .filter(|r| matches!(r, BulkCreateResult::Created { .. }))
+            .count();
+
+        tracing::info!(
+            user_id = %user_id,
+            created = created,
+            failed = results.len() - created,
+            "Bulk feedback creation completed"
+        );
+
+        Ok(BulkCreateResponse {
+            created,
+            failed: results.len() - created,
+            results,
+        })
+    }
+
+    /// Import historical feedback from NDJSON (one `ImportRecord` per line), keeping each
+    /// row's original `created_at`. Unparseable or invalid rows (by the service, parent and
+    /// content rules of live submissions, plus the import date bounds) are rejected with
+    /// their line number; the remaining rows are inserted in batches of `IMPORT_BATCH_SIZE`,
+    /// each in its own transaction. Rows of upsert-mode services replace the user's feedback for
+    /// the same `context.message_id`, keeping the newest row of the file. A database failure
+    /// not tied to a row stops the import; what was written stays and the rest is rejected.
+    /// Imports update metrics but do not trigger webhooks.
+    pub async fn import_feedbacks(&self, ndjson: &str) -> Result<ImportResponse> {
+        let mut rejected = Vec::new();
+        let mut lines = Vec::new();
+        let mut records = Vec::new();
+
+        // 1. Parse and validate every line, remembering the line number of the valid ones
+        for (index, raw) in ndjson.lines().enumerate() {
+            let line = index + 1;
+            if raw.trim().is_empty() {
+                continue;
+            }
+
+            let mut record = match serde_json::from_str::<ImportRecord>(raw) {
+                Ok(record) => record,
+                Err(e) => {
+                    rejected.push(ImportRejection {
+                        line,
+                        error: format!("Invalid JSON: {}", e),
+                    });
+                    continue;
+                }
+            };
+
+            self.apply_default_service(&mut record.submission);
+            self.apply_comment_overflow_policy(&mut record.submission.comment);
+            self.apply_rating_range_policy(
+                &record.submission.feedback_type,
+                record.submission.custom_type.as_deref(),
+                &mut record.submission.rating,
+            );
+            // Held to the same rules as live submissions: a disabled service takes no
+            // feedback, and a parent must be a stored feedback of the row's user (rows get
+            // new ids, so a parent can't be a row of the same file)
+            let validation = match self
+                .validate_service_enabled(&record.submission)
+                .and_then(|()| self.validate_feedback_submission(&record.submission))
+                .and_then(|()| self.validate_import_timestamp(record.created_at))
+            {
+                Ok(()) => {
+                    self.validate_parent(&record.user_id, record.submission.parent_id).await
+                }
+                Err(e) => Err(e),
+            };
+
+            match validation {
+                Ok(()) => {
+                    lines.push(line);
+                    records.push(record);
+                }
+                Err(AppError::ValidationError(error)) => {
+                    rejected.push(ImportRejection { line, error });
+                }
+                Err(e) => return Err(e),
+            }
+        }
+
+        // 2. In upsert mode a message keeps one feedback per user, so of the rows for the
+        // same message only the newest is written; the result then does not depend on
+        // which batch commits last
+        let keys: Vec<_> = records
+            .iter()
+            .map(|record| self.import_message_key(record))
+            .collect();
+        let mut newest: HashMap<&(String, String, String), usize> = HashMap::new();
+        for (position, key) in keys.iter().enumerate() {
+            if let Some(key) = key {
+                let kept = newest.entry(key).or_insert(position);
+                if records[position].created_at >= records[*kept].created_at {
+                    *kept = position;
+                }
+            }
+        }
+
+        let mut writes = Vec::with_capacity(records.len());
+        for (position, record) in records.into_iter().enumerate() {
+            match keys[position].as_ref().map(|key| newest[key]) {
+                Some(kept) if kept != position => rejected.push(ImportRejection {
+                    line: lines[position],
+                    error: format!(
+                        "Superseded by line {} for the same context.message_id",
+                        lines[kept]
+                    ),
+                }),
+                upsert => writes.push((lines[position], (record, upsert.is_some()))),
+            }
+        }
+
+        // 3. Write the remaining records in batches, each in its own transaction, with a
+        // bounded number of batches in flight
+        let repository = Arc::clone(&self.repository);
+        let progress = write_import_batches(
+            writes,
+            self.config.import_batch_size,
+            self.config.import_max_concurrency,
+            move |records| {
+                let repository = Arc::clone(&repository);
+                async move { repository.import(records).await }
+            },
+        )
+        .await;
+
+        // A row that replaced a stored feedback was already counted
+        for (feedback, created) in &progress.written {
+            if *created {
+                self.record_feedback_metrics(feedback);
+            }
+        }
+        let imported = progress.written.len();
+        let batch_count = progress.batches;
+        rejected.extend(progress.rejected);
+
+        rejected.sort_by_key(|rejection| rejection.line);
+
+        tracing::info!(
+            imported = imported,
+            rejected = rejected.len(),
+            batches = batch_count,
+            "Feedback import completed"
+        );
+
+        Ok(ImportResponse {
+            imported,
+            rejected,
+            batches: batch_count,
+        })
     }
 
     /// Get a specific feedback by ID
@@ -76,115 +621,1752 @@ impl FeedbackService {
             .ok_or_else(|| AppError::NotFound(format!("Feedback with id {} not found", id)))
     }
 
+    /// Get the follow-up feedbacks linked to a feedback
+    pub async fn get_children(&self, id: Uuid) -> Result<Vec<Feedback>> {
+        // Surface a 404 for unknown parents rather than an empty list
+        self.get_feedback(id).await?;
+
+        self.repository.get_children(id).await.map_err(Into::into)
+    }
+
+    /// Update a user's own feedback. Omitted fields keep their current value and the
+    /// merged result is re-validated with the same rules as creation.
+    pub async fn update_feedback(
+        &self,
+        id: Uuid,
+        user_id: &str,
+        update: FeedbackUpdate,
+    ) -> Result<Feedback> {
+        let existing = self.get_feedback(id).await?;
+
+        if existing.user_id != user_id {
+            return Err(AppError::Forbidden(
+                "You can only edit your own feedback".to_string(),
+            ));
+        }
+
+        self.ensure_within_edit_window(&existing)?;
+
+        let new_context = update.context.is_some();
+        let mut merged = FeedbackUpdate {
+            rating: update.rating.or(existing.rating),
+            thumbs_up: update.thumbs_up.or(existing.thumbs_up),
+            comment: update.comment.or(existing.comment),
+            context: update.context.or(existing.context),
+            reason: update.reason.or(existing.reason),
+        };
+        // A reason only describes a thumbs-down; it goes away once the thumb is flipped
+        if merged.thumbs_up != Some(false) {
+            merged.reason = None;
+        }
+        self.apply_comment_overflow_policy(&mut merged.comment);
+        self.apply_rating_range_policy(
+            &existing.feedback_type,
+            existing.custom_type.as_deref(),
+            &mut merged.rating,
+        );
+
+        self.validate_feedback_submission(&FeedbackSubmission {
+            service: existing.service.clone(),
+            feedback_type: existing.feedback_type,
+            rating: merged.rating,
+            thumbs_up: merged.thumbs_up,
+            comment: merged.comment.clone(),
+            context: merged.context.clone(),
+            parent_id: existing.parent_id,
+            language: existing.language,
+            reason: merged.reason.clone(),
+            custom_type: existing.custom_type,
+            on_behalf_of: None,
+            country: existing.country,
+        })?;
+
+        // A new context goes through the service's storage policy as on creation; the
+        // stored one already did
+        if new_context {
+            merged.context = self.reduce_stored_context(&existing.service, merged.context);
+        }
+
+        let feedback = self
+            .repository
+            .update(id, merged)
+            .await?
+            .ok_or_else(|| AppError::NotFound(format!("Feedback with id {} not found", id)))?;
+
+        tracing::info!(
+            feedback_id = %feedback.id,
+            user_id = %user_id,
+            "Feedback updated successfully"
+        );
+
+        Ok(feedback)
+    }
+
     /// Query feedbacks with validation
     pub async fn query_feedbacks(&self, query: FeedbackQuery) -> Result<Vec<Feedback>> {
+        self.query_feedbacks_with_timeout(query, None).await
+    }
+
+    /// Query feedbacks, optionally under a per-query statement timeout (bounded by
+    /// DB_TIMEOUT_OVERRIDE_MAX_MS) instead of the database default
+    pub async fn query_feedbacks_with_timeout(
+        &self,
+        mut query: FeedbackQuery,
+        statement_timeout_ms: Option<u64>,
+    ) -> Result<Vec<Feedback>> {
+        let _timer = crate::metrics::SERVICE_LATENCY
+            .with_label_values(&["query_feedbacks"])
+            .start_timer();
+
         // Validate query parameters
         query.validate()?;
+        query.text_search_config = Some(self.config.comment_text_search_config.clone());
+        query.limit = Some(query_limit(query.limit, query.sample, self.config.sample_size));
+        if let Some(key) = &query.context_key {
+            if !self.config.indexed_context_keys.contains(key) {
+                if self.config.context_filter_indexed_only {
+                    return Err(AppError::ValidationError(format!(
+                        "context_key '{}' is not indexed (INDEXED_CONTEXT_KEYS)",
+                        key
+                    )));
+                }
+                tracing::warn!(context_key = %key, "Filtering on an unindexed context key");
+            }
+        }
 
-        self.repository.query(query).await.map_err(Into::into)
+        match statement_timeout_ms {
+            Some(timeout_ms) => {
+                check_statement_timeout(timeout_ms, self.config.db_timeout_override_max_ms)?;
+                self.repository
+                    .query_with_timeout(query, timeout_ms)
+                    .await
+                    .map_err(Into::into)
+            }
+            None => self.repository.query(query).await.map_err(Into::into),
+        }
     }
 
-    /// Get aggregated statistics for a service
-    pub async fn get_stats(&self, service: Option<&str>) -> Result<Vec<FeedbackStats>> {
-        self.repository.get_stats(service).await.map_err(Into::into)
+    /// One page of a user's feedback, newest first. The user filter is forced from the
+    /// path whatever the query says; pages default to `DEFAULT_USER_PAGE_SIZE` entries.
+    pub async fn get_user_feedbacks(
+        &self,
+        user_id: &str,
+        mut query: FeedbackQuery,
+    ) -> Result<Vec<Feedback>> {
+        query.user_id = Some(user_id.to_string());
+        query.limit = Some(query.limit.unwrap_or(DEFAULT_USER_PAGE_SIZE));
+
+        self.query_feedbacks(query).await
     }
 
-    /// Get statistics for a specific service with additional validation
-    pub async fn get_service_stats(&self, service: &str) -> Result<FeedbackStats> {
-        // Validate service name is not empty
-        if service.trim().is_empty() {
-            return Err(AppError::ValidationError(
-                "Service name cannot be empty".to_string(),
-            ));
+    /// Totals over everything a user has submitted
+    pub async fn get_user_summary(&self, user_id: &str) -> Result<UserFeedbackSummary> {
+        self.repository
+            .get_user_summary(user_id)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Get aggregated statistics for a service, served from the stats cache while fresh
+    pub async fn get_stats(&self, service: Option<&str>) -> Result<Vec<FeedbackStats>> {
+        let _timer = crate::metrics::SERVICE_LATENCY
+            .with_label_values(&["get_stats"])
+            .start_timer();
+
+        if let Some(stats) = self.stats_cache.get(service) {
+            return Ok(stats);
         }
 
-        let stats = self.repository.get_stats(Some(service)).await?;
+        self.load_stats(service).await
+    }
 
-        stats
-            .into_iter()
-            .next()
-            .ok_or_else(|| AppError::NotFound(format!("No statistics found for service '{}'", service)))
+    /// Get statistics with services rolled up under their `SERVICE_GROUPS` group name.
+    /// `group` filters on a group (or an ungrouped service name). Not cached.
+    pub async fn get_grouped_stats(&self, group: Option<&str>) -> Result<Vec<FeedbackStats>> {
+        let _timer = crate::metrics::SERVICE_LATENCY
+            .with_label_values(&["get_grouped_stats"])
+            .start_timer();
+
+        let stats = self
+            .repository
+            .get_grouped_stats(&self.config.service_groups, group)
+            .await?;
+        Ok(self.with_satisfaction_scores(stats))
     }
 
-    // Private helper methods for business logic
+    /// Get statistics broken down by service and language. Not cached.
+    pub async fn get_language_stats(&self, service: Option<&str>) -> Result<Vec<LanguageStats>> {
+        self.repository
+            .get_language_stats(service)
+            .await
+            .map_err(Into::into)
+    }
 
-    /// Validate feedback submission according to business rules
-    fn validate_feedback_submission(&self, submission: &FeedbackSubmission) -> Result<()> {
-        // Standard validation
-        submission.validate()?;
+    /// Count feedback per service and custom type. Not cached.
+    pub async fn get_custom_type_stats(
+        &self,
+        service: Option<&str>,
+    ) -> Result<Vec<CustomTypeStats>> {
+        self.repository
+            .get_custom_type_stats(service)
+            .await
+            .map_err(Into::into)
+    }
 
-        // Additional business rules
-        // Rule: Service name should not be empty or just whitespace
-        if submission.service.trim().is_empty() {
-            return Err(AppError::ValidationError(
-                "Service name cannot be empty".to_string(),
+    /// Get statistics broken down by service and country. Not cached.
+    pub async fn get_country_stats(&self, service: Option<&str>) -> Result<Vec<CountryStats>> {
+        self.repository
+            .get_country_stats(service)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Count thumbs-down feedback per service and reason. Not cached.
+    pub async fn get_reason_stats(&self, service: Option<&str>) -> Result<Vec<ReasonStats>> {
+        self.repository
+            .get_reason_stats(service)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// When each service got its first feedback and how much in the following 24 hours,
+    /// for launch retrospectives. Not cached.
+    pub async fn get_first_feedback_stats(
+        &self,
+        service: Option<&str>,
+    ) -> Result<Vec<FirstFeedbackStats>> {
+        self.repository
+            .get_first_feedback_stats(service)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Refresh the stats view. Only one refresh runs at a time across instances; a
+    /// concurrent call gets a conflict instead of running the expensive refresh twice.
+    pub async fn refresh_stats(&self) -> Result<StatsRefreshStatus> {
+        let _timer = crate::metrics::SERVICE_LATENCY
+            .with_label_values(&["refresh_stats"])
+            .start_timer();
+
+        if !self.repository.refresh_stats().await? {
+            return Err(AppError::Conflict(
+                "A stats refresh is already in progress".to_string(),
             ));
         }
 
-        // Rule: If rating is provided, it should match the feedback type
-        if submission.rating.is_some() {
-            use crate::models::FeedbackType;
-            match submission.feedback_type {
-                FeedbackType::Rating | FeedbackType::Nps => {
-                    // Valid - these types can have ratings
-                }
-                _ => {
-                    return Err(AppError::ValidationError(
-                        format!("Rating is not applicable for feedback type {:?}", submission.feedback_type),
-                    ));
-                }
-            }
+        let refreshed_at = chrono::Utc::now();
+        *self
+            .last_stats_refresh
+            .write()
+            .unwrap_or_else(|e| e.into_inner()) = Some(refreshed_at);
+        crate::metrics::STATS_LAST_REFRESH.set(refreshed_at.timestamp());
+        tracing::info!(refreshed_at = %refreshed_at, "Stats view refreshed");
+
+        Ok(StatsRefreshStatus {
+            last_refreshed_at: Some(refreshed_at),
+        })
+    }
+
+    /// When this instance last refreshed the stats view successfully
+    pub fn stats_refresh_status(&self) -> StatsRefreshStatus {
+        StatsRefreshStatus {
+            last_refreshed_at: *self
+                .last_stats_refresh
+                .read()
+                .unwrap_or_else(|e| e.into_inner()),
         }
+    }
 
-        // Rule: Thumbs up/down should only be present for Thumbs feedback type
-        if submission.thumbs_up.is_some() {
-            use crate::models::FeedbackType;
-            if !matches!(submission.feedback_type, FeedbackType::Thumbs) {
-                return Err(AppError::ValidationError(
-                    format!("Thumbs up/down is not applicable for feedback type {:?}", submission.feedback_type),
-                ));
-            }
+    /// Purge feedback older than `RETENTION_DAYS`, one batch at a time with a short pause
+    /// between batches, until nothing older than the cutoff remains. Returns the number of
+    /// rows deleted; does nothing when retention is disabled.
+    pub async fn purge_expired_feedback(&self) -> Result<u64> {
+        if self.config.retention_days == 0 {
+            return Ok(0);
         }
 
-        Ok(())
-    }
+        let cutoff =
+            chrono::Utc::now() - chrono::Duration::days(self.config.retention_days as i64);
+        let batch_size = self.config.retention_purge_batch_size;
+        let pause = Duration::from_millis(self.config.retention_purge_batch_pause_ms);
+        let mut total_deleted = 0;
+        let mut batch = 0;
 
-    /// Record metrics for a feedback submission
-    fn record_feedback_metrics(&self, submission: &FeedbackSubmission) {
-        crate::metrics::record_feedback(
-            &submission.service,
-            &format!("{:?}", submission.feedback_type),
-            submission.rating,
-            submission.thumbs_up,
-            submission.comment.is_some(),
+        loop {
+            let deleted = self.repository.delete_before(cutoff, batch_size).await?;
+            if deleted == 0 {
+                break;
+            }
+
+            batch += 1;
+            total_deleted += deleted;
+            tracing::info!(
+                batch = batch,
+                deleted = deleted,
+                total_deleted = total_deleted,
+                "Retention purge batch completed"
+            );
+
+            if (deleted as i64) < batch_size {
+                break;
+            }
+            tokio::time::sleep(pause).await;
+        }
+
+        tracing::info!(
+            cutoff = %cutoff,
+            total_deleted = total_deleted,
+            batches = batch,
+            "Retention purge run completed"
         );
+
+        Ok(total_deleted)
     }
 
-    /// Trigger webhook notifications asynchronously
-    async fn trigger_webhook_notifications(&self, feedback: Feedback) {
-        if !self.config.webhook_urls.is_empty() {
-            let webhook_urls = self.config.webhook_urls.clone();
-            tokio::spawn(async move {
-                let payload = WebhookPayload {
-                    event: "feedback.created".to_string(),
-                    feedback,
-                };
-                if let Err(e) = send_webhook(&webhook_urls, payload).await {
-                    tracing::error!("Failed to send webhooks: {}", e);
-                }
-            });
+    /// Pre-compute stats for all services and for each of `services`, so the first
+    /// dashboard loads after a deploy hit a warm cache. Failures are logged, not fatal.
+    pub async fn warm_stats_cache(&self, services: &[String]) {
+        if !self.stats_cache.is_enabled() {
+            tracing::warn!("Stats warm-up skipped: STATS_CACHE_TTL_SECS is 0");
+            return;
+        }
+
+        let started = Instant::now();
+        let targets = std::iter::once(None).chain(services.iter().map(|s| Some(s.as_str())));
+        let mut warmed = 0;
+
+        for service in targets {
+            match self.load_stats(service).await {
+                Ok(_) => warmed += 1,
+                Err(e) => tracing::warn!(
+                    service = service.unwrap_or("*"),
+                    error = ?e,
+                    "Failed to warm stats cache"
+                ),
+            }
         }
+
+        tracing::info!(
+            warmed = warmed,
+            elapsed_ms = started.elapsed().as_millis() as u64,
+            "Stats cache warmed"
+        );
     }
-}
 
-#[cfg(test)]
-mod tests {
-    // Note: These are unit tests that would require mocking the database
-    // For now, we'll add integration tests separately
+    /// Get daily statistics, bucketed on local days in the requested timezone (UTC by default)
+    pub async fn get_timeseries(&self, query: TimeseriesQuery) -> Result<Vec<TimeseriesPoint>> {
+        // Validate query parameters (including the timezone name)
+        query.validate()?;
 
-    #[test]
+        let timezone = query.timezone.clone().unwrap_or_else(|| "UTC".to_string());
+
+        self.repository
+            .get_timeseries(query, &timezone)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Daily feedback counts over the trailing `days` UTC days (today included), one point
+    /// per day in order
+    pub async fn get_sparkline(&self, query: SparklineQuery) -> Result<Vec<SparklinePoint>> {
+        query.validate()?;
+
+        let days = query.days.unwrap_or(DEFAULT_SPARKLINE_DAYS);
+        let today = chrono::Utc::now().date_naive();
+        let start = today - chrono::Duration::days(days - 1);
+
+        let timeseries_query = TimeseriesQuery {
+            service: query.service,
+            from_date: Some(start.and_time(chrono::NaiveTime::MIN).and_utc()),
+            to_date: None,
+            timezone: None,
+        };
+        let points = self
+            .repository
+            .get_timeseries(timeseries_query, "UTC")
+            .await?;
+
+        Ok(fill_sparkline(&points, start, days))
+    }
+
+    /// Stats per service over the current calendar day in `tz` (UTC by default). Not cached.
+    pub async fn get_today_stats(&self, query: TodayStatsQuery) -> Result<Vec<FeedbackStats>> {
+        query.validate()?;
+
+        let tz = query
+            .tz
+            .as_deref()
+            .and_then(|tz| tz.parse().ok())
+            .unwrap_or(chrono_tz::UTC);
+        let (from, to) = local_day_bounds(chrono::Utc::now(), tz);
+        let stats = self
+            .repository
+            .get_range_stats(query.service.as_deref(), from, to)
+            .await?;
+        Ok(self.with_satisfaction_scores(stats))
+    }
+
+    /// Thumbs-up ratio and average rating of a service over the last
+    /// `HEALTH_SIGNAL_WINDOW_HOURS`, classified against the configured thresholds
+    pub async fn get_health_signal(&self, query: HealthSignalQuery) -> Result<HealthSignal> {
+        if query.service.trim().is_empty() {
+            return Err(AppError::ValidationError("service is required".to_string()));
+        }
+
+        let window_hours = self.config.health_signal_window_hours;
+        let since = chrono::Utc::now() - chrono::Duration::hours(window_hours);
+        let stats = self
+            .repository
+            .get_window_stats(query.service.trim(), since)
+            .await?;
+
+        let status = health_status(
+            stats.thumbs_up_ratio,
+            self.config.health_thumbs_up_ratio_warning,
+            self.config.health_thumbs_up_ratio_critical,
+        )
+        .max(health_status(
+            stats.rating_avg,
+            self.config.health_rating_warning,
+            self.config.health_rating_critical,
+        ));
+
+        Ok(HealthSignal {
+            service: stats.service,
+            window_hours,
+            total_count: stats.total_count,
+            thumbs_up_ratio: stats.thumbs_up_ratio,
+            rating_avg: stats.rating_avg,
+            status,
+        })
+    }
+
+    /// Most frequent terms in comments, over the latest `WORD_CLOUD_SCAN_LIMIT` matching
+    /// feedbacks, tokenized with `COMMENT_TEXT_SEARCH_CONFIG`
+    pub async fn get_word_cloud(&self, query: WordCloudQuery) -> Result<Vec<WordFrequency>> {
+        query.validate()?;
+
+        let limit = query.limit.unwrap_or(DEFAULT_WORD_CLOUD_LIMIT);
+        self.repository
+            .get_word_frequencies(
+                &query,
+                &self.config.comment_text_search_config,
+                WORD_CLOUD_SCAN_LIMIT,
+                limit,
+            )
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Newest non-empty comments, for dashboards. Not cached.
+    pub async fn get_recent_comments(
+        &self,
+        query: RecentCommentsQuery,
+    ) -> Result<Vec<RecentComment>> {
+        query.validate()?;
+
+        let limit = query.limit.unwrap_or(DEFAULT_RECENT_COMMENTS_LIMIT);
+        self.repository
+            .get_recent_comments(query.service.as_deref(), limit)
+            .await
+            .map_err(Into::into)
+    }
+
+    /// Collect everything held about a user into a single bundle
+    pub async fn export_user_data(&self, user_id: &str) -> Result<UserDataExport> {
+        let feedbacks = self.repository.get_by_user(user_id).await?;
+
+        tracing::info!(
+            user_id = %user_id,
+            feedback_count = feedbacks.len(),
+            "User data exported"
+        );
+
+        Ok(UserDataExport {
+            user_id: user_id.to_string(),
+            exported_at: chrono::Utc::now(),
+            feedback_count: feedbacks.len(),
+            feedbacks,
+        })
+    }
+
+    /// Get statistics for a specific service with additional validation
+    pub async fn get_service_stats(&self, service: &str) -> Result<FeedbackStats> {
+        // Validate service name is not empty
+        if service.trim().is_empty() {
+            return Err(AppError::ValidationError(
+                "Service name cannot be empty".to_string(),
+            ));
+        }
+
+        let stats = self.repository.get_stats(Some(service)).await?;
+
+        self.with_satisfaction_scores(stats)
+            .into_iter()
+            .next()
+            .ok_or_else(|| AppError::NotFound(format!("No statistics found for service '{}'", service)))
+    }
+
+    // Private helper methods for business logic
+
+    /// Compute stats from the database and cache them
+    async fn load_stats(&self, service: Option<&str>) -> Result<Vec<FeedbackStats>> {
+        let stats = self.with_satisfaction_scores(self.repository.get_stats(service).await?);
+        if self.stats_cache.is_enabled() {
+            self.stats_cache.insert(service, stats.clone());
+        }
+        Ok(stats)
+    }
+
+    /// Fill in each entry's satisfaction score with the configured thumbs scores
+    fn with_satisfaction_scores(&self, mut stats: Vec<FeedbackStats>) -> Vec<FeedbackStats> {
+        for entry in &mut stats {
+            entry.satisfaction_score = satisfaction_score(
+                entry,
+                self.config.thumbs_up_score,
+                self.config.thumbs_down_score,
+            );
+        }
+        stats
+    }
+
+    /// Apply the submission policies (default service, comment overflow, rating range) and
+    /// check the result against validation and business rules. A dry run skips the latency
+    /// metric.
+    async fn prepare_submission(
+        &self,
+        user_id: &str,
+        user_email: Option<&str>,
+        submission: &mut FeedbackSubmission,
+        dry_run: bool,
+    ) -> Result<()> {
+        self.validate_user_email(user_email)?;
+        self.apply_default_service(submission);
+        self.apply_comment_overflow_policy(&mut submission.comment);
+        self.apply_rating_range_policy(
+            &submission.feedback_type,
+            submission.custom_type.as_deref(),
+            &mut submission.rating,
+        );
+        self.validate_service_enabled(submission)?;
+        if dry_run {
+            self.check_feedback_submission(submission)?;
+        } else {
+            self.validate_feedback_submission(submission)?;
+        }
+        self.validate_parent(user_id, submission.parent_id).await
+    }
+
+    /// Validate feedback submission according to business rules
+    fn validate_feedback_submission(&self, submission: &FeedbackSubmission) -> Result<()> {
+        let _timer = crate::metrics::SERVICE_LATENCY
+            .with_label_values(&["validate_feedback_submission"])
+            .start_timer();
+        self.check_feedback_submission(submission)
+    }
+
+    /// The business rules behind `validate_feedback_submission`, without the latency metric
+    fn check_feedback_submission(&self, submission: &FeedbackSubmission) -> Result<()> {
+        // Standard validation
+        submission.validate()?;
+
+        // Additional business rules
+        // Rule: Service name should not be empty or just whitespace
+        if submission.service.trim().is_empty() {
+            return Err(reject_submission(
+                submission,
+                "service",
+                "Service name cannot be empty".to_string(),
+            ));
+        }
+
+        // Rule: If rating is provided, it should match the feedback type
+        if submission.rating.is_some() {
+            use crate::models::FeedbackType;
+            match submission.feedback_type {
+                FeedbackType::Rating | FeedbackType::Nps | FeedbackType::Custom => {
+                    // Valid - these types can have ratings (custom ones are checked below)
+                }
+                _ => {
+                    return Err(reject_submission(
+                        submission,
+                        "rating",
+                        format!("Rating is not applicable for feedback type {:?}", submission.feedback_type),
+                    ));
+                }
+            }
+        }
+
+        // Rule: Thumbs up/down should only be present for Thumbs feedback type
+        if submission.thumbs_up.is_some() {
+            use crate::models::FeedbackType;
+            if !matches!(submission.feedback_type, FeedbackType::Thumbs) {
+                return Err(reject_submission(
+                    submission,
+                    "thumbs_up",
+                    format!("Thumbs up/down is not applicable for feedback type {:?}", submission.feedback_type),
+                ));
+            }
+        }
+
+        // Rule: Thumbs-down reasons come from the service's taxonomy
+        self.validate_thumbs_down_reason(submission)?;
+
+        // Rule: Custom feedback follows the rules of its configured type
+        self.validate_custom_type(submission)?;
+
+        // Rule: Context must not have more top-level keys than configured
+        if let Some(JsonValue::Object(context)) = &submission.context {
+            let max_keys = self.config.context_max_top_level_keys;
+            if context.len() > max_keys {
+                return Err(reject_submission(
+                    submission,
+                    "context",
+                    format!(
+                        "Context has too many top-level keys ({}, max {})",
+                        context.len(),
+                        max_keys
+                    ),
+                ));
+            }
+        }
+
+        // Rule: Context must match the service's registered schema, if any
+        self.validate_context_schema(submission)?;
+
+        Ok(())
+    }
+
+    /// A reason may only accompany a thumbs-down, and must be one of the service's configured
+    /// reasons; it is required when REQUIRE_THUMBS_DOWN_REASON is set and the service has some
+    fn validate_thumbs_down_reason(&self, submission: &FeedbackSubmission) -> Result<()> {
+        let allowed = self
+            .config
+            .thumbs_down_reasons
+            .get(&submission.service)
+            .map(Vec::as_slice);
+
+        let required = self.config.require_thumbs_down_reason;
+        match thumbs_down_reason_error(submission, allowed, required) {
+            Some(message) => Err(reject_submission(submission, "reason", message)),
+            None => Ok(()),
+        }
+    }
+
+    /// Custom feedback must name a type from CUSTOM_FEEDBACK_TYPES and carry the rating and
+    /// comment that type requires
+    fn validate_custom_type(&self, submission: &FeedbackSubmission) -> Result<()> {
+        let Some(name) = submission.custom_type.as_deref() else {
+            return Ok(());
+        };
+        let Some(custom_type) = self.config.custom_feedback_types.get(name) else {
+            return Err(reject_submission(
+                submission,
+                "custom_type",
+                format!("Unknown custom feedback type '{}'", sanitize_for_log(name, 64)),
+            ));
+        };
+
+        match (&custom_type.rating_range, submission.rating) {
+            (Some(range), Some(rating)) if !range.contains(&rating) => {
+                return Err(reject_submission(
+                    submission,
+                    "rating",
+                    format!(
+                        "Rating must be between {} and {} for custom type '{}'",
+                        range.start(),
+                        range.end(),
+                        name
+                    ),
+                ));
+            }
+            (Some(_), None) => {
+                return Err(reject_submission(
+                    submission,
+                    "rating",
+                    format!("Rating is required for custom type '{}'", name),
+                ));
+            }
+            (None, Some(_)) => {
+                return Err(reject_submission(
+                    submission,
+                    "rating",
+                    format!("Rating is not applicable for custom type '{}'", name),
+                ));
+            }
+            _ => {}
+        }
+
+        if custom_type.requires_comment
+            && submission.comment.as_deref().is_none_or(|c| c.trim().is_empty())
+        {
+            return Err(reject_submission(
+                submission,
+                "comment",
+                format!("Comment is required for custom type '{}'", name),
+            ));
+        }
+
+        Ok(())
+    }
+
+    /// Built-in feedback types followed by the deployment's custom types
+    pub fn feedback_type_schemas(&self) -> Vec<FeedbackTypeSchema> {
+        let mut schemas = crate::validation::feedback_type_schemas();
+        let mut custom_types: Vec<_> = self.config.custom_feedback_types.iter().collect();
+        custom_types.sort_by(|a, b| a.0.cmp(b.0));
+
+        for (name, custom_type) in custom_types {
+            let mut required_fields = vec!["service".to_string(), "custom_type".to_string()];
+            if custom_type.rating_range.is_some() {
+                required_fields.push("rating".to_string());
+            }
+            if custom_type.requires_comment {
+                required_fields.push("comment".to_string());
+            }
+            schemas.push(FeedbackTypeSchema {
+                feedback_type: FeedbackType::Custom,
+                custom_type: Some(name.clone()),
+                required_fields,
+                rating_range: custom_type.rating_range.as_ref().map(|r| RatingRange {
+                    min: *r.start(),
+                    max: *r.end(),
+                }),
+                max_comment_length: MAX_COMMENT_LENGTH,
+            });
+        }
+
+        schemas
+    }
+
+    /// Check `context` against the JSON Schema registered for the service; a missing
+    /// context is checked as `null`. Every violation is reported with its location.
+    fn validate_context_schema(&self, submission: &FeedbackSubmission) -> Result<()> {
+        let Some(validator) = self.context_validators.get(&submission.service) else {
+            return Ok(());
+        };
+
+        let context = submission.context.clone().unwrap_or(JsonValue::Null);
+        let violations = schema_violations(validator, &context);
+        if violations.is_empty() {
+            return Ok(());
+        }
+
+        Err(reject_submission(
+            submission,
+            "context",
+            format!(
+                "Context does not match the schema for service '{}': {}",
+                submission.service,
+                violations.join("; ")
+            ),
+        ))
+    }
+
+    /// Whether the submission belongs to an upsert-mode service and carries a
+    /// `context.message_id` to deduplicate on (checked after the context policy, since
+    /// the stored context is what the unique index sees)
+    fn upserts_by_message_id(&self, submission: &FeedbackSubmission) -> bool {
+        self.config
+            .message_id_upsert_services
+            .contains(&submission.service)
+            && submission
+                .context
+                .as_ref()
+                .and_then(|context| context.get("message_id"))
+                .is_some_and(|id| !id.is_null())
+    }
+
+    /// The user, service and `context.message_id` an imported row upserts on, when its
+    /// service is in upsert mode (`None` for a plain insert)
+    fn import_message_key(&self, record: &ImportRecord) -> Option<(String, String, String)> {
+        if !self.upserts_by_message_id(&record.submission) {
+            return None;
+        }
+        // Compared as the unique index does, on the text of `context->>'message_id'`
+        let message_id = match record.submission.context.as_ref()?.get("message_id")? {
+            JsonValue::String(id) => id.clone(),
+            other => other.to_string(),
+        };
+        Some((
+            record.user_id.clone(),
+            record.submission.service.clone(),
+            message_id,
+        ))
+    }
+
+    /// With SERVICE_FROM_CLIENT, attribute the submission to the calling client's service
+    /// whatever it claims, so one client cannot submit feedback for another's service
+    pub fn apply_client_service(
+        &self,
+        client_id: Option<&str>,
+        submission: &mut FeedbackSubmission,
+    ) -> Result<()> {
+        if !self.config.service_from_client {
+            return Ok(());
+        }
+
+        let client_id = client_id.ok_or_else(|| {
+            AppError::Forbidden("Token does not identify the calling client".to_string())
+        })?;
+        submission.service = self
+            .config
+            .client_services
+            .get(client_id)
+            .cloned()
+            .unwrap_or_else(|| client_id.to_string());
+
+        Ok(())
+    }
+
+    /// Fill in `DEFAULT_SERVICE` for submissions sent without a service; when unset the
+    /// empty service is left for validation to reject
+    fn apply_default_service(&self, submission: &mut FeedbackSubmission) {
+        if let Some(default_service) = &self.config.default_service {
+            if submission.service.trim().is_empty() {
+                submission.service = default_service.clone();
+            }
+        }
+    }
+
+    /// Under `COMMENT_OVERFLOW=truncate`, shorten over-long comments instead of letting
+    /// validation reject them
+    fn apply_comment_overflow_policy(&self, comment: &mut Option<String>) {
+        if self.config.comment_overflow != CommentOverflow::Truncate {
+            return;
+        }
+
+        if let Some(text) = comment.as_mut() {
+            if text.len() > MAX_COMMENT_LENGTH {
+                tracing::warn!(
+                    original_length = text.len(),
+                    max_length = MAX_COMMENT_LENGTH,
+                    "Truncating oversized comment"
+                );
+                *text = truncate_comment(text, MAX_COMMENT_LENGTH);
+            }
+        }
+    }
+
+    /// Under `RATING_OUT_OF_RANGE=clamp`, move out-of-range ratings and NPS scores (and
+    /// ratings of custom types with a range) to the nearest valid value instead of letting
+    /// validation reject them
+    fn apply_rating_range_policy(
+        &self,
+        feedback_type: &FeedbackType,
+        custom_type: Option<&str>,
+        rating: &mut Option<i32>,
+    ) {
+        if self.config.rating_out_of_range != RatingOutOfRange::Clamp {
+            return;
+        }
+
+        let custom_range = custom_type
+            .filter(|_| matches!(feedback_type, FeedbackType::Custom))
+            .and_then(|name| self.config.custom_feedback_types.get(name))
+            .and_then(|custom| custom.rating_range.clone());
+
+        if let Some(value) = rating.as_mut() {
+            let clamped = match &custom_range {
+                Some(range) => (*value).clamp(*range.start(), *range.end()),
+                None => clamp_rating(feedback_type, *value),
+            };
+            if clamped != *value {
+                tracing::warn!(
+                    feedback_type = ?feedback_type,
+                    original = *value,
+                    clamped = clamped,
+                    "Clamping out-of-range rating"
+                );
+                *value = clamped;
+            }
+        }
+    }
+
+    /// Reject new feedback for services listed in DISABLED_SERVICES; their existing
+    /// feedback stays readable
+    fn validate_service_enabled(&self, submission: &FeedbackSubmission) -> Result<()> {
+        if self.config.disabled_services.contains(submission.service.trim()) {
+            return Err(reject_submission(
+                submission,
+                "service",
+                format!(
+                    "Service '{}' no longer accepts feedback",
+                    submission.service.trim()
+                ),
+            ));
+        }
+        Ok(())
+    }
+
+    /// Reject submitters whose email is at a domain from DISPOSABLE_EMAIL_DOMAINS_FILE
+    fn validate_user_email(&self, user_email: Option<&str>) -> Result<()> {
+        let domains = &self.config.disposable_email_domains;
+        if user_email.is_some_and(|email| is_disposable_email(email, domains)) {
+            tracing::debug!("Rejected feedback from a disposable email domain");
+            return Err(AppError::ValidationError(
+                "Feedback from disposable email addresses is not accepted".to_string(),
+            ));
+        }
+        Ok(())
+    }
+
+    /// A parent, when given, must exist and belong to the submitting user
+    async fn validate_parent(&self, user_id: &str, parent_id: Option<Uuid>) -> Result<()> {
+        let Some(parent_id) = parent_id else {
+            return Ok(());
+        };
+
+        match self.repository.get_by_id(parent_id).await? {
+            Some(parent) if parent.user_id == user_id => Ok(()),
+            _ => Err(AppError::ValidationError(format!(
+                "parent_id {} does not reference one of your feedbacks",
+                parent_id
+            ))),
+        }
+    }
+
+    /// Reduce stored context for services with a sampling rate or key cap configured
+    fn apply_context_storage_policy(&self, submission: &mut FeedbackSubmission) {
+        let context = submission.context.take();
+        submission.context = self.reduce_stored_context(&submission.service, context);
+    }
+
+    /// `context` as stored for `service`: sampled and capped per its storage policy
+    fn reduce_stored_context(
+        &self,
+        service: &str,
+        context: Option<JsonValue>,
+    ) -> Option<JsonValue> {
+        let sample_rate = self.config.context_sample_rates.get(service).copied();
+        let max_keys = self.config.context_max_keys.get(service).copied();
+        if sample_rate.is_none() && max_keys.is_none() {
+            return context;
+        }
+
+        let roll = rand::thread_rng().gen::<f64>();
+        reduce_context(context, sample_rate, max_keys, roll)
+    }
+
+    /// Imported timestamps must not be in the future (beyond the allowed clock skew) nor
+    /// before the configured earliest date
+    fn validate_import_timestamp(&self, created_at: chrono::DateTime<chrono::Utc>) -> Result<()> {
+        match import_timestamp_error(
+            created_at,
+            chrono::Utc::now(),
+            self.config.import_max_future_skew_secs,
+            self.config.import_earliest_date,
+        ) {
+            Some(message) => Err(AppError::ValidationError(message)),
+            None => Ok(()),
+        }
+    }
+
+    /// Reject edits once the configured edit window has elapsed (0 = edits always allowed)
+    fn ensure_within_edit_window(&self, feedback: &Feedback) -> Result<()> {
+        let window_secs = self.config.feedback_edit_window_secs;
+        if window_secs == 0 {
+            return Ok(());
+        }
+
+        let age = chrono::Utc::now() - feedback.created_at;
+        if age.num_seconds() > window_secs as i64 {
+            return Err(AppError::Forbidden(format!(
+                "Feedback can no longer be edited (edits are allowed within {} seconds of submission)",
+                window_secs
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Record metrics for a stored feedback
+    fn record_feedback_metrics(&self, feedback: &Feedback) {
+        crate::metrics::record_feedback(
+            &feedback.service,
+            &format!("{:?}", feedback.feedback_type),
+            feedback.rating,
+            feedback.thumbs_up,
+            feedback.comment.is_some(),
+            feedback.created_at,
+            self.metrics_timezone,
+        );
+    }
+
+    /// Trigger webhook notifications asynchronously
+    async fn trigger_webhook_notifications(&self, feedback: Feedback) {
+        let webhook_urls = self
+            .config
+            .service_webhook_urls
+            .get(&feedback.service)
+            .unwrap_or(&self.config.webhook_urls);
+        if !webhook_urls.is_empty() {
+            if self.webhooks_paused.load(Ordering::SeqCst) {
+                let skipped = self.webhooks_skipped.fetch_add(1, Ordering::SeqCst) + 1;
+                crate::metrics::WEBHOOK_DELIVERIES
+                    .with_label_values(&["skipped"])
+                    .inc_by(webhook_urls.len() as f64);
+                tracing::info!(
+                    feedback_id = %feedback.id,
+                    skipped = skipped,
+                    "Webhook delivery paused, skipping"
+                );
+                return;
+            }
+
+            let webhook_urls = webhook_urls.clone();
+            let allow_insecure = self.config.webhook_allow_insecure;
+            let format = self.config.webhook_format;
+            let headers = self.webhook_headers.clone();
+            let permits = Arc::clone(&self.webhook_permits);
+            // Lag counts from here, so waiting for a delivery slot is included
+            let enqueued_at = Instant::now();
+            tokio::spawn(async move {
+                // Wait for a free slot; the semaphore is never closed
+                let Ok(_permit) = permits.acquire_owned().await else {
+                    return;
+                };
+                let payload = WebhookPayload {
+                    event: "feedback.created".to_string(),
+                    feedback,
+                };
+                let feedback_id = payload.feedback.id;
+                let delivery = send_webhook(
+                    &webhook_urls,
+                    payload,
+                    format,
+                    headers,
+                    allow_insecure,
+                    enqueued_at,
+                );
+                match delivery.await {
+                    Ok(report) if report.failed > 0 => tracing::warn!(
+                        feedback_id = %feedback_id,
+                        succeeded = report.succeeded,
+                        failed = report.failed,
+                        "Webhook fan-out partially failed"
+                    ),
+                    Ok(report) => tracing::info!(
+                        feedback_id = %feedback_id,
+                        succeeded = report.succeeded,
+                        failed = report.failed,
+                        "Webhook fan-out completed"
+                    ),
+                    Err(e) => tracing::error!("Failed to send webhooks: {}", e),
+                }
+            });
+        }
+    }
+}
+
+/// ISO country code of `ip` in a GeoIP country database, if it is listed
+fn lookup_country(geoip: &maxminddb::Reader<Vec<u8>>, ip: IpAddr) -> Option<String> {
+    let country: maxminddb::geoip2::Country = geoip.lookup(ip).ok()?;
+    country.country?.iso_code.map(str::to_string)
+}
+
+/// What an import wrote, the lines it did not, and how many batches it started
+struct ImportProgress<T> {
+    written: Vec<T>,
+    rejected: Vec<ImportRejection>,
+    batches: usize,
+}
+
+/// Write `(line, record)` rows through `import` in batches of `batch_size`, each in its own
+/// transaction, with at most `max_concurrency` batches in flight. A batch in which the
+/// database refuses a row is reported line by line and the import goes on; any other
+/// failure stops it: no batch is started after it, the ones in flight are awaited, and
+/// every line not written is reported.
+async fn write_import_batches<R, T, F, Fut>(
+    rows: Vec<(usize, R)>,
+    batch_size: usize,
+    max_concurrency: usize,
+    import: F,
+) -> ImportProgress<T>
+where
+    T: Send + 'static,
+    F: Fn(Vec<R>) -> Fut,
+    Fut: Future<Output = anyhow::Result<Vec<T>>> + Send + 'static,
+{
+    let mut progress = ImportProgress {
+        written: Vec::new(),
+        rejected: Vec::new(),
+        batches: 0,
+    };
+    let batch_count = rows.len().div_ceil(batch_size);
+    let mut batches = JoinSet::new();
+    let mut batch_lines = HashMap::new();
+    let mut pending = rows.into_iter().peekable();
+    let mut stopped = false;
+    let mut completed = 0;
+
+    loop {
+        while !stopped && batches.len() < max_concurrency && pending.peek().is_some() {
+            let (lines, records): (Vec<usize>, Vec<R>) =
+                pending.by_ref().take(batch_size).unzip();
+            let handle = batches.spawn(import(records));
+            batch_lines.insert(handle.id(), lines);
+            progress.batches += 1;
+        }
+
+        let Some(joined) = batches.join_next_with_id().await else {
+            break;
+        };
+        completed += 1;
+        let (lines, result) = match joined {
+            Ok((id, result)) => (batch_lines.remove(&id).unwrap_or_default(), result),
+            Err(e) => (
+                batch_lines.remove(&e.id()).unwrap_or_default(),
+                Err(anyhow::Error::new(e)),
+            ),
+        };
+
+        match result {
+            Ok(rows) => progress.written.extend(rows),
+            Err(e) => match e.downcast_ref::<BatchInsertError>() {
+                Some(batch_error) => {
+                    // Only this batch was rolled back; point the caller at the row the
+                    // database refused and report the rest of the batch as not imported
+                    let failed_line = lines[batch_error.position];
+                    tracing::error!(
+                        line = failed_line,
+                        error = %batch_error.message,
+                        "Import batch rolled back after database error"
+                    );
+                    for line in lines {
+                        let error = if line == failed_line {
+                            "Rejected by the database".to_string()
+                        } else {
+                            format!(
+                                "Not imported: line {} of the same batch was rejected",
+                                failed_line
+                            )
+                        };
+                        progress.rejected.push(ImportRejection { line, error });
+                    }
+                }
+                None => {
+                    // Not about a row of this batch: batches already written stay
+                    // imported, the ones in flight finish, and no more are started
+                    tracing::error!(
+                        imported = progress.written.len(),
+                        error = %e,
+                        "Import stopped after database error"
+                    );
+                    stopped = true;
+                    progress
+                        .rejected
+                        .extend(lines.into_iter().map(|line| ImportRejection {
+                            line,
+                            error: "Not imported: database error".to_string(),
+                        }));
+                }
+            },
+        }
+
+        tracing::info!(
+            batches_completed = completed,
+            batches_total = batch_count,
+            imported = progress.written.len(),
+            "Import batch finished"
+        );
+    }
+
+    progress
+        .rejected
+        .extend(pending.map(|(line, _)| ImportRejection {
+            line,
+            error: "Not imported: import stopped after a database error".to_string(),
+        }));
+    progress
+}
+
+/// Check a per-query statement timeout against DB_TIMEOUT_OVERRIDE_MAX_MS (`0` turns
+/// the override off)
+fn check_statement_timeout(timeout_ms: u64, max_ms: u64) -> Result<()> {
+    if max_ms == 0 {
+        return Err(AppError::ValidationError(
+            "X-DB-Timeout-Ms is not enabled on this server (DB_TIMEOUT_OVERRIDE_MAX_MS=0)"
+                .to_string(),
+        ));
+    }
+    if timeout_ms == 0 || timeout_ms > max_ms {
+        return Err(AppError::ValidationError(format!(
+            "X-DB-Timeout-Ms must be between 1 and {}",
+            max_ms
+        )));
+    }
+    Ok(())
+}
+
+/// The page size a feedback query runs with: the caller's limit, else SAMPLE_SIZE for a
+/// sample, else `DEFAULT_QUERY_LIMIT`
+fn query_limit(limit: Option<i64>, sample: bool, sample_size: i64) -> i64 {
+    match limit {
+        Some(limit) => limit,
+        None if sample => sample_size,
+        None => DEFAULT_QUERY_LIMIT,
+    }
+}
+
+/// Cut `comment` so that, with the truncation marker appended, it fits in `max_len`
+/// bytes without splitting a character
+fn truncate_comment(comment: &str, max_len: usize) -> String {
+    let mut end = max_len.saturating_sub(TRUNCATION_MARKER.len());
+    while !comment.is_char_boundary(end) {
+        end -= 1;
+    }
+    format!("{}{}", &comment[..end], TRUNCATION_MARKER)
+}
+
+/// Apply a context storage policy. `roll` is a uniform sample in [0, 1): context is kept
+/// only when it falls below `sample_rate`, then capped to the first `max_keys` keys in
+/// alphabetical order. The order the client sent is not kept: context is parsed into a
+/// sorted map, and a jsonb column would not keep it either.
+fn reduce_context(
+    context: Option<JsonValue>,
+    sample_rate: Option<f64>,
+    max_keys: Option<usize>,
+    roll: f64,
+) -> Option<JsonValue> {
+    if let Some(rate) = sample_rate {
+        if roll >= rate {
+            return None;
+        }
+    }
+
+    match (context, max_keys) {
+        (Some(JsonValue::Object(map)), Some(max_keys)) if map.len() > max_keys => {
+            Some(JsonValue::Object(map.into_iter().take(max_keys).collect()))
+        }
+        (context, _) => context,
+    }
+}
+
+/// One point per day from `start`, taking counts from `points` and 0 for missing days
+fn fill_sparkline(
+    points: &[TimeseriesPoint],
+    start: chrono::NaiveDate,
+    days: i64,
+) -> Vec<SparklinePoint> {
+    let counts: HashMap<chrono::NaiveDate, i64> = points
+        .iter()
+        .map(|point| (point.date, point.total_count))
+        .collect();
+
+    (0..days)
+        .map(|offset| {
+            let date = start + chrono::Duration::days(offset);
+            SparklinePoint {
+                date,
+                count: counts.get(&date).copied().unwrap_or(0),
+            }
+        })
+        .collect()
+}
+
+/// Start and end (exclusive) of the local calendar day containing `now` in `tz`. A day
+/// whose midnight falls in a DST gap starts at the first local time after the gap.
+fn local_day_bounds(
+    now: chrono::DateTime<chrono::Utc>,
+    tz: chrono_tz::Tz,
+) -> (chrono::DateTime<chrono::Utc>, chrono::DateTime<chrono::Utc>) {
+    use chrono::TimeZone;
+
+    let start_of = |day: chrono::NaiveDate| {
+        let midnight = day.and_time(chrono::NaiveTime::MIN);
+        tz.from_local_datetime(&midnight)
+            .earliest()
+            .or_else(|| {
+                tz.from_local_datetime(&(midnight + chrono::Duration::hours(1)))
+                    .earliest()
+            })
+            .map(|start| start.with_timezone(&chrono::Utc))
+            .unwrap_or_else(|| midnight.and_utc())
+    };
+
+    let today = now.with_timezone(&tz).date_naive();
+    (start_of(today), start_of(today + chrono::Duration::days(1)))
+}
+
+/// Status of one signal whose healthy values are high: below `critical` is critical, below
+/// `warning` is a warning; no data is good
+fn health_status(value: Option<f64>, warning: f64, critical: f64) -> HealthStatus {
+    match value {
+        Some(value) if value < critical => HealthStatus::Critical,
+        Some(value) if value < warning => HealthStatus::Warning,
+        _ => HealthStatus::Good,
+    }
+}
+
+/// Mean of all ratings and thumbs, each thumbs up counting as `up_score` and each thumbs
+/// down as `down_score`; `None` when there are neither
+fn satisfaction_score(stats: &FeedbackStats, up_score: f64, down_score: f64) -> Option<f64> {
+    let count = stats.rating_count + stats.thumbs_up_count + stats.thumbs_down_count;
+    if count == 0 {
+        return None;
+    }
+    let rating_sum = stats.rating_avg.unwrap_or(0.0) * stats.rating_count as f64;
+    let thumbs_sum = stats.thumbs_up_count as f64 * up_score
+        + stats.thumbs_down_count as f64 * down_score;
+    Some((rating_sum + thumbs_sum) / count as f64)
+}
+
+/// Whether the domain of `email`, or any parent domain, is in `domains` (lowercase)
+fn is_disposable_email(email: &str, domains: &HashSet<String>) -> bool {
+    if domains.is_empty() {
+        return false;
+    }
+    let Some((_, domain)) = email.rsplit_once('@') else {
+        return false;
+    };
+
+    let domain = domain.trim().trim_end_matches('.').to_ascii_lowercase();
+    let mut candidate = domain.as_str();
+    loop {
+        if domains.contains(candidate) {
+            return true;
+        }
+        match candidate.split_once('.') {
+            Some((_, parent)) => candidate = parent,
+            None => return false,
+        }
+    }
+}
+
+/// Every way `instance` fails `validator`, as `<JSON pointer>: <message>`
+fn schema_violations(validator: &jsonschema::Validator, instance: &JsonValue) -> Vec<String> {
+    validator
+        .iter_errors(instance)
+        .map(|error| {
+            let path = error.instance_path().as_str();
+            format!("{}: {}", if path.is_empty() { "/" } else { path }, error)
+        })
+        .collect()
+}
+
+/// Move a rating into the valid range of its feedback type; types without a rating range
+/// are left alone
+fn clamp_rating(feedback_type: &FeedbackType, rating: i32) -> i32 {
+    let range = match feedback_type {
+        FeedbackType::Rating => RATING_RANGE,
+        FeedbackType::Nps => NPS_RANGE,
+        _ => return rating,
+    };
+    rating.clamp(*range.start(), *range.end())
+}
+
+/// Why an imported `created_at` is unacceptable at `now`, if it is: later than
+/// `max_future_skew_secs` ahead, or before `earliest`
+fn import_timestamp_error(
+    created_at: chrono::DateTime<chrono::Utc>,
+    now: chrono::DateTime<chrono::Utc>,
+    max_future_skew_secs: i64,
+    earliest: Option<chrono::DateTime<chrono::Utc>>,
+) -> Option<String> {
+    if created_at > now + chrono::Duration::seconds(max_future_skew_secs) {
+        return Some(format!("created_at {} is in the future", created_at.to_rfc3339()));
+    }
+
+    match earliest {
+        Some(earliest) if created_at < earliest => Some(format!(
+            "created_at {} is before the earliest allowed date {}",
+            created_at.to_rfc3339(),
+            earliest.to_rfc3339()
+        )),
+        _ => None,
+    }
+}
+
+/// Why the submission's thumbs-down reason is unacceptable, if it is, given the service's
+/// configured reasons
+fn thumbs_down_reason_error(
+    submission: &FeedbackSubmission,
+    allowed: Option<&[String]>,
+    required: bool,
+) -> Option<String> {
+    use crate::models::FeedbackType;
+
+    let is_thumbs_down = matches!(submission.feedback_type, FeedbackType::Thumbs)
+        && submission.thumbs_up == Some(false);
+
+    match (&submission.reason, allowed) {
+        (Some(_), _) if !is_thumbs_down => {
+            Some("A reason is only applicable to thumbs-down feedback".to_string())
+        }
+        (Some(_), None) => Some(format!(
+            "No thumbs-down reasons are configured for service {}",
+            submission.service
+        )),
+        (Some(reason), Some(allowed)) if !allowed.contains(reason) => {
+            Some(format!("Invalid reason (allowed: {})", allowed.join(", ")))
+        }
+        (None, Some(allowed)) if is_thumbs_down && required => Some(format!(
+            "A reason is required for thumbs-down feedback (allowed: {})",
+            allowed.join(", ")
+        )),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    // Note: These are unit tests that would require mocking the database
+    // For now, we'll add integration tests separately
+    use super::*;
+    use crate::models::StatsVersion;
+
+    #[test]
     fn test_service_creation() {
         // This is a simple test to verify the service can be created
         // In a real scenario, we'd use a mock database
     }
+
+    #[test]
+    fn test_context_sampling_drops_unsampled_context() {
+        let context = Some(serde_json::json!({"call_id": "abc"}));
+        assert_eq!(reduce_context(context.clone(), Some(0.1), None, 0.05), context);
+        assert_eq!(reduce_context(context, Some(0.1), None, 0.5), None);
+    }
+
+    #[test]
+    fn test_context_key_cap() {
+        let context = Some(serde_json::json!({"a": 1, "b": 2, "c": 3}));
+        let reduced = reduce_context(context, None, Some(2), 0.0).unwrap();
+        assert_eq!(reduced.as_object().unwrap().len(), 2);
+
+        // The alphabetically first keys are kept, whatever order they were sent in
+        let context: JsonValue = serde_json::from_str(r#"{"zone": 1, "b": 2, "a": 3}"#).unwrap();
+        let reduced = reduce_context(Some(context), None, Some(2), 0.0);
+        assert_eq!(reduced, Some(serde_json::json!({"a": 3, "b": 2})));
+    }
+
+    #[test]
+    fn test_schema_violations_report_each_location() {
+        let schema = serde_json::json!({
+            "type": "object",
+            "properties": {"call_id": {"type": "string"}, "duration": {"type": "integer"}},
+            "required": ["call_id"]
+        });
+        let validator = jsonschema::validator_for(&schema).unwrap();
+
+        assert!(schema_violations(&validator, &serde_json::json!({"call_id": "abc"})).is_empty());
+
+        let invalid = serde_json::json!({"call_id": 1, "duration": "x"});
+        let violations = schema_violations(&validator, &invalid);
+        assert_eq!(violations.len(), 2);
+        assert!(violations.iter().any(|v| v.starts_with("/call_id: ")));
+        assert!(violations.iter().any(|v| v.starts_with("/duration: ")));
+    }
+
+    #[test]
+    fn test_sparkline_fills_missing_days() {
+        let start = chrono::NaiveDate::from_ymd_opt(2024, 3, 1).unwrap();
+        let points = vec![TimeseriesPoint {
+            date: chrono::NaiveDate::from_ymd_opt(2024, 3, 2).unwrap(),
+            total_count: 7,
+            rating_avg: None,
+            thumbs_up_count: 0,
+            thumbs_down_count: 0,
+        }];
+
+        let sparkline = fill_sparkline(&points, start, 3);
+
+        let counts: Vec<i64> = sparkline.iter().map(|point| point.count).collect();
+        assert_eq!(counts, vec![0, 7, 0]);
+        assert_eq!(sparkline[0].date, start);
+    }
+
+    /// Import six rows in batches of two, one batch at a time, failing the second batch
+    async fn import_with_failing_second_batch(
+        error: fn() -> anyhow::Error,
+    ) -> ImportProgress<usize> {
+        let rows = (1..=6).map(|line| (line, line)).collect();
+        write_import_batches(rows, 2, 1, move |records: Vec<usize>| async move {
+            if records.contains(&3) {
+                Err(error())
+            } else {
+                Ok(records)
+            }
+        })
+        .await
+    }
+
+    #[tokio::test]
+    async fn test_import_goes_on_after_rejected_row() {
+        let progress = import_with_failing_second_batch(|| {
+            BatchInsertError {
+                position: 1,
+                message: "violates foreign key constraint".to_string(),
+            }
+            .into()
+        })
+        .await;
+
+        assert_eq!(progress.written, vec![1, 2, 5, 6]);
+        assert_eq!(progress.batches, 3);
+        let rejected: Vec<(usize, &str)> = progress
+            .rejected
+            .iter()
+            .map(|rejection| (rejection.line, rejection.error.as_str()))
+            .collect();
+        assert_eq!(
+            rejected,
+            vec![
+                (3, "Not imported: line 4 of the same batch was rejected"),
+                (4, "Rejected by the database"),
+            ]
+        );
+    }
+
+    #[tokio::test]
+    async fn test_import_stops_after_database_error() {
+        let progress =
+            import_with_failing_second_batch(|| anyhow::anyhow!("connection reset")).await;
+
+        // The first batch stays written; the failed and the unstarted batches are reported
+        assert_eq!(progress.written, vec![1, 2]);
+        assert_eq!(progress.batches, 2);
+        let lines: Vec<usize> = progress.rejected.iter().map(|r| r.line).collect();
+        assert_eq!(lines, vec![3, 4, 5, 6]);
+        assert!(progress.rejected[2].error.contains("import stopped"));
+    }
+
+    /// A minimal IPv4 GeoIP country database placing 1.2.3.0/24 in France: a search tree
+    /// of one node per prefix bit, then the data section and the metadata
+    fn geoip_test_database() -> Vec<u8> {
+        let node_count: u32 = 24;
+        let not_found = node_count;
+        let data = node_count + 16;
+        let network = u32::from(std::net::Ipv4Addr::new(1, 2, 3, 0));
+
+        let mut db = Vec::new();
+        for depth in 0..node_count {
+            let next = if depth + 1 < node_count { depth + 1 } else { data };
+            let (left, right) = if network & (1 << (31 - depth)) == 0 {
+                (next, not_found)
+            } else {
+                (not_found, next)
+            };
+            db.extend_from_slice(&left.to_be_bytes()[1..]);
+            db.extend_from_slice(&right.to_be_bytes()[1..]);
+        }
+        db.extend_from_slice(&[0; 16]);
+
+        // {"country": {"iso_code": "FR"}}
+        db.extend_from_slice(b"\xe1\x47country\xe1\x48iso_code\x42FR");
+
+        db.extend_from_slice(b"\xab\xcd\xefMaxMind.com");
+        db.extend_from_slice(b"\xe9");
+        db.extend_from_slice(b"\x5bbinary_format_major_version\xa1\x02");
+        db.extend_from_slice(b"\x5bbinary_format_minor_version\xa0");
+        db.extend_from_slice(b"\x4bbuild_epoch\x00\x02");
+        db.extend_from_slice(b"\x4ddatabase_type\x44Test");
+        db.extend_from_slice(b"\x4bdescription\xe0");
+        db.extend_from_slice(b"\x4aip_version\xa1\x04");
+        db.extend_from_slice(b"\x49languages\x00\x04");
+        db.extend_from_slice(b"\x4anode_count\xc1\x18");
+        db.extend_from_slice(b"\x4brecord_size\xa1\x18");
+        db
+    }
+
+    #[test]
+    fn test_lookup_country() {
+        let geoip = maxminddb::Reader::from_source(geoip_test_database()).unwrap();
+
+        assert_eq!(lookup_country(&geoip, "1.2.3.4".parse().unwrap()), Some("FR".to_string()));
+        assert_eq!(lookup_country(&geoip, "1.2.4.4".parse().unwrap()), None);
+    }
+
+    #[test]
+    fn test_import_timestamp_bounds() {
+        let now = chrono::DateTime::parse_from_rfc3339("2024-06-01T12:00:00Z")
+            .unwrap()
+            .with_timezone(&chrono::Utc);
+        let first_day = now - chrono::Duration::days(365);
+        let earliest = Some(first_day);
+
+        assert_eq!(import_timestamp_error(now, now, 300, earliest), None);
+        // Within the clock skew, and exactly at the earliest date, are accepted
+        let skewed = now + chrono::Duration::seconds(300);
+        assert_eq!(import_timestamp_error(skewed, now, 300, earliest), None);
+        assert_eq!(import_timestamp_error(first_day, now, 300, earliest), None);
+
+        let future = now + chrono::Duration::seconds(301);
+        let error = import_timestamp_error(future, now, 300, earliest).unwrap();
+        assert_eq!(error, "created_at 2024-06-01T12:05:01+00:00 is in the future");
+
+        let old = now - chrono::Duration::days(366);
+        let error = import_timestamp_error(old, now, 300, earliest).unwrap();
+        assert!(error.contains("is before the earliest allowed date 2023-06-02T12:00:00+00:00"));
+        assert_eq!(import_timestamp_error(old, now, 300, None), None);
+    }
+
+    #[test]
+    fn test_statement_timeout_bounds() {
+        assert!(check_statement_timeout(1, 60000).is_ok());
+        assert!(check_statement_timeout(60000, 60000).is_ok());
+        for (timeout_ms, max_ms) in [(0, 60000), (60001, 60000)] {
+            match check_statement_timeout(timeout_ms, max_ms) {
+                Err(AppError::ValidationError(message)) => {
+                    assert_eq!(message, "X-DB-Timeout-Ms must be between 1 and 60000")
+                }
+                other => panic!("expected a validation error, got {:?}", other),
+            }
+        }
+        match check_statement_timeout(1000, 0) {
+            Err(AppError::ValidationError(message)) => assert!(message.contains("not enabled")),
+            other => panic!("expected a validation error, got {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_query_limit_defaults() {
+        assert_eq!(query_limit(Some(20), true, 5), 20);
+        assert_eq!(query_limit(None, true, 5), 5);
+        assert_eq!(query_limit(None, false, 5), DEFAULT_QUERY_LIMIT);
+    }
+
+    #[test]
+    fn test_truncate_comment_fits_limit() {
+        let truncated = truncate_comment(&"é".repeat(3000), MAX_COMMENT_LENGTH);
+        assert!(truncated.len() <= MAX_COMMENT_LENGTH);
+        assert!(truncated.ends_with(TRUNCATION_MARKER));
+    }
+
+    #[test]
+    fn test_clamp_rating_low() {
+        assert_eq!(clamp_rating(&FeedbackType::Rating, 0), 1);
+        assert_eq!(clamp_rating(&FeedbackType::Nps, -3), 0);
+    }
+
+    #[test]
+    fn test_clamp_rating_high() {
+        assert_eq!(clamp_rating(&FeedbackType::Rating, 6), 5);
+        assert_eq!(clamp_rating(&FeedbackType::Nps, 11), 10);
+        assert_eq!(clamp_rating(&FeedbackType::Rating, 4), 4);
+    }
+
+    #[test]
+    fn test_out_of_range_rating_rejected_by_default() {
+        // What Config::from_env uses when RATING_OUT_OF_RANGE is unset
+        assert_eq!(RatingOutOfRange::default(), RatingOutOfRange::Reject);
+        assert_eq!(
+            "reject".parse::<RatingOutOfRange>().unwrap(),
+            RatingOutOfRange::Reject
+        );
+        let submission = FeedbackSubmission {
+            service: "test-service".to_string(),
+            feedback_type: FeedbackType::Rating,
+            rating: Some(6),
+            thumbs_up: None,
+            comment: None,
+            context: None,
+            parent_id: None,
+            language: None,
+            reason: None,
+            custom_type: None,
+            on_behalf_of: None,
+            country: None,
+        };
+        assert!(submission.validate().is_err());
+    }
+
+    #[test]
+    fn test_thumbs_down_reason_rules() {
+        use crate::models::FeedbackType;
+
+        let submission = |thumbs_up, reason: Option<&str>| FeedbackSubmission {
+            service: "chatbot".to_string(),
+            feedback_type: FeedbackType::Thumbs,
+            rating: None,
+            thumbs_up: Some(thumbs_up),
+            comment: None,
+            context: None,
+            parent_id: None,
+            language: None,
+            reason: reason.map(str::to_string),
+            custom_type: None,
+            on_behalf_of: None,
+            country: None,
+        };
+        let allowed = vec!["inaccurate".to_string(), "slow".to_string()];
+        let allowed = Some(allowed.as_slice());
+        let error = thumbs_down_reason_error;
+
+        assert!(error(&submission(false, Some("slow")), allowed, true).is_none());
+        assert!(error(&submission(false, None), allowed, false).is_none());
+        assert!(error(&submission(false, None), allowed, true).is_some());
+        assert!(error(&submission(false, Some("rude")), allowed, false).is_some());
+        assert!(error(&submission(true, Some("slow")), allowed, false).is_some());
+        assert!(error(&submission(false, Some("slow")), None, false).is_some());
+        // Without a taxonomy, a reason is never required
+        assert!(error(&submission(false, None), None, true).is_none());
+    }
+
+    #[test]
+    fn test_health_status_thresholds() {
+        assert_eq!(health_status(Some(0.9), 0.7, 0.5), HealthStatus::Good);
+        assert_eq!(health_status(Some(0.7), 0.7, 0.5), HealthStatus::Good);
+        assert_eq!(health_status(Some(0.6), 0.7, 0.5), HealthStatus::Warning);
+        assert_eq!(health_status(Some(0.4), 0.7, 0.5), HealthStatus::Critical);
+        assert_eq!(health_status(None, 0.7, 0.5), HealthStatus::Good);
+        // The worst signal decides
+        assert_eq!(HealthStatus::Good.max(HealthStatus::Critical), HealthStatus::Critical);
+    }
+
+    #[test]
+    fn test_satisfaction_score() {
+        let mut stats = FeedbackStats {
+            service: "test".to_string(),
+            total_count: 6,
+            rating_avg: Some(4.0),
+            rating_count: 2,
+            thumbs_up_count: 3,
+            thumbs_down_count: 1,
+            thumbs_up_ratio: Some(0.75),
+            comment_count: 0,
+            satisfaction_score: None,
+            stats_version: StatsVersion::default(),
+        };
+        // (4 * 2 + 5 * 3 + 1 * 1) / 6
+        assert_eq!(satisfaction_score(&stats, 5.0, 1.0), Some(4.0));
+        // (4 * 2 + 1 * 3 + 0 * 1) / 6
+        assert_eq!(satisfaction_score(&stats, 1.0, 0.0), Some(11.0 / 6.0));
+
+        stats.rating_avg = None;
+        stats.rating_count = 0;
+        stats.thumbs_up_count = 0;
+        stats.thumbs_down_count = 0;
+        assert_eq!(satisfaction_score(&stats, 5.0, 1.0), None);
+    }
+
+    #[test]
+    fn test_local_day_bounds() {
+        let at = |s: &str| s.parse::<chrono::DateTime<chrono::Utc>>().unwrap();
+
+        // 23:30 UTC is already the next day in Paris (UTC+1 in winter)
+        let (from, to) = local_day_bounds(at("2024-01-15T23:30:00Z"), chrono_tz::Europe::Paris);
+        assert_eq!(from, at("2024-01-15T23:00:00Z"));
+        assert_eq!(to, at("2024-01-16T23:00:00Z"));
+
+        let (from, to) = local_day_bounds(at("2024-01-15T23:30:00Z"), chrono_tz::UTC);
+        assert_eq!(from, at("2024-01-15T00:00:00Z"));
+        assert_eq!(to, at("2024-01-16T00:00:00Z"));
+
+        // The day clocks go forward is 23 hours long
+        let (from, to) = local_day_bounds(at("2024-03-31T12:00:00Z"), chrono_tz::Europe::Paris);
+        assert_eq!(to - from, chrono::Duration::hours(23));
+    }
+
+    #[test]
+    fn test_disposable_email_domains() {
+        let domains: HashSet<String> = ["mailinator.com".to_string()].into();
+
+        assert!(is_disposable_email("spam@mailinator.com", &domains));
+        assert!(is_disposable_email("spam@Eu.Mailinator.COM", &domains));
+        assert!(!is_disposable_email("user@example.com", &domains));
+        assert!(!is_disposable_email("user@notmailinator.com", &domains));
+        assert!(!is_disposable_email("spam@mailinator.com", &HashSet::new()));
+    }
 }
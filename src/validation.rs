@@ -1,74 +1,272 @@
 use crate::error::{AppError, Result};
-use crate::models::{FeedbackQuery, FeedbackSubmission, FeedbackType};
+use crate::models::{
+    FeedbackQuery, FeedbackSubmission, FeedbackType, FeedbackTypeSchema, RatingRange,
+    RecentCommentsQuery, SparklineQuery, TimeseriesQuery, TodayStatsQuery, WordCloudQuery,
+};
+use sqlx::types::JsonValue;
+use std::ops::RangeInclusive;
+
+/// Accepted star ratings for `Rating` feedback
+pub const RATING_RANGE: RangeInclusive<i32> = 1..=5;
+/// Accepted scores for `Nps` feedback
+pub const NPS_RANGE: RangeInclusive<i32> = 0..=10;
+/// Accepted sparkline lengths, in days
+pub const SPARKLINE_DAYS_RANGE: RangeInclusive<i64> = 1..=365;
+/// Accepted number of words in a word cloud
+pub const WORD_CLOUD_LIMIT_RANGE: RangeInclusive<i64> = 1..=200;
+/// Accepted number of recent comments
+pub const RECENT_COMMENTS_LIMIT_RANGE: RangeInclusive<i64> = 1..=100;
+/// Longest accepted language tag (the size of the `language` column)
+pub const MAX_LANGUAGE_TAG_LENGTH: usize = 35;
+/// Longest accepted custom type name (the size of the `custom_type` column)
+pub const MAX_CUSTOM_TYPE_LENGTH: usize = 64;
+/// Maximum comment length, in bytes
+pub const MAX_COMMENT_LENGTH: usize = 5000;
+/// Longest accepted full-text search, in bytes
+pub const MAX_SEARCH_LENGTH: usize = 200;
+/// Longest context key usable in filters and indexes (keeps index names under 63 bytes)
+pub const MAX_CONTEXT_KEY_LENGTH: usize = 40;
 
 pub trait Validate {
     fn validate(&self) -> Result<()>;
 }
 
+/// Build the validation error for a rejected submission, logging at debug level which
+/// field failed and a sanitized, truncated copy of its value to help debug client
+/// integrations. Comment text is reduced to its length and a short prefix.
+pub(crate) fn reject_submission(
+    submission: &FeedbackSubmission,
+    field: &'static str,
+    message: String,
+) -> AppError {
+    let value = match field {
+        "service" => sanitize_for_log(&submission.service, 64),
+        "rating" => format!("{:?}", submission.rating),
+        "thumbs_up" => format!("{:?}", submission.thumbs_up),
+        "language" => sanitize_for_log(submission.language.as_deref().unwrap_or_default(), 40),
+        "reason" => sanitize_for_log(submission.reason.as_deref().unwrap_or_default(), 64),
+        "custom_type" => {
+            sanitize_for_log(submission.custom_type.as_deref().unwrap_or_default(), 64)
+        }
+        "context" => match &submission.context {
+            Some(JsonValue::Object(context)) => format!("{} keys", context.len()),
+            Some(_) => "non-object".to_string(),
+            None => "None".to_string(),
+        },
+        "comment" => match &submission.comment {
+            Some(comment) => format!(
+                "{} chars: {}",
+                comment.chars().count(),
+                sanitize_for_log(comment, 32)
+            ),
+            None => "None".to_string(),
+        },
+        _ => String::new(),
+    };
+
+    tracing::debug!(
+        feedback_type = ?submission.feedback_type,
+        service = %sanitize_for_log(&submission.service, 64),
+        field = field,
+        value = %value,
+        reason = %message,
+        "Feedback submission failed validation"
+    );
+
+    AppError::ValidationError(message)
+}
+
+/// Strip control characters and cut to `max_chars`, marking the cut with an ellipsis
+pub(crate) fn sanitize_for_log(value: &str, max_chars: usize) -> String {
+    let mut sanitized: String = value
+        .chars()
+        .filter(|c| !c.is_control())
+        .take(max_chars)
+        .collect();
+    if value.chars().filter(|c| !c.is_control()).count() > max_chars {
+        sanitized.push('…');
+    }
+    sanitized
+}
+
+/// Whether `tag` is well-formed BCP-47: a 2-3 or 5-8 letter primary language subtag
+/// followed by 1-8 character alphanumeric subtags (script, region, variants...)
+pub fn is_valid_language_tag(tag: &str) -> bool {
+    if tag.len() > MAX_LANGUAGE_TAG_LENGTH {
+        return false;
+    }
+
+    let mut subtags = tag.split('-');
+    let primary_ok = subtags.next().is_some_and(|primary| {
+        matches!(primary.len(), 2..=3 | 5..=8) && primary.chars().all(|c| c.is_ascii_alphabetic())
+    });
+
+    primary_ok
+        && subtags.all(|subtag| {
+            (1..=8).contains(&subtag.len()) && subtag.chars().all(|c| c.is_ascii_alphanumeric())
+        })
+}
+
+/// Whether `key` can be used as a context filter or index key: ASCII letters, digits
+/// and underscores only, since it is written into SQL as a literal
+pub fn is_valid_context_key(key: &str) -> bool {
+    !key.is_empty()
+        && key.len() <= MAX_CONTEXT_KEY_LENGTH
+        && key.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Describe every built-in feedback type using the same rules `Validate` enforces
+pub fn feedback_type_schemas() -> Vec<FeedbackTypeSchema> {
+    let schema = |feedback_type, required: &[&str], range: Option<&RangeInclusive<i32>>| {
+        FeedbackTypeSchema {
+            feedback_type,
+            custom_type: None,
+            required_fields: required.iter().map(|f| f.to_string()).collect(),
+            rating_range: range.map(|r| RatingRange {
+                min: *r.start(),
+                max: *r.end(),
+            }),
+            max_comment_length: MAX_COMMENT_LENGTH,
+        }
+    };
+
+    vec![
+        schema(FeedbackType::Rating, &["service", "rating"], Some(&RATING_RANGE)),
+        schema(FeedbackType::Thumbs, &["service", "thumbs_up"], None),
+        schema(FeedbackType::Comment, &["service", "comment"], None),
+        schema(FeedbackType::Nps, &["service", "rating"], Some(&NPS_RANGE)),
+    ]
+}
+
 impl Validate for FeedbackSubmission {
     fn validate(&self) -> Result<()> {
         // Validate service name
         if self.service.is_empty() {
-            return Err(AppError::ValidationError(
+            return Err(reject_submission(
+                self,
+                "service",
                 "Service name cannot be empty".to_string(),
             ));
         }
 
         if self.service.len() > 100 {
-            return Err(AppError::ValidationError(
+            return Err(reject_submission(
+                self,
+                "service",
                 "Service name too long (max 100 characters)".to_string(),
             ));
         }
 
+        if let Some(language) = &self.language {
+            if !is_valid_language_tag(language) {
+                return Err(reject_submission(
+                    self,
+                    "language",
+                    format!(
+                        "Invalid language '{}' (expected a BCP-47 tag such as fr or en-GB)",
+                        sanitize_for_log(language, 40)
+                    ),
+                ));
+            }
+        }
+
         // Validate rating based on feedback type
         match self.feedback_type {
             FeedbackType::Rating => {
                 if let Some(rating) = self.rating {
-                    if !(1..=5).contains(&rating) {
-                        return Err(AppError::ValidationError(
-                            "Rating must be between 1 and 5".to_string(),
+                    if !RATING_RANGE.contains(&rating) {
+                        return Err(reject_submission(
+                            self,
+                            "rating",
+                            format!(
+                                "Rating must be between {} and {}",
+                                RATING_RANGE.start(),
+                                RATING_RANGE.end()
+                            ),
                         ));
                     }
                 } else {
-                    return Err(AppError::ValidationError(
+                    return Err(reject_submission(
+                        self,
+                        "rating",
                         "Rating is required for Rating feedback type".to_string(),
                     ));
                 }
             }
             FeedbackType::Nps => {
                 if let Some(rating) = self.rating {
-                    if !(0..=10).contains(&rating) {
-                        return Err(AppError::ValidationError(
-                            "NPS score must be between 0 and 10".to_string(),
+                    if !NPS_RANGE.contains(&rating) {
+                        return Err(reject_submission(
+                            self,
+                            "rating",
+                            format!(
+                                "NPS score must be between {} and {}",
+                                NPS_RANGE.start(),
+                                NPS_RANGE.end()
+                            ),
                         ));
                     }
                 } else {
-                    return Err(AppError::ValidationError(
+                    return Err(reject_submission(
+                        self,
+                        "rating",
                         "Rating is required for NPS feedback type".to_string(),
                     ));
                 }
             }
             FeedbackType::Thumbs => {
                 if self.thumbs_up.is_none() {
-                    return Err(AppError::ValidationError(
+                    return Err(reject_submission(
+                        self,
+                        "thumbs_up",
                         "thumbs_up is required for Thumbs feedback type".to_string(),
                     ));
                 }
             }
             FeedbackType::Comment => {
                 if self.comment.is_none() || self.comment.as_ref().unwrap().is_empty() {
-                    return Err(AppError::ValidationError(
+                    return Err(reject_submission(
+                        self,
+                        "comment",
                         "Comment is required for Comment feedback type".to_string(),
                     ));
                 }
             }
+            // Checked against CUSTOM_FEEDBACK_TYPES by the service
+            FeedbackType::Custom => match &self.custom_type {
+                None => {
+                    return Err(reject_submission(
+                        self,
+                        "custom_type",
+                        "custom_type is required for Custom feedback type".to_string(),
+                    ));
+                }
+                Some(custom_type) if custom_type.len() > MAX_CUSTOM_TYPE_LENGTH => {
+                    return Err(reject_submission(
+                        self,
+                        "custom_type",
+                        format!("custom_type too long (max {} characters)", MAX_CUSTOM_TYPE_LENGTH),
+                    ));
+                }
+                Some(_) => {}
+            },
+        }
+
+        if self.custom_type.is_some() && !matches!(self.feedback_type, FeedbackType::Custom) {
+            return Err(reject_submission(
+                self,
+                "custom_type",
+                "custom_type only applies to Custom feedback type".to_string(),
+            ));
         }
 
         // Validate comment length if present
         if let Some(comment) = &self.comment {
-            if comment.len() > 5000 {
-                return Err(AppError::ValidationError(
-                    "Comment too long (max 5000 characters)".to_string(),
+            if comment.len() > MAX_COMMENT_LENGTH {
+                return Err(reject_submission(
+                    self,
+                    "comment",
+                    format!("Comment too long (max {} characters)", MAX_COMMENT_LENGTH),
                 ));
             }
         }
@@ -81,7 +279,7 @@ impl Validate for FeedbackQuery {
     fn validate(&self) -> Result<()> {
         // Validate limit
         if let Some(limit) = self.limit {
-            if limit < 1 || limit > 1000 {
+            if !(1..=1000).contains(&limit) {
                 return Err(AppError::ValidationError(
                     "limit must be between 1 and 1000".to_string(),
                 ));
@@ -95,6 +293,12 @@ impl Validate for FeedbackQuery {
                     "offset must be >= 0".to_string(),
                 ));
             }
+            // Each sample is drawn afresh, so there are no pages to step through
+            if self.sample {
+                return Err(AppError::ValidationError(
+                    "offset cannot be combined with sample".to_string(),
+                ));
+            }
         }
 
         // Validate date range
@@ -106,6 +310,21 @@ impl Validate for FeedbackQuery {
             }
         }
 
+        // Validate update range
+        if let (Some(from), Some(to)) = (self.updated_from, self.updated_to) {
+            if from > to {
+                return Err(AppError::ValidationError(
+                    "updated_from must be before updated_to".to_string(),
+                ));
+            }
+        }
+
+        if self.sample && self.order.is_some() {
+            return Err(AppError::ValidationError(
+                "order cannot be combined with sample".to_string(),
+            ));
+        }
+
         // Validate service name length if present
         if let Some(service) = &self.service {
             if service.is_empty() {
@@ -120,6 +339,131 @@ impl Validate for FeedbackQuery {
             }
         }
 
+        match (&self.context_key, &self.context_value) {
+            (Some(key), Some(_)) if !is_valid_context_key(key) => {
+                return Err(AppError::ValidationError(format!(
+                    "context_key must be 1-{} letters, digits or underscores",
+                    MAX_CONTEXT_KEY_LENGTH
+                )));
+            }
+            (Some(_), None) | (None, Some(_)) => {
+                return Err(AppError::ValidationError(
+                    "context_key and context_value must be given together".to_string(),
+                ));
+            }
+            _ => {}
+        }
+
+        if let Some(search) = &self.search {
+            if search.trim().is_empty() {
+                return Err(AppError::ValidationError(
+                    "search cannot be empty".to_string(),
+                ));
+            }
+            if search.len() > MAX_SEARCH_LENGTH {
+                return Err(AppError::ValidationError(format!(
+                    "search too long (max {} bytes)",
+                    MAX_SEARCH_LENGTH
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Validate for TodayStatsQuery {
+    fn validate(&self) -> Result<()> {
+        if let Some(tz) = &self.tz {
+            if tz.parse::<chrono_tz::Tz>().is_err() {
+                return Err(AppError::ValidationError(format!(
+                    "Unknown timezone '{}' (expected an IANA name such as Europe/Paris)",
+                    tz
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Validate for TimeseriesQuery {
+    fn validate(&self) -> Result<()> {
+        // Validate date range
+        if let (Some(from), Some(to)) = (self.from_date, self.to_date) {
+            if from > to {
+                return Err(AppError::ValidationError(
+                    "from_date must be before to_date".to_string(),
+                ));
+            }
+        }
+
+        // Validate timezone is a known IANA name
+        if let Some(timezone) = &self.timezone {
+            if timezone.parse::<chrono_tz::Tz>().is_err() {
+                return Err(AppError::ValidationError(format!(
+                    "Unknown timezone '{}' (expected an IANA name such as Europe/Paris)",
+                    timezone
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Validate for SparklineQuery {
+    fn validate(&self) -> Result<()> {
+        if let Some(days) = self.days {
+            if !SPARKLINE_DAYS_RANGE.contains(&days) {
+                return Err(AppError::ValidationError(format!(
+                    "days must be between {} and {}",
+                    SPARKLINE_DAYS_RANGE.start(),
+                    SPARKLINE_DAYS_RANGE.end()
+                )));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Validate for WordCloudQuery {
+    fn validate(&self) -> Result<()> {
+        if let Some(limit) = self.limit {
+            if !WORD_CLOUD_LIMIT_RANGE.contains(&limit) {
+                return Err(AppError::ValidationError(format!(
+                    "limit must be between {} and {}",
+                    WORD_CLOUD_LIMIT_RANGE.start(),
+                    WORD_CLOUD_LIMIT_RANGE.end()
+                )));
+            }
+        }
+
+        if let (Some(from), Some(to)) = (self.from_date, self.to_date) {
+            if from > to {
+                return Err(AppError::ValidationError(
+                    "from_date must be before to_date".to_string(),
+                ));
+            }
+        }
+
+        Ok(())
+    }
+}
+
+impl Validate for RecentCommentsQuery {
+    fn validate(&self) -> Result<()> {
+        if let Some(limit) = self.limit {
+            if !RECENT_COMMENTS_LIMIT_RANGE.contains(&limit) {
+                return Err(AppError::ValidationError(format!(
+                    "limit must be between {} and {}",
+                    RECENT_COMMENTS_LIMIT_RANGE.start(),
+                    RECENT_COMMENTS_LIMIT_RANGE.end()
+                )));
+            }
+        }
+
         Ok(())
     }
 }
@@ -137,6 +481,12 @@ mod tests {
             thumbs_up: None,
             comment: None,
             context: None,
+            parent_id: None,
+            language: None,
+            reason: None,
+            custom_type: None,
+            on_behalf_of: None,
+            country: None,
         };
         assert!(feedback.validate().is_ok());
     }
@@ -150,6 +500,12 @@ mod tests {
             thumbs_up: None,
             comment: None,
             context: None,
+            parent_id: None,
+            language: None,
+            reason: None,
+            custom_type: None,
+            on_behalf_of: None,
+            country: None,
         };
         assert!(feedback.validate().is_err());
     }
@@ -163,6 +519,12 @@ mod tests {
             thumbs_up: None,
             comment: None,
             context: None,
+            parent_id: None,
+            language: None,
+            reason: None,
+            custom_type: None,
+            on_behalf_of: None,
+            country: None,
         };
         assert!(feedback.validate().is_err());
     }
@@ -176,6 +538,12 @@ mod tests {
             thumbs_up: None,
             comment: None,
             context: None,
+            parent_id: None,
+            language: None,
+            reason: None,
+            custom_type: None,
+            on_behalf_of: None,
+            country: None,
         };
         assert!(feedback.validate().is_ok());
     }
@@ -189,6 +557,12 @@ mod tests {
             thumbs_up: None,
             comment: None,
             context: None,
+            parent_id: None,
+            language: None,
+            reason: None,
+            custom_type: None,
+            on_behalf_of: None,
+            country: None,
         };
         assert!(feedback.validate().is_err());
     }
@@ -202,6 +576,12 @@ mod tests {
             thumbs_up: Some(true),
             comment: None,
             context: None,
+            parent_id: None,
+            language: None,
+            reason: None,
+            custom_type: None,
+            on_behalf_of: None,
+            country: None,
         };
         assert!(feedback.validate().is_ok());
     }
@@ -215,6 +595,12 @@ mod tests {
             thumbs_up: None,
             comment: None,
             context: None,
+            parent_id: None,
+            language: None,
+            reason: None,
+            custom_type: None,
+            on_behalf_of: None,
+            country: None,
         };
         assert!(feedback.validate().is_err());
     }
@@ -228,6 +614,12 @@ mod tests {
             thumbs_up: None,
             comment: None,
             context: None,
+            parent_id: None,
+            language: None,
+            reason: None,
+            custom_type: None,
+            on_behalf_of: None,
+            country: None,
         };
         assert!(feedback.validate().is_err());
     }
@@ -241,7 +633,138 @@ mod tests {
             thumbs_up: None,
             comment: Some("x".repeat(5001)),
             context: None,
+            parent_id: None,
+            language: None,
+            reason: None,
+            custom_type: None,
+            on_behalf_of: None,
+            country: None,
         };
         assert!(feedback.validate().is_err());
     }
+
+    #[test]
+    fn test_valid_timeseries_timezone() {
+        let query = TimeseriesQuery {
+            service: None,
+            from_date: None,
+            to_date: None,
+            timezone: Some("Europe/Paris".to_string()),
+        };
+        assert!(query.validate().is_ok());
+    }
+
+    #[test]
+    fn test_invalid_timeseries_timezone() {
+        let query = TimeseriesQuery {
+            service: None,
+            from_date: None,
+            to_date: None,
+            timezone: Some("Mars/Olympus_Mons".to_string()),
+        };
+        assert!(query.validate().is_err());
+    }
+
+    #[test]
+    fn test_feedback_type_schemas_match_validation() {
+        for schema in feedback_type_schemas() {
+            let Some(range) = schema.rating_range else { continue };
+            let submission = |rating| FeedbackSubmission {
+                service: "test-service".to_string(),
+                feedback_type: schema.feedback_type.clone(),
+                rating: Some(rating),
+                thumbs_up: None,
+                comment: None,
+                context: None,
+                parent_id: None,
+                language: None,
+                reason: None,
+                custom_type: None,
+                on_behalf_of: None,
+                country: None,
+            };
+            assert!(submission(range.min).validate().is_ok());
+            assert!(submission(range.max).validate().is_ok());
+            assert!(submission(range.min - 1).validate().is_err());
+            assert!(submission(range.max + 1).validate().is_err());
+        }
+    }
+
+    #[test]
+    fn test_custom_type_only_with_custom_feedback() {
+        let submission = |feedback_type, custom_type: Option<&str>| FeedbackSubmission {
+            service: "test-service".to_string(),
+            feedback_type,
+            rating: Some(3),
+            thumbs_up: None,
+            comment: None,
+            context: None,
+            parent_id: None,
+            language: None,
+            reason: None,
+            custom_type: custom_type.map(str::to_string),
+            on_behalf_of: None,
+            country: None,
+        };
+
+        assert!(submission(FeedbackType::Custom, Some("effort")).validate().is_ok());
+        assert!(submission(FeedbackType::Custom, None).validate().is_err());
+        assert!(submission(FeedbackType::Rating, Some("effort")).validate().is_err());
+    }
+
+    #[test]
+    fn test_search_bounds() {
+        let query = |search: &str| FeedbackQuery {
+            service: None,
+            feedback_type: None,
+            user_id: None,
+            language: None,
+            reason: None,
+            country: None,
+            custom_type: None,
+            search: Some(search.to_string()),
+            context_key: None,
+            context_value: None,
+            from_date: None,
+            to_date: None,
+            updated_from: None,
+            updated_to: None,
+            order: None,
+            limit: None,
+            offset: None,
+            count: false,
+            sample: false,
+            text_search_config: None,
+        };
+
+        assert!(query("crash login").validate().is_ok());
+        assert!(query("  ").validate().is_err());
+        assert!(query(&"a".repeat(MAX_SEARCH_LENGTH + 1)).validate().is_err());
+    }
+
+    #[test]
+    fn test_context_keys() {
+        for key in ["call_id", "page", "A1"] {
+            assert!(is_valid_context_key(key), "{}", key);
+        }
+        for key in ["", "call-id", "x'y", "a b", &"k".repeat(MAX_CONTEXT_KEY_LENGTH + 1)] {
+            assert!(!is_valid_context_key(key), "{}", key);
+        }
+    }
+
+    #[test]
+    fn test_language_tags() {
+        for tag in ["fr", "en-GB", "zh-Hant-TW", "es-419"] {
+            assert!(is_valid_language_tag(tag), "{}", tag);
+        }
+        for tag in ["", "f", "english!", "en--GB", "en-", "fr_FR"] {
+            assert!(!is_valid_language_tag(tag), "{}", tag);
+        }
+    }
+
+    #[test]
+    fn test_sanitize_for_log_strips_and_truncates() {
+        assert_eq!(sanitize_for_log("ab\ncd", 10), "abcd");
+        assert_eq!(sanitize_for_log("abcdef", 3), "abc…");
+    }
 }